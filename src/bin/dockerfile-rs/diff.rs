@@ -0,0 +1,299 @@
+//! Comparison of instructions of two Dockerfiles
+//!
+//! Parsed instructions of both files are matched by their longest common subsequence, so layout,
+//! line continuations, keyword casing and comments don't matter. Shell commands are compared with
+//! runs of unquoted whitespace as a single space, because line continuations leave indentation
+//! of the next line in them.
+
+use dockerfile_rs::{CommandForm, Directive, DockerFile, From, HealthCheck, InstructionKind};
+use std::fmt::{self, Display};
+
+#[derive(Clone, Copy)]
+enum Key<'a> {
+    Directive(&'a Directive),
+    From(&'a From),
+    Instruction(&'a InstructionKind),
+}
+
+impl PartialEq for Key<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Key::Directive(a), Key::Directive(b)) => a == b,
+            (Key::From(a), Key::From(b)) => a == b,
+            (Key::Instruction(a), Key::Instruction(b)) => same_instruction(a, b),
+            _ => false,
+        }
+    }
+}
+
+fn same_instruction(a: &InstructionKind, b: &InstructionKind) -> bool {
+    match (a, b) {
+        (InstructionKind::Run(a), InstructionKind::Run(b)) => {
+            a.mounts == b.mounts
+                && a.network == b.network
+                && a.security == b.security
+                && same_command(&a.command, &b.command)
+        }
+        (InstructionKind::Cmd(a), InstructionKind::Cmd(b)) => same_command(&a.command, &b.command),
+        (InstructionKind::EntryPoint(a), InstructionKind::EntryPoint(b)) => {
+            same_command(&a.command, &b.command)
+        }
+        (
+            InstructionKind::HealthCheck(HealthCheck::Check {
+                cmd,
+                interval,
+                timeout,
+                start_period,
+                start_interval,
+                retries,
+            }),
+            InstructionKind::HealthCheck(HealthCheck::Check {
+                cmd: other_cmd,
+                interval: other_interval,
+                timeout: other_timeout,
+                start_period: other_start_period,
+                start_interval: other_start_interval,
+                retries: other_retries,
+            }),
+        ) => {
+            (interval, timeout, start_period, start_interval, retries)
+                == (
+                    other_interval,
+                    other_timeout,
+                    other_start_period,
+                    other_start_interval,
+                    other_retries,
+                )
+                && same_command(&cmd.command, &other_cmd.command)
+        }
+        (InstructionKind::OnBuild(a), InstructionKind::OnBuild(b)) => {
+            same_instruction(a.instruction(), b.instruction())
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn same_command(a: &CommandForm, b: &CommandForm) -> bool {
+    match (a, b) {
+        (CommandForm::Shell(a), CommandForm::Shell(b)) => collapse(a) == collapse(b),
+        (a, b) => a == b,
+    }
+}
+
+/// Replaces runs of whitespace outside of quotes with a single space
+fn collapse(command: &str) -> String {
+    let mut result = String::with_capacity(command.len());
+    let mut quote = None;
+    let mut space = false;
+    let mut chars = command.trim().chars();
+    while let Some(c) = chars.next() {
+        if quote.is_none() && c.is_whitespace() {
+            space = true;
+            continue;
+        }
+        if space {
+            result.push(' ');
+            space = false;
+        }
+        result.push(c);
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some('"'), '\\') | (None, '\\') => result.extend(chars.next()),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// Rendered instruction, `FROM` of its stage and parsed instruction it's compared by
+struct Item<'a> {
+    stage: Option<String>,
+    text: String,
+    key: Key<'a>,
+}
+
+impl<'a> Item<'a> {
+    fn new(stage: Option<String>, key: Key<'a>) -> Self {
+        let text = match key {
+            Key::Directive(directive) => directive.to_string(),
+            Key::From(from) => from.to_string(),
+            Key::Instruction(instruction) => instruction.to_string(),
+        };
+        Item { stage, text, key }
+    }
+}
+
+fn items(docker_file: &DockerFile) -> Vec<Item<'_>> {
+    let is_comment =
+        |instruction: &&InstructionKind| matches!(instruction, InstructionKind::Comment(_));
+
+    let mut items: Vec<Item> = docker_file
+        .directives()
+        .iter()
+        .map(Key::Directive)
+        .chain(
+            docker_file
+                .global()
+                .iter()
+                .filter(|i| !is_comment(i))
+                .map(Key::Instruction),
+        )
+        .map(|key| Item::new(None, key))
+        .collect();
+    for stage in docker_file.stages() {
+        let base = stage.base().to_string();
+        items.push(Item::new(None, Key::From(stage.base())));
+        items.extend(
+            stage
+                .instructions()
+                .iter()
+                .filter(|i| !is_comment(i))
+                .map(|i| Item::new(Some(base.clone()), Key::Instruction(i))),
+        );
+    }
+    items
+}
+
+enum Line {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Lines that differ, with `FROM` of their stage as context
+pub struct Changes(Vec<Line>);
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|line| matches!(line, Line::Context(_)))
+    }
+}
+
+impl Display for Changes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.0 {
+            let (prefix, text) = match line {
+                Line::Context(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+            for line in text.lines() {
+                writeln!(f, "{} {}", prefix, line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn change<'a>(lines: &mut Vec<Line>, context: &mut Option<&'a str>, item: &'a Item, line: Line) {
+    if let Some(stage) = &item.stage {
+        if *context != Some(stage.as_str()) {
+            lines.push(Line::Context(stage.clone()));
+        }
+    }
+    *context = Some(item.stage.as_deref().unwrap_or(&item.text));
+    lines.push(line);
+}
+
+pub fn diff(old: &DockerFile, new: &DockerFile) -> Changes {
+    let old = items(old);
+    let new = items(new);
+
+    // lengths of common subsequences of suffixes
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i].key == new[j].key {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    // `FROM` of the last changed line, it's not repeated for changes in the same stage
+    let mut context: Option<&str> = None;
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i].key == new[j].key {
+            context = None;
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && lengths[i + 1][j] >= lengths[i][j + 1]) {
+            change(
+                &mut lines,
+                &mut context,
+                &old[i],
+                Line::Removed(old[i].text.clone()),
+            );
+            i += 1;
+        } else {
+            change(
+                &mut lines,
+                &mut context,
+                &new[j],
+                Line::Added(new[j].text.clone()),
+            );
+            j += 1;
+        }
+    }
+    Changes(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff() {
+        let old = DockerFile::parse(
+            "FROM rust AS builder\n# build\nrun cargo build \\\n    --release\nFROM debian\nCMD [\"app\"]\n",
+        )
+        .unwrap();
+        let changed = DockerFile::parse(
+            "FROM rust AS builder\nRUN cargo build --release\n\nFROM debian\n\nCMD app-not\n",
+        )
+        .unwrap();
+        let changes = super::diff(&old, &changed);
+        assert!(!changes.is_empty());
+        assert_eq!(
+            changes.to_string(),
+            "  FROM debian\n- CMD [\"app\"]\n+ CMD app-not\n"
+        );
+
+        let new =
+            DockerFile::parse("FROM rust:1.75 AS builder\nRUN cargo build --release\n").unwrap();
+        assert_eq!(
+            super::diff(&old, &new).to_string(),
+            "- FROM rust AS builder\n+ FROM rust:1.75 AS builder\n- FROM debian\n- CMD [\"app\"]\n"
+        );
+
+        let formatted = DockerFile::parse(&old.to_string()).unwrap();
+        assert!(super::diff(&old, &formatted).is_empty());
+
+        let diff = |old: &str, new: &str| {
+            let old = DockerFile::parse(&format!("FROM rust\n{}", old)).unwrap();
+            let new = DockerFile::parse(&format!("FROM rust\n{}", new)).unwrap();
+            super::diff(&old, &new).to_string()
+        };
+        assert_eq!(
+            diff(r#"ENV A="a  b""#, r#"ENV A="a b""#),
+            "  FROM rust\n- ENV A=\"a  b\"\n+ ENV A=\"a b\"\n"
+        );
+        assert_eq!(
+            diff(r#"CMD ["x  y"]"#, r#"CMD ["x y"]"#),
+            "  FROM rust\n- CMD [\"x  y\"]\n+ CMD [\"x y\"]\n"
+        );
+        assert_eq!(
+            diff(r#"RUN echo "a  b""#, r#"RUN echo "a b""#),
+            "  FROM rust\n- RUN echo \"a  b\"\n+ RUN echo \"a b\"\n"
+        );
+        assert_eq!(diff("RUN echo  a \\\n    b", "RUN echo a b"), "");
+        assert_eq!(
+            diff("ONBUILD RUN make \\\n  all", "ONBUILD RUN make all"),
+            ""
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! Command-line interface of dockerfile-rs, requires `cli` feature
+//!
+//! Exit code is 0 on success, 1 when check fails (lint found problems, file is not formatted or
+//! Dockerfiles differ) and 2 on errors like unreadable files or invalid input.
+
+mod diff;
+mod report;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use dockerfile_rs::{
+    format::{BlankLines, Case, Formatter},
+    lint::{LintConfig, Severity},
+    spec, DockerFile,
+};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "dockerfile-rs",
+    version,
+    about = "Generates, formats and checks Dockerfiles"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Compiles YAML or TOML spec into Dockerfile
+    Render {
+        /// Spec file, `-` reads standard input
+        spec: PathBuf,
+        /// Format of spec, guessed from extension by default
+        #[arg(long, value_enum)]
+        format: Option<SpecFormat>,
+        /// Writes Dockerfile into file instead of standard output
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Parses Dockerfiles and writes them back in canonical form with given layout
+    Fmt {
+        /// Dockerfiles, `-` reads standard input
+        #[arg(default_value = "Dockerfile")]
+        files: Vec<PathBuf>,
+        /// Lists files that are not formatted and fails if there are any
+        #[arg(long)]
+        check: bool,
+        /// Writes formatted files in place
+        #[arg(short, long, conflicts_with = "check")]
+        write: bool,
+        #[command(flatten)]
+        layout: Layout,
+    },
+    /// Checks Dockerfile with lint rules
+    Lint {
+        /// Dockerfile, `-` reads standard input
+        #[arg(default_value = "Dockerfile")]
+        file: PathBuf,
+        #[arg(long, value_enum, default_value_t = LintFormat::Human)]
+        format: LintFormat,
+        /// Disables rule, can be given several times
+        #[arg(long, value_name = "RULE")]
+        disable: Vec<String>,
+        /// Lowest severity that fails the check
+        #[arg(long, value_enum, default_value_t = Level::Warning)]
+        fail_on: Level,
+    },
+    /// Compares instructions of two Dockerfiles ignoring comments and formatting
+    Diff { old: PathBuf, new: PathBuf },
+    /// Prints configuration of the built image as OCI config JSON
+    Eval {
+        /// Dockerfile, `-` reads standard input
+        #[arg(default_value = "Dockerfile")]
+        file: PathBuf,
+        /// Build argument, value is taken from environment if it's omitted
+        #[arg(long = "build-arg", value_name = "NAME[=VALUE]")]
+        build_args: Vec<String>,
+    },
+}
+
+/// Options of `Formatter`, defaults are the same as `Display` of `DockerFile`
+#[derive(Args)]
+struct Layout {
+    /// Wraps `RUN` at `&&` and writes a pair of `ENV` and `LABEL` per line in longer lines
+    #[arg(long)]
+    width: Option<usize>,
+    /// Writes every pair of `ENV` and `LABEL` on its own line
+    #[arg(long)]
+    align: bool,
+    #[arg(long, value_enum, default_value_t = KeywordCase::Upper)]
+    case: KeywordCase,
+    #[arg(long, value_enum, default_value_t = Blank::Sections)]
+    blank_lines: Blank,
+    /// Drops comments
+    #[arg(long)]
+    no_comments: bool,
+    /// Drops comments and blank lines, writes `ENV` and `LABEL` on one line
+    #[arg(long, conflicts_with_all = ["align", "blank_lines", "no_comments"])]
+    compact: bool,
+}
+
+impl Layout {
+    fn formatter(&self) -> Formatter {
+        let formatter = if self.compact {
+            Formatter::compact()
+        } else {
+            let blank_lines = match self.blank_lines {
+                Blank::Sections => BlankLines::Sections,
+                Blank::Stages => BlankLines::Stages,
+                Blank::None => BlankLines::None,
+            };
+            let formatter = Formatter::default()
+                .blank_lines(blank_lines)
+                .comments(!self.no_comments);
+            if self.align {
+                formatter.align(true)
+            } else {
+                formatter
+            }
+        };
+        let formatter = match self.case {
+            KeywordCase::Upper => formatter.case(Case::Upper),
+            KeywordCase::Lower => formatter.case(Case::Lower),
+        };
+        match self.width {
+            Some(width) => formatter.width(width),
+            None => formatter,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum KeywordCase {
+    Upper,
+    Lower,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Blank {
+    Sections,
+    Stages,
+    None,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SpecFormat {
+    Yaml,
+    Toml,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LintFormat {
+    Human,
+    Json,
+    Sarif,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Level {
+    Info,
+    Warning,
+    Error,
+}
+
+impl From<Level> for Severity {
+    fn from(level: Level) -> Self {
+        match level {
+            Level::Info => Severity::Info,
+            Level::Warning => Severity::Warning,
+            Level::Error => Severity::Error,
+        }
+    }
+}
+
+/// Result of command that completed
+enum Outcome {
+    Success,
+    Failure,
+}
+
+type Result<T> = std::result::Result<T, String>;
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Render {
+            spec,
+            format,
+            output,
+        } => render(&spec, format, output.as_deref()),
+        Command::Fmt {
+            files,
+            check,
+            write,
+            layout,
+        } => fmt(&files, check, write, &layout.formatter()),
+        Command::Lint {
+            file,
+            format,
+            disable,
+            fail_on,
+        } => lint(&file, format, disable, fail_on.into()),
+        Command::Diff { old, new } => diff(&old, &new),
+        Command::Eval { file, build_args } => eval(&file, &build_args),
+    };
+    match result {
+        Ok(Outcome::Success) => ExitCode::SUCCESS,
+        Ok(Outcome::Failure) => ExitCode::from(1),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|e| format!("standard input: {}", e))?;
+        Ok(input)
+    } else {
+        fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    fs::write(path, content).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn parse(path: &Path) -> Result<DockerFile> {
+    DockerFile::parse(&read(path)?).map_err(|e| format!("{}:{}", path.display(), e))
+}
+
+fn render(path: &Path, format: Option<SpecFormat>, output: Option<&Path>) -> Result<Outcome> {
+    let format = format.unwrap_or_else(|| match path.extension() {
+        Some(extension) if extension == "toml" => SpecFormat::Toml,
+        _ => SpecFormat::Yaml,
+    });
+    let input = read(path)?;
+    let docker_file = match format {
+        SpecFormat::Yaml => spec::from_yaml(&input),
+        SpecFormat::Toml => spec::from_toml(&input),
+    }
+    .map_err(|e| format!("{}:{}", path.display(), e))?;
+    docker_file
+        .validate()
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+
+    match output {
+        Some(output) => write(output, &docker_file.to_string())?,
+        None => print!("{}", docker_file),
+    }
+    Ok(Outcome::Success)
+}
+
+fn fmt(
+    files: &[PathBuf],
+    check: bool,
+    write_files: bool,
+    formatter: &Formatter,
+) -> Result<Outcome> {
+    let mut outcome = Outcome::Success;
+    for path in files {
+        let input = read(path)?;
+        let formatted = DockerFile::parse(&input)
+            .map_err(|e| format!("{}:{}", path.display(), e))?
+            .format(formatter);
+        if check {
+            if input != formatted {
+                println!("{}", path.display());
+                outcome = Outcome::Failure;
+            }
+        } else if write_files && path != Path::new("-") {
+            if input != formatted {
+                write(path, &formatted)?;
+            }
+        } else {
+            print!("{}", formatted);
+        }
+    }
+    Ok(outcome)
+}
+
+fn lint(
+    path: &Path,
+    format: LintFormat,
+    disable: Vec<String>,
+    fail_on: Severity,
+) -> Result<Outcome> {
+    let config = LintConfig::default();
+    if let Some(rule) = disable
+        .iter()
+        .find(|id| !config.rules().iter().any(|rule| rule.id() == id.as_str()))
+    {
+        return Err(format!("unknown lint rule `{}`", rule));
+    }
+    let config = disable.into_iter().fold(config, LintConfig::disable);
+    let docker_file = parse(path)?;
+    let diagnostics = docker_file.lint(&config);
+
+    let name = path.display().to_string();
+    match format {
+        LintFormat::Human => print!("{}", report::human(&name, &diagnostics)),
+        LintFormat::Json => println!("{:#}", report::json(&diagnostics)),
+        LintFormat::Sarif => println!("{:#}", report::sarif(&name, &config, &diagnostics)),
+    }
+
+    if diagnostics.iter().any(|d| d.severity >= fail_on) {
+        Ok(Outcome::Failure)
+    } else {
+        Ok(Outcome::Success)
+    }
+}
+
+fn diff(old: &Path, new: &Path) -> Result<Outcome> {
+    let changes = diff::diff(&parse(old)?, &parse(new)?);
+    if changes.is_empty() {
+        Ok(Outcome::Success)
+    } else {
+        println!("--- {}\n+++ {}", old.display(), new.display());
+        print!("{}", changes);
+        Ok(Outcome::Failure)
+    }
+}
+
+fn eval(path: &Path, build_args: &[String]) -> Result<Outcome> {
+    let build_args: HashMap<String, String> = build_args
+        .iter()
+        .filter_map(|arg| match arg.split_once('=') {
+            Some((name, value)) => Some((name.to_string(), value.to_string())),
+            // like `docker build`, argument without value is skipped if it's not in environment
+            None => env::var(arg).ok().map(|value| (arg.clone(), value)),
+        })
+        .collect();
+    let config = parse(path)?
+        .image_config_with(&build_args)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    println!("{}", config.to_json());
+    Ok(Outcome::Success)
+}
@@ -0,0 +1,151 @@
+//! Output formats of `lint` command
+
+use dockerfile_rs::lint::{Diagnostic, LintConfig, Location, Severity};
+use serde_json::{json, Value};
+use std::fmt::Write;
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Human readable position like `stage 2, instruction 3`, counted from 1
+fn position(location: &Location) -> String {
+    match (location.stage, location.index) {
+        (None, Some(index)) => format!("instruction {} before FROM", index + 1),
+        (Some(stage), None) => format!("stage {}", stage + 1),
+        (Some(stage), Some(index)) => format!("stage {}, instruction {}", stage + 1, index + 1),
+        (None, None) => "file".to_string(),
+    }
+}
+
+/// Path in the model like `stages[1].instructions[2]`, counted from 0
+fn path(location: &Location) -> String {
+    match (location.stage, location.index) {
+        (None, Some(index)) => format!("global[{}]", index),
+        (Some(stage), None) => format!("stages[{}].from", stage),
+        (Some(stage), Some(index)) => format!("stages[{}].instructions[{}]", stage, index),
+        (None, None) => String::new(),
+    }
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "note",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+pub fn human(file: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut output = String::new();
+    for diagnostic in diagnostics {
+        let _ = writeln!(
+            output,
+            "{}: {}: {}",
+            file,
+            position(&diagnostic.location),
+            diagnostic
+        );
+        for line in diagnostic.instruction.to_string().lines() {
+            let _ = writeln!(output, "    {}", line);
+        }
+    }
+    output
+}
+
+pub fn json(diagnostics: &[Diagnostic]) -> Value {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "rule": diagnostic.rule,
+                "severity": diagnostic.severity.to_string(),
+                "message": diagnostic.message,
+                "stage": diagnostic.location.stage,
+                "index": diagnostic.location.index,
+                "instruction": diagnostic.instruction.to_string(),
+            })
+        })
+        .collect()
+}
+
+pub fn sarif(file: &str, config: &LintConfig, diagnostics: &[Diagnostic]) -> Value {
+    let rules: Vec<Value> = config
+        .rules()
+        .iter()
+        .map(|rule| {
+            json!({
+                "id": rule.id(),
+                "shortDescription": { "text": rule.description() },
+                "defaultConfiguration": { "level": sarif_level(rule.severity()) },
+            })
+        })
+        .collect();
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            json!({
+                "ruleId": diagnostic.rule,
+                "level": sarif_level(diagnostic.severity),
+                "message": { "text": diagnostic.message },
+                "locations": [{
+                    "physicalLocation": { "artifactLocation": { "uri": file } },
+                    "logicalLocations": [{
+                        "fullyQualifiedName": path(&diagnostic.location),
+                        "kind": "instruction",
+                    }],
+                }],
+            })
+        })
+        .collect();
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dockerfile_rs::DockerFile;
+
+    #[test]
+    fn formats() {
+        let docker_file = DockerFile::parse("FROM rust\nWORKDIR app\n").unwrap();
+        let config = LintConfig::default();
+        let diagnostics = docker_file.lint(&config);
+
+        assert_eq!(
+            human("Dockerfile", &diagnostics),
+            "Dockerfile: stage 1: warning[untagged-image]: base image `rust` has no tag\n    FROM rust\n\
+             Dockerfile: stage 1, instruction 1: warning[relative-workdir]: `WORKDIR app` is relative\n    WORKDIR \"app\"\n"
+        );
+
+        let json = json(&diagnostics);
+        assert_eq!(json[1]["rule"], "relative-workdir");
+        assert_eq!(json[1]["stage"], 0);
+        assert_eq!(json[1]["index"], 0);
+
+        let sarif = sarif("Dockerfile", &config, &diagnostics);
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "dockerfile-rs");
+        assert_eq!(
+            run["tool"]["driver"]["rules"].as_array().unwrap().len(),
+            config.rules().len()
+        );
+        assert_eq!(run["results"][1]["ruleId"], "relative-workdir");
+        assert_eq!(
+            run["results"][1]["locations"][0]["logicalLocations"][0]["fullyQualifiedName"],
+            "stages[0].instructions[0]"
+        );
+    }
+}
@@ -1,79 +1,130 @@
 use crate::{
-    Add, Arg, Cmd, Comment, Copy, EntryPoint, Env, Expose, From, HealthCheck, Label, Maintainer,
-    OnBuild, Run, Shell, StopSignal, StorageInstruction, User, Volume, WorkDir,
+    eval::{Evaluator, ExpandError, Variables},
+    format::Formatter,
+    image::{self, ImageConfig},
+    lint::{self, Diagnostic, LintConfig},
+    parser, quote, Add, Arg, Cmd, Comment, Copy, Directive, EntryPoint, Env, Expose, From,
+    HealthCheck, InstructionKind, Label, Maintainer, Network, OnBuild, ParseError, Run, Shell,
+    StopSignal, User, ValidationError, Volume, WorkDir,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    mem,
+    str::FromStr,
 };
-use std::fmt::{self, Display};
 
-/// `Dockerfile` generator
+/// Build stage, starts with `FROM` and has its own `ENTRYPOINT`, `CMD` and `ONBUILD` instructions
 /// # Example
-/// ```rust,no_run
-/// # use std::io::Result;
-/// # fn main() -> Result<()> {
-/// use std::{io::Write, fs::File};
-/// use dockerfile_rs::{DockerFile, Copy, FROM};
+/// ```rust
+/// use dockerfile_rs::{Stage, FROM};
 ///
-/// let docker_file = DockerFile::from(FROM!(nginx:latest))
-///     .comment("open port for server")
-///     .expose(80)
-///     .copy(Copy {
-///         src: ".".to_string(),
-///         dst: ".".to_string(),
-///         from: None,
-///         chown: None,
-///     })
-///     .cmd(vec!["echo", "Hello from container!"]);
+/// let stage = Stage::from(FROM!(rust AS builder))
+///     .run(vec!["cargo", "build", "--release"]);
+/// assert_eq!(stage.name(), Some("builder"));
+/// assert_eq!(stage.to_string(), r#"FROM rust AS builder
 ///
-/// // write into file
-/// let mut file = File::create("nginx.Dockerfile")?;
-/// write!(&mut file, "{}", docker_file)?;
-/// # Ok(())
-/// # }
+/// RUN ["cargo", "build", "--release"]
+/// "#);
 /// ```
-pub struct DockerFile {
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stage {
     from: From,
-    maintainer: Option<Maintainer>,
-    entry_point: Option<EntryPoint>,
-    cmd: Option<Cmd>,
-    instructions: Vec<Box<StorageInstruction>>,
-    on_builds: Vec<OnBuild>,
+    instructions: Vec<InstructionKind>,
+    network: Option<Network>,
 }
 
-impl DockerFile {
+impl Stage {
     pub fn from(from: From) -> Self {
         Self {
             from,
-            maintainer: None,
-            entry_point: None,
-            cmd: None,
             instructions: Vec::new(),
-            on_builds: Vec::new(),
+            network: None,
         }
     }
 
-    /// Can be defined just once, only last function call will have effect
-    /// Deprecated, use [`label`] with `maintainer` key instead
+    /// Base image of stage
+    pub fn base(&self) -> &From {
+        &self.from
+    }
+
+    pub fn base_mut(&mut self) -> &mut From {
+        &mut self.from
+    }
+
+    /// Name given with `AS`
+    pub fn name(&self) -> Option<&str> {
+        self.from.name.as_deref()
+    }
+
+    /// Instructions after `FROM` in the order they were added
+    pub fn instructions(&self) -> &[InstructionKind] {
+        &self.instructions
+    }
+
+    pub fn instructions_mut(&mut self) -> &mut Vec<InstructionKind> {
+        &mut self.instructions
+    }
+
+    /// Validates base image and every instruction, returns the first error
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.from.validate()?;
+        self.instructions
+            .iter()
+            .try_for_each(InstructionKind::validate)
+    }
+
+    /// Network set by [`default_network`]
     ///
-    /// [`label`]: struct.DockerFile.html#method.label
-    pub fn maintainer<T: Into<Maintainer> + 'static>(mut self, maintainer: T) -> Self {
-        self.maintainer = Some(maintainer.into());
+    /// [`default_network`]: struct.Stage.html#method.default_network
+    pub fn network(&self) -> Option<Network> {
+        self.network
+    }
+
+    /// Sets `--network` of every `RUN` in stage that has no network, including ones added later
+    pub fn default_network(mut self, network: Network) -> Self {
+        self.network = Some(network);
+        for instruction in &mut self.instructions {
+            if let InstructionKind::Run(run) = instruction {
+                run.network.get_or_insert(network);
+            }
+        }
         self
     }
 
-    fn instruction<T: StorageInstruction + 'static>(mut self, t: T) -> Self {
-        self.instructions.push(Box::new(t));
+    pub(crate) fn instruction<T: Into<InstructionKind>>(mut self, t: T) -> Self {
+        let mut instruction = t.into();
+        if let (InstructionKind::Run(run), Some(network)) = (&mut instruction, self.network) {
+            run.network.get_or_insert(network);
+        }
+        if let InstructionKind::Maintainer(_)
+        | InstructionKind::EntryPoint(_)
+        | InstructionKind::Cmd(_) = instruction
+        {
+            let kind = mem::discriminant(&instruction);
+            self.instructions.retain(|i| mem::discriminant(i) != kind);
+        }
+        self.instructions.push(instruction);
         self
     }
 
     /// Can be defined just once, only last function call will have effect
-    pub fn entry_point<T: Into<EntryPoint> + 'static>(mut self, entry_point: T) -> Self {
-        self.entry_point = Some(entry_point.into());
-        self
+    /// Deprecated, use [`label`] with `maintainer` key instead
+    ///
+    /// [`label`]: struct.Stage.html#method.label
+    pub fn maintainer<T: Into<Maintainer> + 'static>(self, maintainer: T) -> Self {
+        self.instruction(maintainer.into())
     }
 
     /// Can be defined just once, only last function call will have effect
-    pub fn cmd<T: Into<Cmd> + 'static>(mut self, cmd: T) -> Self {
-        self.cmd = Some(cmd.into());
-        self
+    pub fn entry_point<T: Into<EntryPoint> + 'static>(self, entry_point: T) -> Self {
+        self.instruction(entry_point.into())
+    }
+
+    /// Can be defined just once, only last function call will have effect
+    pub fn cmd<T: Into<Cmd> + 'static>(self, cmd: T) -> Self {
+        self.instruction(cmd.into())
     }
 
     pub fn run<T: Into<Run> + 'static>(self, run: T) -> Self {
@@ -97,8 +148,8 @@ impl DockerFile {
         self.instruction(add)
     }
 
-    pub fn copy(self, copy: Copy) -> Self {
-        self.instruction(copy)
+    pub fn copy<T: Into<Copy> + 'static>(self, copy: T) -> Self {
+        self.instruction(copy.into())
     }
 
     pub fn volume<T: Into<Volume> + 'static>(self, volume: T) -> Self {
@@ -133,60 +184,496 @@ impl DockerFile {
         self.instruction(comment.into())
     }
 
-    pub fn on_build<T: Into<OnBuild> + 'static>(mut self, on_build: T) -> Self {
-        self.on_builds.push(on_build.into());
-        self
+    pub fn on_build<T: Into<OnBuild> + 'static>(self, on_build: T) -> Self {
+        self.instruction(on_build.into())
     }
 }
 
-impl Display for DockerFile {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "{}", self.from)?;
+/// Writes non-empty group of instructions separated by blank line
+fn section<'a, I>(f: &mut fmt::Formatter, instructions: I) -> fmt::Result
+where
+    I: Iterator<Item = &'a InstructionKind>,
+{
+    let mut instructions = instructions.peekable();
+    if instructions.peek().is_some() {
+        writeln!(f)?;
+        for instruction in instructions {
+            writeln!(f, "{}", instruction)?;
+        }
+    }
+    Ok(())
+}
 
-        if let Some(maintainer) = &self.maintainer {
-            writeln!(f)?;
-            writeln!(f, "{}", maintainer)?;
+impl Stage {
+    /// Groups of instructions after `FROM` in the order they are written: `MAINTAINER`, the rest,
+    /// `ONBUILD`, `ENTRYPOINT` and `CMD`
+    ///
+    /// Comments stay before the next instruction, so `# lint-ignore:` applies to the same
+    /// instruction after rendering. Comments at the end of stage are in the rest.
+    pub(crate) fn sections(&self) -> [Vec<&InstructionKind>; 4] {
+        use InstructionKind::{Cmd, Comment, EntryPoint, Maintainer, OnBuild};
+
+        let mut sections: [Vec<&InstructionKind>; 5] = Default::default();
+        let mut comments = Vec::new();
+        for instruction in &self.instructions {
+            let section = match instruction {
+                Comment(_) => {
+                    comments.push(instruction);
+                    continue;
+                }
+                Maintainer(_) => 0,
+                OnBuild(_) => 2,
+                EntryPoint(_) => 3,
+                Cmd(_) => 4,
+                _ => 1,
+            };
+            sections[section].append(&mut comments);
+            sections[section].push(instruction);
         }
+        sections[1].append(&mut comments);
 
-        if !self.instructions.is_empty() {
-            writeln!(f)?;
-            for instruction in &self.instructions {
-                writeln!(f, "{}", instruction)?;
-            }
+        let [maintainer, rest, on_build, mut entry_point, cmd] = sections;
+        entry_point.extend(cmd);
+        [maintainer, rest, on_build, entry_point]
+    }
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.from)?;
+        for instructions in self.sections().iter() {
+            section(f, instructions.iter().copied())?;
         }
+        Ok(())
+    }
+}
 
-        if !self.on_builds.is_empty() {
-            writeln!(f)?;
-            for on_build in &self.on_builds {
-                writeln!(f, "{}", on_build)?;
-            }
+/// `Dockerfile` generator
+///
+/// `DockerFile` is an ordered list of [`Stage`]s, instruction functions are applied to the last one.
+/// # Example
+/// ```rust,no_run
+/// # use std::io::Result;
+/// # fn main() -> Result<()> {
+/// use std::{io::Write, fs::File};
+/// use dockerfile_rs::{DockerFile, FROM};
+///
+/// let docker_file = DockerFile::from(FROM!(nginx:latest))
+///     .comment("open port for server")
+///     .expose(80)
+///     .copy((".", "."))
+///     .cmd(vec!["echo", "Hello from container!"]);
+///
+/// // write into file
+/// let mut file = File::create("nginx.Dockerfile")?;
+/// write!(&mut file, "{}", docker_file)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Stage`]: struct.Stage.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DockerFile {
+    directives: Vec<Directive>,
+    global: Vec<InstructionKind>,
+    stages: Vec<Stage>,
+}
+
+impl DockerFile {
+    pub fn from(from: From) -> Self {
+        Self {
+            directives: Vec::new(),
+            global: Vec::new(),
+            stages: vec![Stage::from(from)],
         }
+    }
 
-        match (&self.entry_point, &self.cmd) {
-            (Some(entry_point), Some(cmd)) => {
-                writeln!(f)?;
-                writeln!(f, "{}", entry_point)?;
-                writeln!(f, "{}", cmd)?;
-            }
-            (Some(entry_point), None) => {
-                writeln!(f)?;
-                writeln!(f, "{}", entry_point)?;
-            }
-            (None, Some(cmd)) => {
-                writeln!(f)?;
-                writeln!(f, "{}", cmd)?;
-            }
-            (None, None) => {}
+    /// Parse existing `Dockerfile`
+    ///
+    /// Keywords are case-insensitive, line continuations and comments are supported.
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::DockerFile;
+    ///
+    /// let docker_file = DockerFile::parse("FROM nginx:latest\nexpose 80").unwrap();
+    /// assert_eq!(docker_file.to_string(), "FROM nginx:latest\n\nEXPOSE 80\n");
+    /// ```
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        parser::parse(input)
+    }
+
+    /// Add new stage, following instruction functions will be applied to it
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{DockerFile, COPY, FROM};
+    ///
+    /// let docker_file = DockerFile::from(FROM!(rust AS builder))
+    ///     .run(vec!["cargo", "build", "--release"])
+    ///     .stage(FROM!(debian), |stage| {
+    ///         stage.copy(COPY!(--from=builder "/target/release/app" "/usr/bin/app"))
+    ///     })
+    ///     .cmd(vec!["app"]);
+    /// assert_eq!(docker_file.stages().len(), 2);
+    /// ```
+    pub fn stage<F>(mut self, from: From, f: F) -> Self
+    where
+        F: FnOnce(Stage) -> Stage,
+    {
+        self.stages.push(f(Stage::from(from)));
+        self
+    }
+
+    /// All stages in build order
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    pub fn stages_mut(&mut self) -> &mut Vec<Stage> {
+        &mut self.stages
+    }
+
+    /// Parser directive written at the top of the file, replaces directive with the same name
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{DockerFile, Directive, FROM};
+    ///
+    /// let docker_file = DockerFile::from(FROM!(rust))
+    ///     .directive(Directive::syntax("docker/dockerfile:1"))
+    ///     .directive(Directive::Escape('`'))
+    ///     .run("cargo build \\\n    --release");
+    /// assert_eq!(
+    ///     docker_file.to_string(),
+    ///     "# syntax=docker/dockerfile:1\n# escape=`\n\nFROM rust\n\nRUN cargo build \\`\n    --release\n"
+    /// );
+    /// ```
+    pub fn directive<T: Into<Directive>>(mut self, directive: T) -> Self {
+        let directive = directive.into();
+        match self
+            .directives
+            .iter_mut()
+            .find(|d| d.name() == directive.name())
+        {
+            Some(existing) => *existing = directive,
+            None => self.directives.push(directive),
         }
+        self
+    }
 
-        Ok(())
+    /// Parser directives in order they are written
+    pub fn directives(&self) -> &[Directive] {
+        &self.directives
+    }
+
+    /// Character set by `escape` directive, `\` by default
+    pub fn escape(&self) -> char {
+        self.directives
+            .iter()
+            .find_map(|directive| match directive {
+                Directive::Escape(c) => Some(*c),
+                _ => None,
+            })
+            .unwrap_or('\\')
+    }
+
+    /// Instructions before the first `FROM`
+    pub fn global(&self) -> &[InstructionKind] {
+        &self.global
+    }
+
+    pub fn global_mut(&mut self) -> &mut Vec<InstructionKind> {
+        &mut self.global
+    }
+
+    /// `ARG` before the first `FROM`, can be used in `FROM` of any stage
+    pub fn global_arg<T: Into<Arg> + 'static>(mut self, arg: T) -> Self {
+        self.global.push(InstructionKind::Arg(arg.into()));
+        self
+    }
+
+    /// Comment before the first `FROM`
+    pub fn global_comment<T: Into<Comment> + 'static>(mut self, comment: T) -> Self {
+        self.global.push(InstructionKind::Comment(comment.into()));
+        self
+    }
+
+    /// Validates global instructions and every stage, returns the first error
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{DockerFile, ValidationError, FROM};
+    ///
+    /// let docker_file = DockerFile::from(FROM!(rust)).env(("RUST VERSION", "1.33"));
+    /// assert_eq!(
+    ///     docker_file.validate(),
+    ///     Err(ValidationError::InvalidKey("RUST VERSION".to_string()))
+    /// );
+    /// ```
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.directives.iter().try_for_each(Directive::validate)?;
+        quote::with_escape(self.escape(), || {
+            self.global.iter().try_for_each(InstructionKind::validate)?;
+            self.stages.iter().try_for_each(Stage::validate)
+        })
+    }
+
+    /// Runs lint rules, diagnostics are sorted by location of instruction
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{lint::LintConfig, DockerFile};
+    ///
+    /// let docker_file = DockerFile::parse("FROM rust:1.33\nCMD a\nCMD b").unwrap();
+    /// let diagnostics = docker_file.lint(&LintConfig::default());
+    /// assert_eq!(diagnostics[0].rule, "duplicate-cmd");
+    /// assert_eq!(diagnostics[0].to_string(), "warning[duplicate-cmd]: `CMD` is overridden by a later `CMD`");
+    /// ```
+    pub fn lint(&self, config: &LintConfig) -> Vec<Diagnostic> {
+        lint::lint(self, config)
+    }
+
+    /// Copy with `$VAR` references resolved for given build arguments, see [`eval`]
+    /// # Example
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use dockerfile_rs::DockerFile;
+    ///
+    /// let docker_file = DockerFile::parse("ARG TAG=1.33\nFROM rust:${TAG}-slim").unwrap();
+    /// let substituted = docker_file.substitute(&HashMap::new()).unwrap();
+    /// assert_eq!(substituted.stages()[0].base().to_string(), "FROM rust:1.33-slim");
+    /// ```
+    ///
+    /// [`eval`]: eval/index.html
+    pub fn substitute(
+        &self,
+        build_args: &HashMap<String, String>,
+    ) -> Result<DockerFile, ExpandError> {
+        Evaluator::new(build_args.clone()).substitute(self)
+    }
+
+    /// `ARG` and `ENV` variables visible at the end of every stage
+    pub fn variables(
+        &self,
+        build_args: &HashMap<String, String>,
+    ) -> Result<Vec<Variables>, ExpandError> {
+        Evaluator::new(build_args.clone()).variables(self)
+    }
+
+    /// Writes `DockerFile` with layout of `formatter`, see [`format`]
+    ///
+    /// [`format`]: format/index.html
+    pub fn format(&self, formatter: &Formatter) -> String {
+        formatter.format(self)
+    }
+
+    /// Configuration of the built image, see [`image`]
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::DockerFile;
+    ///
+    /// let docker_file = DockerFile::parse("FROM rust\nARG USER=nobody\nUSER $USER\nCMD run").unwrap();
+    /// let config = docker_file.image_config().unwrap();
+    /// assert_eq!(config.user.as_deref(), Some("nobody"));
+    /// assert_eq!(config.cmd, Some(vec!["/bin/sh".to_string(), "-c".to_string(), "run".to_string()]));
+    /// ```
+    ///
+    /// [`image`]: image/index.html
+    pub fn image_config(&self) -> Result<ImageConfig, ExpandError> {
+        image::image_config(self, &HashMap::new())
+    }
+
+    /// Configuration of the image built with given build arguments
+    /// # Example
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use dockerfile_rs::DockerFile;
+    ///
+    /// let docker_file = DockerFile::parse("FROM rust\nARG USER=nobody\nUSER $USER").unwrap();
+    /// let mut build_args = HashMap::new();
+    /// build_args.insert("USER".to_string(), "app".to_string());
+    /// let config = docker_file.image_config_with(&build_args).unwrap();
+    /// assert_eq!(config.user.as_deref(), Some("app"));
+    /// ```
+    pub fn image_config_with(
+        &self,
+        build_args: &HashMap<String, String>,
+    ) -> Result<ImageConfig, ExpandError> {
+        image::image_config(self, build_args)
+    }
+
+    /// Appends instruction to the last stage as is, earlier `CMD` and `ENTRYPOINT` are kept
+    pub(crate) fn push<T: Into<InstructionKind>>(self, t: T) -> Self {
+        self.last_stage(|mut stage| {
+            stage.instructions.push(t.into());
+            stage
+        })
+    }
+
+    fn last_stage<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(Stage) -> Stage,
+    {
+        let stage = self
+            .stages
+            .pop()
+            .expect("DockerFile has at least one stage");
+        self.stages.push(f(stage));
+        self
+    }
+
+    /// Can be defined just once per stage, only last function call will have effect
+    /// Deprecated, use [`label`] with `maintainer` key instead
+    ///
+    /// [`label`]: struct.DockerFile.html#method.label
+    pub fn maintainer<T: Into<Maintainer> + 'static>(self, maintainer: T) -> Self {
+        self.last_stage(|stage| stage.maintainer(maintainer))
+    }
+
+    /// Can be defined just once per stage, only last function call will have effect
+    pub fn entry_point<T: Into<EntryPoint> + 'static>(self, entry_point: T) -> Self {
+        self.last_stage(|stage| stage.entry_point(entry_point))
+    }
+
+    /// Can be defined just once per stage, only last function call will have effect
+    pub fn cmd<T: Into<Cmd> + 'static>(self, cmd: T) -> Self {
+        self.last_stage(|stage| stage.cmd(cmd))
+    }
+
+    /// Sets `--network` of every `RUN` in the last stage that has no network,
+    /// including ones added later
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{DockerFile, Network, FROM};
+    ///
+    /// let docker_file = DockerFile::from(FROM!(rust))
+    ///     .run(vec!["cargo", "fetch"])
+    ///     .stage(FROM!(rust), |stage| stage)
+    ///     .default_network(Network::None)
+    ///     .run(vec!["cargo", "build", "--offline"]);
+    /// assert_eq!(
+    ///     docker_file.to_string(),
+    ///     r#"FROM rust
+    ///
+    /// RUN ["cargo", "fetch"]
+    ///
+    /// FROM rust
+    ///
+    /// RUN --network=none ["cargo", "build", "--offline"]
+    /// "#
+    /// );
+    /// ```
+    pub fn default_network(self, network: Network) -> Self {
+        self.last_stage(|stage| stage.default_network(network))
+    }
+
+    /// Accepts exec form, shell form and [`Heredoc`]
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{DockerFile, Heredoc, FROM};
+    ///
+    /// let docker_file = DockerFile::from(FROM!(debian))
+    ///     .run(Heredoc::from("apt-get update\napt-get install -y curl"));
+    /// assert_eq!(
+    ///     docker_file.to_string(),
+    ///     "FROM debian\n\nRUN <<EOF\napt-get update\napt-get install -y curl\nEOF\n"
+    /// );
+    /// ```
+    ///
+    /// [`Heredoc`]: struct.Heredoc.html
+    pub fn run<T: Into<Run> + 'static>(self, run: T) -> Self {
+        self.last_stage(|stage| stage.run(run))
+    }
+
+    pub fn label<T: Into<Label> + 'static>(self, label: T) -> Self {
+        self.last_stage(|stage| stage.label(label))
+    }
+
+    pub fn expose<T: Into<Expose> + 'static>(self, expose: T) -> Self {
+        self.last_stage(|stage| stage.expose(expose))
+    }
+
+    pub fn env<T: Into<Env> + 'static>(self, env: T) -> Self {
+        self.last_stage(|stage| stage.env(env))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(self, add: Add) -> Self {
+        self.last_stage(|stage| stage.add(add))
+    }
+
+    /// Accepts [`Copy`] or pair of source and destination, source can be a [`Heredoc`]
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{DockerFile, Heredoc, FROM};
+    ///
+    /// let docker_file = DockerFile::from(FROM!(nginx))
+    ///     .copy((Heredoc::from("listen 80;"), "/etc/nginx/conf.d/app.conf"));
+    /// assert_eq!(
+    ///     docker_file.to_string(),
+    ///     "FROM nginx\n\nCOPY <<EOF \"/etc/nginx/conf.d/app.conf\"\nlisten 80;\nEOF\n"
+    /// );
+    /// ```
+    ///
+    /// [`Copy`]: struct.Copy.html
+    /// [`Heredoc`]: struct.Heredoc.html
+    pub fn copy<T: Into<Copy> + 'static>(self, copy: T) -> Self {
+        self.last_stage(|stage| stage.copy(copy))
+    }
+
+    pub fn volume<T: Into<Volume> + 'static>(self, volume: T) -> Self {
+        self.last_stage(|stage| stage.volume(volume))
+    }
+
+    pub fn user(self, user: User) -> Self {
+        self.last_stage(|stage| stage.user(user))
+    }
+
+    pub fn work_dir<T: Into<WorkDir> + 'static>(self, work_dir: T) -> Self {
+        self.last_stage(|stage| stage.work_dir(work_dir))
+    }
+
+    pub fn arg<T: Into<Arg> + 'static>(self, arg: T) -> Self {
+        self.last_stage(|stage| stage.arg(arg))
+    }
+
+    pub fn stop_signal<T: Into<StopSignal> + 'static>(self, stop_signal: T) -> Self {
+        self.last_stage(|stage| stage.stop_signal(stop_signal))
+    }
+
+    pub fn health_check(self, health_check: HealthCheck) -> Self {
+        self.last_stage(|stage| stage.health_check(health_check))
+    }
+
+    pub fn shell<T: Into<Shell> + 'static>(self, shell: T) -> Self {
+        self.last_stage(|stage| stage.shell(shell))
+    }
+
+    pub fn comment<T: Into<Comment> + 'static>(self, comment: T) -> Self {
+        self.last_stage(|stage| stage.comment(comment))
+    }
+
+    pub fn on_build<T: Into<OnBuild> + 'static>(self, on_build: T) -> Self {
+        self.last_stage(|stage| stage.on_build(on_build))
+    }
+}
+
+impl Display for DockerFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&Formatter::default().format(self))
+    }
+}
+
+impl FromStr for DockerFile {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::Tag;
+    use crate::{Tag, FROM};
+    use std::collections::HashMap;
 
     #[test]
     fn builder() {
@@ -194,6 +681,7 @@ mod tests {
             image: String::from("rust"),
             tag_or_digest: Some(Tag("latest".to_string())),
             name: None,
+            platform: None,
         })
         .maintainer("lead rustacean")
         .comment("Hello, world!")
@@ -202,15 +690,24 @@ mod tests {
         .expose(80)
         .env(("RUST", "1.0.0"))
         .add(Add {
-            src: "/var/run".to_string(),
+            src: vec!["/var/run".into()],
             dst: "/home".to_string(),
             chown: None,
+            chmod: None,
+            link: false,
+            checksum: None,
+            keep_git_dir: false,
+            exclude: Vec::new(),
         })
         .copy(Copy {
-            src: "/var/run".to_string(),
+            src: vec!["/var/run".into()],
             dst: "/home".to_string(),
             from: None,
             chown: None,
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
         })
         .volume(vec!["/var/run", "/var/www"])
         .user(User {
@@ -257,4 +754,251 @@ CMD ["echo", "Hi!"]
 "#
         );
     }
+
+    #[test]
+    fn instructions() {
+        let mut docker_file = DockerFile::from(From {
+            image: String::from("rust"),
+            tag_or_digest: None,
+            name: None,
+            platform: None,
+        })
+        .cmd(vec!["cargo", "build"])
+        .run(vec!["cargo", "test"])
+        .cmd(vec!["cargo", "run"]);
+        assert_eq!(
+            docker_file.stages()[0].instructions(),
+            [
+                InstructionKind::Run(Run::from(vec!["cargo", "test"])),
+                InstructionKind::Cmd(Cmd::from(vec!["cargo", "run"])),
+            ]
+        );
+
+        let clone = docker_file.clone();
+        docker_file.stages_mut()[0]
+            .instructions_mut()
+            .retain(|i| !matches!(i, InstructionKind::Cmd(_)));
+        assert_ne!(docker_file, clone);
+        assert_eq!(
+            docker_file.to_string(),
+            "FROM rust\n\nRUN [\"cargo\", \"test\"]\n"
+        );
+    }
+
+    #[test]
+    fn default_network() {
+        let mut fetch = Run::from(vec!["cargo", "fetch"]);
+        fetch.network = Some(Network::Default);
+        let stage = Stage::from(FROM!(rust))
+            .run(fetch)
+            .run(vec!["cargo", "check"])
+            .default_network(Network::None)
+            .run(vec!["cargo", "build"])
+            .on_build(OnBuild::from(Run::from(vec!["cargo", "test"])));
+        assert_eq!(stage.network(), Some(Network::None));
+        assert_eq!(
+            stage.to_string(),
+            r#"FROM rust
+
+RUN --network=default ["cargo", "fetch"]
+RUN --network=none ["cargo", "check"]
+RUN --network=none ["cargo", "build"]
+
+ONBUILD RUN ["cargo", "test"]
+"#
+        );
+    }
+
+    #[test]
+    fn directives() {
+        let docker_file = DockerFile::from(FROM!(rust))
+            .directive(Directive::Escape('`'))
+            .directive(Directive::syntax("docker/dockerfile:1.4"))
+            .directive(Directive::syntax("docker/dockerfile:1"))
+            .global_comment("base image")
+            .label(("path", r#"C:\bin "x""#));
+        assert_eq!(
+            docker_file.directives(),
+            &[
+                Directive::Escape('`'),
+                Directive::syntax("docker/dockerfile:1")
+            ]
+        );
+        assert_eq!(docker_file.escape(), '`');
+        assert_eq!(
+            docker_file.to_string(),
+            "# escape=`\n# syntax=docker/dockerfile:1\n\n# base image\n\nFROM rust\n\nLABEL path=\"C:\\bin `\"x`\"\"\n"
+        );
+        assert_eq!(docker_file.validate(), Ok(()));
+
+        let invalid = docker_file.clone().env(("A`B", "1"));
+        assert_eq!(
+            invalid.validate(),
+            Err(ValidationError::InvalidKey("A`B".to_string()))
+        );
+        assert_eq!(
+            DockerFile::from(FROM!(rust)).env(("A`B", "1")).validate(),
+            Ok(())
+        );
+
+        let docker_file = docker_file.comment("escape=\\");
+        assert_eq!(
+            docker_file.validate(),
+            Err(ValidationError::MisplacedDirective("escape=\\".to_string()))
+        );
+
+        let docker_file = DockerFile::from(FROM!(rust)).stage(
+            From {
+                image: "debian".to_string(),
+                tag_or_digest: Some(Tag("12\nRUN evil".to_string())),
+                name: Some("runtime".to_string()),
+                platform: None,
+            },
+            |stage| stage,
+        );
+        assert_eq!(
+            docker_file.validate(),
+            Err(ValidationError::InvalidImage(
+                "debian:12\nRUN evil".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn reproducible() {
+        let render = || {
+            let labels: HashMap<String, String> = (0..16)
+                .map(|i| (format!("key{}", i), i.to_string()))
+                .collect();
+            DockerFile::from(FROM!(rust))
+                .label(labels)
+                .env(vec![("B", "1"), ("A", "2")])
+                .to_string()
+        };
+        let content = render();
+        assert!((0..8).all(|_| render() == content));
+        assert!(content.contains("LABEL key0=\"0\" \\\n      key1=\"1\" \\\n      key10=\"10\""));
+        assert!(content.ends_with("ENV B=\"1\" A=\"2\"\n"));
+    }
+
+    #[test]
+    fn stages() {
+        let content = DockerFile::from(From {
+            image: String::from("rust"),
+            tag_or_digest: Some(Tag("latest".to_string())),
+            name: Some(String::from("builder")),
+            platform: None,
+        })
+        .global_comment("Hello, world!")
+        .global_arg(("VERSION", "1.0.0"))
+        .run(vec!["cargo", "build"])
+        .cmd(vec!["cargo", "test"])
+        .stage(
+            From {
+                image: String::from("debian"),
+                tag_or_digest: None,
+                name: None,
+                platform: None,
+            },
+            |stage| {
+                stage
+                    .on_build(OnBuild::from(Run::from(vec!["echo"])))
+                    .entry_point(vec!["app"])
+            },
+        )
+        .copy(Copy {
+            src: vec!["/app".into()],
+            dst: "/usr/bin/app".to_string(),
+            from: Some("builder".to_string()),
+            chown: None,
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
+        })
+        .to_string();
+        assert_eq!(
+            content,
+            r#"# Hello, world!
+ARG VERSION="1.0.0"
+
+FROM rust:latest AS builder
+
+RUN ["cargo", "build"]
+
+CMD ["cargo", "test"]
+
+FROM debian
+
+COPY --from=builder "/app" "/usr/bin/app"
+
+ONBUILD RUN ["echo"]
+
+ENTRYPOINT ["app"]
+"#
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let docker_file = DockerFile::parse(
+            "FROM rust:1.33 AS builder\nLABEL a=b\nRUN cargo build\nONBUILD COPY . /app",
+        )
+        .unwrap();
+        let json = serde_json::to_value(&docker_file).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "directives": [],
+                "global": [],
+                "stages": [{
+                    "from": {
+                        "image": "rust",
+                        "tag_or_digest": { "type": "tag", "value": "1.33" },
+                        "name": "builder",
+                        "platform": null
+                    },
+                    "instructions": [
+                        {
+                            "type": "label",
+                            "value": { "pairs": [["a", { "value": "b", "expand": false }]] }
+                        },
+                        {
+                            "type": "run",
+                            "value": {
+                                "mounts": [],
+                                "network": null,
+                                "security": null,
+                                "command": { "type": "shell", "value": "cargo build" }
+                            }
+                        },
+                        {
+                            "type": "on_build",
+                            "value": {
+                                "trigger": {
+                                    "type": "copy",
+                                    "value": {
+                                        "src": [{ "type": "path", "value": "." }],
+                                        "dst": "/app",
+                                        "from": null,
+                                        "chown": null,
+                                        "chmod": null,
+                                        "link": false,
+                                        "parents": false,
+                                        "exclude": []
+                                    }
+                                }
+                            }
+                        }
+                    ],
+                    "network": null
+                }]
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<DockerFile>(json).unwrap(),
+            docker_file
+        );
+    }
 }
@@ -0,0 +1,584 @@
+//! Build context packed into tar archive, the way it's sent to Docker Engine API or BuildKit
+//!
+//! Archive is reproducible: entries are sorted by path, modification time is fixed, owner is
+//! `root`, directories and executable files have `0755` mode and other files `0644`. Rendered
+//! [`DockerFile`] is added at [`docker_file_path`] and replaces a file with the same path.
+//! # Example
+//! ```rust,no_run
+//! use std::fs::File;
+//! use dockerfile_rs::{context::Context, ignore::DockerIgnore, DockerFile, FROM};
+//!
+//! let docker_file = DockerFile::from(FROM!(rust))
+//!     .copy(("src", "/app/src"))
+//!     .run(vec!["cargo", "build"]);
+//! let context = Context::new(".", &docker_file)
+//!     .ignore(DockerIgnore::proposed(&docker_file));
+//! context.write(File::create("context.tar").unwrap()).unwrap();
+//! ```
+//!
+//! [`DockerFile`]: ../struct.DockerFile.html
+//! [`docker_file_path`]: struct.Context.html#method.docker_file_path
+
+use crate::{
+    ignore::{self, DockerIgnore, Matcher},
+    DockerFile,
+};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const BLOCK: usize = 512;
+
+#[derive(Debug)]
+enum Entry {
+    Dir,
+    File {
+        path: PathBuf,
+        size: u64,
+        executable: bool,
+    },
+    Symlink(String),
+    Data(Vec<u8>),
+}
+
+/// Directory with files for the build and [`DockerFile`] to build it
+///
+/// If ignore rules are not given, `.dockerignore` of the directory is used when it exists.
+///
+/// [`DockerFile`]: ../struct.DockerFile.html
+#[derive(Debug, Clone)]
+pub struct Context {
+    root: PathBuf,
+    docker_file: String,
+    docker_file_path: String,
+    ignore: Option<DockerIgnore>,
+    mtime: u64,
+}
+
+impl Context {
+    pub fn new<P: Into<PathBuf>>(root: P, docker_file: &DockerFile) -> Self {
+        Self {
+            root: root.into(),
+            docker_file: docker_file.to_string(),
+            docker_file_path: "Dockerfile".to_string(),
+            ignore: None,
+            mtime: 0,
+        }
+    }
+
+    /// Path of `Dockerfile` inside of archive, `Dockerfile` by default
+    ///
+    /// Writing fails if the path is empty, outside of the directory, a directory or inside of
+    /// a file.
+    pub fn docker_file_path<T: Into<String>>(mut self, path: T) -> Self {
+        let path = ignore::clean(&path.into());
+        self.docker_file_path = path.trim_start_matches('/').to_string();
+        self
+    }
+
+    /// Rules that filter files of the directory, `Dockerfile` is added anyway
+    pub fn ignore(mut self, ignore: DockerIgnore) -> Self {
+        self.ignore = Some(ignore);
+        self
+    }
+
+    /// Modification time of every entry in seconds since Unix epoch, `0` by default
+    pub fn mtime(mut self, mtime: u64) -> Self {
+        self.mtime = mtime;
+        self
+    }
+
+    /// Paths of archive entries in the order they are written, directories end with `/`
+    pub fn paths(&self) -> io::Result<Vec<String>> {
+        let entries = self.entries()?;
+        Ok(entries
+            .iter()
+            .map(|(path, entry)| match entry {
+                Entry::Dir => format!("{}/", path),
+                _ => path.clone(),
+            })
+            .collect())
+    }
+
+    /// Writes tar archive, identical directory and `DockerFile` give identical bytes
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (path, entry) in self.entries()? {
+            match entry {
+                Entry::Dir => {
+                    self.header(&mut writer, &format!("{}/", path), 0o755, 0, b'5', "")?;
+                }
+                Entry::Symlink(target) => {
+                    self.header(&mut writer, &path, 0o777, 0, b'2', &target)?;
+                }
+                Entry::Data(data) => {
+                    self.header(&mut writer, &path, 0o644, data.len() as u64, b'0', "")?;
+                    writer.write_all(&data)?;
+                    pad(&mut writer, data.len() as u64)?;
+                }
+                Entry::File {
+                    path: source,
+                    size,
+                    executable,
+                } => {
+                    let mode = if executable { 0o755 } else { 0o644 };
+                    self.header(&mut writer, &path, mode, size, b'0', "")?;
+                    let copied = io::copy(&mut File::open(&source)?.take(size), &mut writer)?;
+                    if copied != size {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!("`{}` changed while it was read", source.display()),
+                        ));
+                    }
+                    pad(&mut writer, size)?;
+                }
+            }
+        }
+        writer.write_all(&[0; BLOCK * 2])
+    }
+
+    fn entries(&self) -> io::Result<BTreeMap<String, Entry>> {
+        let ignore = match &self.ignore {
+            Some(ignore) => ignore.clone(),
+            None => match fs::read_to_string(self.root.join(".dockerignore")) {
+                Ok(content) => DockerIgnore::parse(&content)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => DockerIgnore::new(),
+                Err(e) => return Err(e),
+            },
+        };
+
+        let mut entries = BTreeMap::new();
+        walk(&self.root, "", &ignore.matcher(), &mut entries)?;
+
+        // `Dockerfile` can't replace directory, be inside of file or outside of archive
+        let path = self.docker_file_path.as_str();
+        let outside = matches!(path, "" | "." | "..") || path.starts_with("../");
+        let is_dir = matches!(entries.get(path), Some(Entry::Dir))
+            || entries.keys().any(|entry| {
+                entry
+                    .strip_prefix(path)
+                    .is_some_and(|rest| rest.starts_with('/'))
+            });
+        let in_file = path.match_indices('/').any(|(i, _)| {
+            entries
+                .get(&path[..i])
+                .is_some_and(|entry| !matches!(entry, Entry::Dir))
+        });
+        if outside || is_dir || in_file {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid path of Dockerfile `{}`", path),
+            ));
+        }
+        entries.insert(
+            self.docker_file_path.clone(),
+            Entry::Data(self.docker_file.clone().into_bytes()),
+        );
+
+        // directories of included files are written even if they are ignored themselves
+        let parents: Vec<String> = entries
+            .keys()
+            .flat_map(|path| {
+                path.match_indices('/')
+                    .map(move |(i, _)| path[..i].to_string())
+            })
+            .collect();
+        for parent in parents {
+            entries.entry(parent).or_insert(Entry::Dir);
+        }
+        Ok(entries)
+    }
+
+    fn header<W: Write>(
+        &self,
+        writer: &mut W,
+        path: &str,
+        mode: u32,
+        size: u64,
+        kind: u8,
+        link: &str,
+    ) -> io::Result<()> {
+        let mut pax = Vec::new();
+        let (prefix, name) = match split(path) {
+            Some(split) => split,
+            None => {
+                pax.push(("path", path.to_string()));
+                ("", truncate(path, 100))
+            }
+        };
+        if link.len() > 100 {
+            pax.push(("linkpath", link.to_string()));
+        }
+        if size > 0o777_7777_7777 {
+            pax.push(("size", size.to_string()));
+        }
+
+        if !pax.is_empty() {
+            let mut records = Vec::new();
+            for (key, value) in pax {
+                records.extend(pax_record(key, &value));
+            }
+            let header = self.block("././@PaxHeader", "", 0o644, records.len() as u64, b'x', "");
+            writer.write_all(&header)?;
+            writer.write_all(&records)?;
+            pad(writer, records.len() as u64)?;
+        }
+
+        let size = size.min(0o777_7777_7777);
+        writer.write_all(&self.block(name, prefix, mode, size, kind, truncate(link, 100)))
+    }
+
+    fn block(
+        &self,
+        name: &str,
+        prefix: &str,
+        mode: u32,
+        size: u64,
+        kind: u8,
+        link: &str,
+    ) -> [u8; BLOCK] {
+        let mut block = [0; BLOCK];
+        block[..name.len()].copy_from_slice(name.as_bytes());
+        octal(&mut block[100..108], u64::from(mode));
+        octal(&mut block[108..116], 0);
+        octal(&mut block[116..124], 0);
+        octal(&mut block[124..136], size);
+        octal(&mut block[136..148], self.mtime);
+        block[148..156].copy_from_slice(b"        ");
+        block[156] = kind;
+        block[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        block[257..263].copy_from_slice(b"ustar\0");
+        block[263..265].copy_from_slice(b"00");
+        block[265..269].copy_from_slice(b"root");
+        block[297..301].copy_from_slice(b"root");
+        octal(&mut block[329..337], 0);
+        octal(&mut block[337..345], 0);
+        block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+
+        let checksum: u64 = block.iter().map(|b| u64::from(*b)).sum();
+        octal(&mut block[148..155], checksum);
+        block
+    }
+}
+
+/// Adds entries of `dir` that are not ignored, `prefix` is path of `dir` inside of archive
+fn walk(
+    dir: &Path,
+    prefix: &str,
+    ignore: &Matcher,
+    entries: &mut BTreeMap<String, Entry>,
+) -> io::Result<()> {
+    let mut children = Vec::new();
+    for child in fs::read_dir(dir)? {
+        let child = child?;
+        let name = child.file_name().into_string().map_err(|name| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("path `{}` is not valid UTF-8", dir.join(name).display()),
+            )
+        })?;
+        children.push((name, child.path()));
+    }
+    children.sort();
+
+    let has_exclusions = ignore.has_exclusions();
+    for (name, source) in children {
+        let path = format!("{}{}", prefix, name);
+        let ignored = ignore.is_ignored(&path);
+        let metadata = fs::symlink_metadata(&source)?;
+        if metadata.is_dir() {
+            // exclusion can include something from ignored directory back
+            if !ignored || has_exclusions {
+                if !ignored {
+                    entries.insert(path.clone(), Entry::Dir);
+                }
+                walk(&source, &format!("{}/", path), ignore, entries)?;
+            }
+        } else if ignored {
+            continue;
+        } else if metadata.file_type().is_symlink() {
+            let target = fs::read_link(&source)?;
+            let target = target.to_str().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("link `{}` is not valid UTF-8", source.display()),
+                )
+            })?;
+            entries.insert(path, Entry::Symlink(target.replace('\\', "/")));
+        } else if metadata.is_file() {
+            entries.insert(
+                path,
+                Entry::File {
+                    path: source,
+                    size: metadata.len(),
+                    executable: executable(&metadata),
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn executable(_: &fs::Metadata) -> bool {
+    false
+}
+
+/// Splits path into `prefix` and `name` fields of ustar header
+fn split(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    path.match_indices('/')
+        .map(|(i, _)| (&path[..i], &path[i + 1..]))
+        .find(|(prefix, name)| prefix.len() <= 155 && name.len() <= 100 && !name.is_empty())
+}
+
+fn truncate(s: &str, len: usize) -> &str {
+    let mut end = s.len().min(len);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    // length of the record includes its own digits
+    let rest = key.len() + value.len() + 3;
+    let mut len = rest + rest.to_string().len();
+    if len.to_string().len() != rest.to_string().len() {
+        len += 1;
+    }
+    format!("{} {}={}\n", len, key, value).into_bytes()
+}
+
+/// Writes zero-padded octal number followed by NUL
+fn octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:o}", value);
+    let width = field.len() - 1;
+    let digits = format!("{:0>width$}", digits, width = width);
+    field[..width].copy_from_slice(&digits.as_bytes()[digits.len() - width..]);
+    field[width] = 0;
+}
+
+fn pad<W: Write>(writer: &mut W, size: u64) -> io::Result<()> {
+    let rest = (size % BLOCK as u64) as usize;
+    if rest == 0 {
+        Ok(())
+    } else {
+        writer.write_all(&[0; BLOCK][rest..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FROM;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dockerfile-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src/bin")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        fs::write(dir.join("Cargo.toml"), "[package]\n").unwrap();
+        fs::write(dir.join("src/main.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("src/bin/tool.rs"), "fn main() {}\n").unwrap();
+        fs::write(dir.join("target/debug/app"), "binary").unwrap();
+        fs::write(dir.join("target/debug/keep"), "").unwrap();
+        fs::write(dir.join("Dockerfile"), "FROM scratch\n").unwrap();
+        dir
+    }
+
+    /// Names, modes and contents of archive entries, content of symlink is its target
+    fn read(archive: &[u8]) -> Vec<(String, String, String)> {
+        let field = |bytes: &[u8]| {
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            String::from_utf8(bytes[..end].to_vec()).unwrap()
+        };
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while archive[offset..offset + BLOCK] != [0; BLOCK][..] {
+            let header = &archive[offset..offset + BLOCK];
+            let checksum: u64 = header[..148]
+                .iter()
+                .chain(&[b' '; 8])
+                .chain(&header[156..])
+                .map(|b| u64::from(*b))
+                .sum();
+            assert_eq!(field(&header[148..155]), format!("{:06o}", checksum));
+            let prefix = field(&header[345..500]);
+            let name = field(&header[..100]);
+            let name = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            let mode = field(&header[100..108]);
+            let size = u64::from_str_radix(&field(&header[124..136]), 8).unwrap() as usize;
+            let content = match header[156] {
+                b'2' => field(&header[157..257]),
+                _ => field(&archive[offset + BLOCK..offset + BLOCK + size]),
+            };
+            entries.push((name, mode, content));
+            offset += BLOCK + size.div_ceil(BLOCK) * BLOCK;
+        }
+        assert_eq!(archive.len(), offset + BLOCK * 2);
+        entries
+    }
+
+    #[test]
+    fn reproducible() {
+        let root = dir("reproducible");
+        let docker_file = DockerFile::from(FROM!(rust)).copy((".", "/app"));
+        let context = Context::new(&root, &docker_file)
+            .ignore(DockerIgnore::parse("target\n!target/debug/keep\nsrc/bin").unwrap());
+
+        let mut first = Vec::new();
+        context.write(&mut first).unwrap();
+        fs::write(root.join("Cargo.toml"), "[package]\n").unwrap();
+        let mut second = Vec::new();
+        context.write(&mut second).unwrap();
+        assert_eq!(first, second);
+
+        let entry = |name: &str, mode: &str, content: &str| {
+            (name.to_string(), mode.to_string(), content.to_string())
+        };
+        let rendered = docker_file.to_string();
+        assert_eq!(
+            read(&first),
+            [
+                entry("Cargo.toml", "0000644", "[package]\n"),
+                entry("Dockerfile", "0000644", &rendered),
+                entry("src/", "0000755", ""),
+                entry("src/main.rs", "0000644", "fn main() {}\n"),
+                entry("target/", "0000755", ""),
+                entry("target/debug/", "0000755", ""),
+                entry("target/debug/keep", "0000644", ""),
+            ]
+        );
+
+        // without ignore rules and `.dockerignore` everything is included
+        let mut archive = Vec::new();
+        Context::new(&root, &docker_file)
+            .write(&mut archive)
+            .unwrap();
+        assert_eq!(
+            read(&archive),
+            [
+                entry("Cargo.toml", "0000644", "[package]\n"),
+                entry("Dockerfile", "0000644", &rendered),
+                entry("src/", "0000755", ""),
+                entry("src/bin/", "0000755", ""),
+                entry("src/bin/tool.rs", "0000644", "fn main() {}\n"),
+                entry("src/main.rs", "0000644", "fn main() {}\n"),
+                entry("target/", "0000755", ""),
+                entry("target/debug/", "0000755", ""),
+                entry("target/debug/app", "0000644", "binary"),
+                entry("target/debug/keep", "0000644", ""),
+            ]
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{symlink, PermissionsExt};
+
+            fs::set_permissions(
+                root.join("target/debug/app"),
+                fs::Permissions::from_mode(0o755),
+            )
+            .unwrap();
+            symlink("debug/app", root.join("target/app")).unwrap();
+            let mut archive = Vec::new();
+            Context::new(&root, &docker_file)
+                .ignore(DockerIgnore::parse("src\ntarget/debug/keep").unwrap())
+                .write(&mut archive)
+                .unwrap();
+            assert_eq!(
+                read(&archive),
+                [
+                    entry("Cargo.toml", "0000644", "[package]\n"),
+                    entry("Dockerfile", "0000644", &rendered),
+                    entry("target/", "0000755", ""),
+                    entry("target/app", "0000777", "debug/app"),
+                    entry("target/debug/", "0000755", ""),
+                    entry("target/debug/app", "0000755", "binary"),
+                ]
+            );
+        }
+
+        fs::write(root.join(".dockerignore"), "target\nsrc\nDockerfile\n").unwrap();
+        let (long, longer) = ("a".repeat(90), "b".repeat(120));
+        let context = Context::new(&root, &docker_file)
+            .docker_file_path(format!("./docker/{}/{}", long, longer))
+            .mtime(1);
+        assert_eq!(
+            context.paths().unwrap(),
+            [
+                ".dockerignore".to_string(),
+                "Cargo.toml".to_string(),
+                "docker/".to_string(),
+                format!("docker/{}/", long),
+                format!("docker/{}/{}", long, longer),
+            ]
+        );
+        let mut archive = Vec::new();
+        context.write(&mut archive).unwrap();
+        let entries = read(&archive);
+        assert_eq!(entries[3].0, format!("docker/{}/", long));
+        assert_eq!(entries[4].0, "././@PaxHeader");
+        assert_eq!(
+            entries[4].2,
+            format!("228 path=docker/{}/{}\n", long, longer)
+        );
+        assert_eq!(entries[5].2, docker_file.to_string());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn docker_file_path() {
+        let root = dir("docker-file-path");
+        let docker_file = DockerFile::from(FROM!(rust));
+        let paths = |path: &str| {
+            Context::new(&root, &docker_file)
+                .docker_file_path(path)
+                .paths()
+        };
+
+        assert!(paths("src/Dockerfile")
+            .unwrap()
+            .contains(&"src/Dockerfile".to_string()));
+        assert!(paths("./Cargo.toml")
+            .unwrap()
+            .contains(&"Cargo.toml".to_string()));
+        for path in &[
+            "",
+            ".",
+            "/",
+            "src",
+            "src/bin/",
+            "../Dockerfile",
+            "Cargo.toml/Dockerfile",
+        ] {
+            let err = paths(path).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", path);
+        }
+        assert_eq!(
+            paths("target/../src").unwrap_err().to_string(),
+            "invalid path of Dockerfile `src`"
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
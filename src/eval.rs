@@ -0,0 +1,770 @@
+//! Expansion of `$VAR` and `${VAR}` references the way Docker does it during build
+//!
+//! [`Evaluator`] walks [`DockerFile`] in order: global `ARG`s are visible in `FROM`, `ARG` and
+//! `ENV` inside stage are visible in the following instructions, `ENV` has priority over `ARG`
+//! and stage based on another stage inherits its `ENV`. References are substituted in `FROM`,
+//! `ENV`, `LABEL`, `ARG`, `ADD`, `COPY`, `WORKDIR`, `USER`, `VOLUME` and `STOPSIGNAL`,
+//! commands of `RUN`, `CMD` and `ENTRYPOINT` are left for the shell.
+//!
+//! Supported forms are `${VAR:-default}`, `${VAR-default}`, `${VAR:+alternative}`,
+//! `${VAR+alternative}`, `${VAR:?error}`, `${VAR?error}`, prefix removal `${VAR#glob}` and
+//! `${VAR##glob}`, suffix removal `${VAR%glob}` and `${VAR%%glob}` and replacement
+//! `${VAR/glob/replacement}` and `${VAR//glob/replacement}`.
+//! # Example
+//! ```rust
+//! use std::collections::HashMap;
+//! use dockerfile_rs::DockerFile;
+//!
+//! let docker_file = DockerFile::parse(
+//!     "ARG VERSION=1.33\nFROM rust:$VERSION\nARG VERSION\nENV DIR=/opt/app-${VERSION%.*}\nWORKDIR $DIR",
+//! )
+//! .unwrap();
+//! let mut build_args = HashMap::new();
+//! build_args.insert("VERSION".to_string(), "1.34.2".to_string());
+//! assert_eq!(
+//!     docker_file.substitute(&build_args).unwrap().to_string(),
+//!     "ARG VERSION=\"1.34.2\"\n\nFROM rust:1.34.2\n\nARG VERSION=\"1.34.2\"\n\
+//!      ENV DIR=\"/opt/app-1.34\"\nWORKDIR \"/opt/app-1.34\"\n"
+//! );
+//! ```
+//!
+//! [`Evaluator`]: struct.Evaluator.html
+//! [`DockerFile`]: ../struct.DockerFile.html
+
+use crate::{
+    parser, Add, Arg, Copy, DockerFile, Env, Expose, ExposedPort, From, InstructionKind, Label,
+    Platform, Source, StopSignal, User, Value, Volume, WorkDir,
+};
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+    iter::Peekable,
+    str::Chars,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExpandError {
+    /// `${` is not closed
+    Unterminated(String),
+    /// Text inside of `${...}` is not a supported form
+    BadSubstitution(String),
+    /// `${VAR?message}` or `${VAR:?message}` references unset or empty variable
+    Unset { name: String, message: String },
+    /// Expanded word is not a valid value for the instruction, e.g. `EXPOSE $PORT` with `PORT=http`
+    InvalidValue(String),
+}
+
+impl Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExpandError::Unterminated(word) => write!(f, "missing `}}` in `{}`", word),
+            ExpandError::BadSubstitution(word) => write!(f, "bad substitution in `{}`", word),
+            ExpandError::Unset { name, message } if message.is_empty() => {
+                write!(f, "`{}` is not set", name)
+            }
+            ExpandError::Unset { name, message } => write!(f, "`{}`: {}", name, message),
+            ExpandError::InvalidValue(value) => write!(f, "invalid value `{}`", value),
+        }
+    }
+}
+
+impl Error for ExpandError {}
+
+/// `ARG` and `ENV` values visible to an instruction, `ENV` has priority over `ARG`
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Variables {
+    args: Vec<(String, String)>,
+    env: Vec<(String, String)>,
+}
+
+impl Variables {
+    pub fn new() -> Self {
+        Variables::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .chain(&self.args)
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_arg<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        set(&mut self.args, name.into(), value.into());
+    }
+
+    pub fn set_env<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        set(&mut self.env, name.into(), value.into());
+    }
+
+    /// `ARG`s in the order they were declared
+    pub fn args(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.args.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// `ENV` variables in the order they were set, they are stored in the image
+    pub fn env(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.env.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+fn set(pairs: &mut Vec<(String, String)>, name: String, value: String) {
+    match pairs.iter_mut().find(|(k, _)| *k == name) {
+        Some((_, v)) => *v = value,
+        None => pairs.push((name, value)),
+    }
+}
+
+/// Substitutes references in `word`, `\` escapes the next character like in expanding [`Value`]
+///
+/// Unset variables are replaced with empty string.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::eval::{expand, Variables};
+///
+/// let mut variables = Variables::new();
+/// variables.set_env("FILE", "archive.tar.gz");
+/// assert_eq!(expand("${FILE%%.*}-${MISSING:-none} \\$5", &variables).unwrap(), "archive-none $5");
+/// assert_eq!(expand("${FILE/tar/zip}", &variables).unwrap(), "archive.zip.gz");
+/// ```
+///
+/// [`Value`]: ../struct.Value.html
+pub fn expand(word: &str, variables: &Variables) -> Result<String, ExpandError> {
+    Expander::new(word, variables, true).all()
+}
+
+/// Substitutes references in text that has no escapes, like paths and names
+fn expand_raw(word: &str, variables: &Variables) -> Result<String, ExpandError> {
+    Expander::new(word, variables, false).all()
+}
+
+fn expand_value(value: &Value, variables: &Variables) -> Result<Value, ExpandError> {
+    if value.expand {
+        expand(&value.value, variables).map(Value::literal)
+    } else {
+        Ok(value.clone())
+    }
+}
+
+struct Expander<'a> {
+    word: &'a str,
+    chars: Peekable<Chars<'a>>,
+    variables: &'a Variables,
+    escapes: bool,
+}
+
+impl<'a> Expander<'a> {
+    fn new(word: &'a str, variables: &'a Variables, escapes: bool) -> Self {
+        Expander {
+            word,
+            chars: word.chars().peekable(),
+            variables,
+            escapes,
+        }
+    }
+
+    fn all(mut self) -> Result<String, ExpandError> {
+        self.until(&[], false).map(|(text, _)| text)
+    }
+
+    /// Expands text until one of `ends`, returns the text and the end that was found
+    ///
+    /// With `pattern` escapes are kept, so glob characters stay literal in matching.
+    fn until(&mut self, ends: &[char], pattern: bool) -> Result<(String, char), ExpandError> {
+        let mut result = String::new();
+        while let Some(c) = self.chars.next() {
+            match c {
+                c if ends.contains(&c) => return Ok((result, c)),
+                '\\' if self.escapes => match self.chars.next() {
+                    Some(next) => {
+                        if pattern {
+                            result.push('\\');
+                        }
+                        result.push(next);
+                    }
+                    None => result.push('\\'),
+                },
+                '$' => {
+                    let value = self.variable()?;
+                    if pattern {
+                        result.push_str(&escape_glob(&value));
+                    } else {
+                        result.push_str(&value);
+                    }
+                }
+                c => result.push(c),
+            }
+        }
+
+        if ends.is_empty() {
+            Ok((result, '\0'))
+        } else {
+            Err(ExpandError::Unterminated(self.word.to_string()))
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                name.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        name
+    }
+
+    /// Expands reference after `$`
+    fn variable(&mut self) -> Result<String, ExpandError> {
+        if self.chars.peek() != Some(&'{') {
+            let name = self.name();
+            if name.is_empty() {
+                return Ok("$".to_string());
+            }
+            return Ok(self.variables.get(&name).unwrap_or_default().to_string());
+        }
+
+        self.chars.next();
+        let name = self.name();
+        if name.is_empty() {
+            return Err(self.bad_substitution());
+        }
+        let value = self.variables.get(&name);
+        let modifier = match self.chars.next() {
+            Some(c) => c,
+            None => return Err(ExpandError::Unterminated(self.word.to_string())),
+        };
+        let (modifier, empty_is_unset) = match modifier {
+            '}' => return Ok(value.unwrap_or_default().to_string()),
+            ':' => match self.chars.next() {
+                Some(c) if c == '-' || c == '+' || c == '?' => (c, true),
+                _ => return Err(self.bad_substitution()),
+            },
+            c => (c, false),
+        };
+        let is_set = match value {
+            Some(value) => !(empty_is_unset && value.is_empty()),
+            None => false,
+        };
+        let value = value.unwrap_or_default().to_string();
+
+        match modifier {
+            '-' => {
+                let (word, _) = self.until(&['}'], false)?;
+                Ok(if is_set { value } else { word })
+            }
+            '+' => {
+                let (word, _) = self.until(&['}'], false)?;
+                Ok(if is_set { word } else { String::new() })
+            }
+            '?' => {
+                let (message, _) = self.until(&['}'], false)?;
+                if is_set {
+                    Ok(value)
+                } else {
+                    Err(ExpandError::Unset { name, message })
+                }
+            }
+            '#' | '%' => {
+                let longest = self.chars.peek() == Some(&modifier);
+                if longest {
+                    self.chars.next();
+                }
+                let (pattern, _) = self.until(&['}'], true)?;
+                let pattern: Vec<char> = pattern.chars().collect();
+                let chars: Vec<char> = value.chars().collect();
+                let mut lengths: Vec<usize> = (0..=chars.len()).collect();
+                if longest {
+                    lengths.reverse();
+                }
+                let removed = lengths.into_iter().find_map(|len| {
+                    if modifier == '#' && matches(&pattern, &chars[..len]) {
+                        Some(chars[len..].iter().collect())
+                    } else if modifier == '%' && matches(&pattern, &chars[chars.len() - len..]) {
+                        Some(chars[..chars.len() - len].iter().collect())
+                    } else {
+                        None
+                    }
+                });
+                Ok(removed.unwrap_or(value))
+            }
+            '/' => {
+                let all = self.chars.peek() == Some(&'/');
+                if all {
+                    self.chars.next();
+                }
+                let (pattern, end) = self.until(&['/', '}'], true)?;
+                let replacement = match end {
+                    '/' => self.until(&['}'], false)?.0,
+                    _ => String::new(),
+                };
+                Ok(replace(&value, &pattern, &replacement, all))
+            }
+            _ => Err(self.bad_substitution()),
+        }
+    }
+
+    fn bad_substitution(&self) -> ExpandError {
+        ExpandError::BadSubstitution(self.word.to_string())
+    }
+}
+
+/// Escapes glob characters of expanded variable inside of pattern
+fn escape_glob(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for c in s.chars() {
+        if c == '*' || c == '?' || c == '\\' {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Matches the whole `text` with glob `pattern` supporting `*`, `?` and `\` escapes
+fn matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && matches(rest, &text[1..]),
+        Some(('\\', rest)) if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && matches(&rest[1..], &text[1..])
+        }
+        Some((c, rest)) => text.first() == Some(c) && matches(rest, &text[1..]),
+    }
+}
+
+/// Replaces the longest match of `pattern` at the first position where it matches
+fn replace(value: &str, pattern: &str, replacement: &str, all: bool) -> String {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let chars: Vec<char> = value.chars().collect();
+    if pattern.is_empty() {
+        return value.to_string();
+    }
+
+    let mut result = String::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + 1..=chars.len())
+            .rev()
+            .find(|&end| matches(&pattern, &chars[start..end]));
+        match end {
+            Some(end) => {
+                result.push_str(replacement);
+                start = end;
+                if !all {
+                    break;
+                }
+            }
+            None => {
+                result.push(chars[start]);
+                start += 1;
+            }
+        }
+    }
+    result.extend(&chars[start..]);
+    result
+}
+
+/// Substitutes variables in [`DockerFile`] for given build arguments
+///
+/// [`DockerFile`]: ../struct.DockerFile.html
+#[derive(Debug, Clone, Default)]
+pub struct Evaluator {
+    build_args: HashMap<String, String>,
+}
+
+impl Evaluator {
+    pub fn new(build_args: HashMap<String, String>) -> Self {
+        Evaluator { build_args }
+    }
+
+    /// Build argument given with `--build-arg`
+    pub fn build_arg<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+        self.build_args.insert(name.into(), value.into());
+        self
+    }
+
+    /// Copy of `docker_file` with references substituted
+    pub fn substitute(&self, docker_file: &DockerFile) -> Result<DockerFile, ExpandError> {
+        self.evaluate(docker_file)
+            .map(|(docker_file, _)| docker_file)
+    }
+
+    /// Variables visible at the end of every stage
+    pub fn variables(&self, docker_file: &DockerFile) -> Result<Vec<Variables>, ExpandError> {
+        self.evaluate(docker_file).map(|(_, variables)| variables)
+    }
+
+    fn evaluate(
+        &self,
+        docker_file: &DockerFile,
+    ) -> Result<(DockerFile, Vec<Variables>), ExpandError> {
+        let mut result = docker_file.clone();
+        let mut global = Variables::new();
+        for instruction in result.global_mut() {
+            if let InstructionKind::Arg(arg) = instruction {
+                *arg = self.arg(arg, &mut global, &Variables::new())?;
+            }
+        }
+
+        let mut stages: Vec<(Option<String>, Variables)> = Vec::new();
+        for stage in result.stages_mut() {
+            let from = self.from(stage.base(), &global)?;
+            let parent = stages
+                .iter()
+                .find(|(name, _)| {
+                    name.as_ref()
+                        .is_some_and(|name| name.eq_ignore_ascii_case(&from.image))
+                })
+                .map(|(_, variables)| variables);
+            let mut variables = Variables::new();
+            for (name, value) in parent.iter().flat_map(|variables| variables.env()) {
+                variables.set_env(name, value);
+            }
+            *stage.base_mut() = from;
+
+            for instruction in stage.instructions_mut() {
+                *instruction = self.instruction(instruction, &mut variables, &global)?;
+            }
+            stages.push((stage.name().map(String::from), variables));
+        }
+
+        Ok((result, stages.into_iter().map(|(_, v)| v).collect()))
+    }
+
+    /// Resolves `ARG` and declares it, global `ARG` without default inherits global value
+    fn arg(
+        &self,
+        arg: &Arg,
+        variables: &mut Variables,
+        global: &Variables,
+    ) -> Result<Arg, ExpandError> {
+        let default = match &arg.value {
+            Some(value) => Some(expand_value(value, variables)?.value),
+            None => global
+                .args()
+                .find(|(name, _)| *name == arg.name)
+                .map(|(_, value)| value.to_string()),
+        };
+        let value = self.build_args.get(&arg.name).cloned().or(default);
+        if let Some(value) = &value {
+            variables.set_arg(arg.name.as_str(), value.as_str());
+        }
+        Ok(Arg {
+            name: arg.name.clone(),
+            value: value.map(Value::literal),
+        })
+    }
+
+    fn from(&self, from: &From, global: &Variables) -> Result<From, ExpandError> {
+        // variable can hold the whole reference, like `rust:1.75` of `FROM $IMAGE`
+        let (image, tag_or_digest) =
+            parser::reference(&expand_raw(&from.image_reference(), global)?);
+        let platform = match &from.platform {
+            // automatic platform args like `BUILDPLATFORM` are set by the builder, so variable
+            // that expands to nothing is kept
+            Some(platform) => match expand_raw(&platform.to_string(), global)? {
+                expanded if expanded.is_empty() => Some(platform.clone()),
+                expanded => Some(Platform::from(expanded)),
+            },
+            None => None,
+        };
+        Ok(From {
+            image,
+            tag_or_digest,
+            name: from.name.clone(),
+            platform,
+        })
+    }
+
+    fn instruction(
+        &self,
+        instruction: &InstructionKind,
+        variables: &mut Variables,
+        global: &Variables,
+    ) -> Result<InstructionKind, ExpandError> {
+        let raw = |s: &str| expand_raw(s, variables);
+        let user = |user: &User| -> Result<User, ExpandError> {
+            Ok(User {
+                user: raw(&user.user)?,
+                group: user.group.as_deref().map(raw).transpose()?,
+            })
+        };
+        let sources = |src: &[Source]| -> Result<Vec<Source>, ExpandError> {
+            src.iter()
+                .map(|source| match source {
+                    Source::Path(path) => raw(path).map(Source::Path),
+                    Source::Heredoc(heredoc) => Ok(Source::Heredoc(heredoc.clone())),
+                })
+                .collect()
+        };
+        let exclude = |exclude: &[String]| -> Result<Vec<String>, ExpandError> {
+            exclude.iter().map(|pattern| raw(pattern)).collect()
+        };
+
+        let instruction = match instruction {
+            InstructionKind::Arg(arg) => InstructionKind::Arg(self.arg(arg, variables, global)?),
+            InstructionKind::Env(env) => {
+                // every value sees variables from before the instruction
+                let mut pairs = Vec::new();
+                for (name, value) in env.iter() {
+                    pairs.push((name.to_string(), expand_value(value, variables)?));
+                }
+                for (name, value) in &pairs {
+                    variables.set_env(name.as_str(), value.value.as_str());
+                }
+                InstructionKind::Env(Env::from(pairs))
+            }
+            InstructionKind::Label(label) => {
+                let mut pairs = Vec::new();
+                for (key, value) in label.iter() {
+                    pairs.push((raw(key)?, expand_value(value, variables)?));
+                }
+                InstructionKind::Label(Label::from(pairs))
+            }
+            InstructionKind::WorkDir(work_dir) => InstructionKind::WorkDir(WorkDir {
+                path: raw(&work_dir.path)?,
+            }),
+            InstructionKind::User(u) => InstructionKind::User(user(u)?),
+            InstructionKind::Volume(volume) => InstructionKind::Volume(Volume {
+                paths: volume
+                    .paths
+                    .iter()
+                    .map(|path| raw(path))
+                    .collect::<Result<_, _>>()?,
+            }),
+            InstructionKind::Expose(expose) => {
+                let mut ports = Vec::new();
+                for port in &expose.ports {
+                    match port {
+                        ExposedPort::Port(_) => ports.push(port.clone()),
+                        // one variable can hold several ports, like `EXPOSE $PORTS`
+                        ExposedPort::Variable(word) => {
+                            for text in expand(word, variables)?.split_whitespace() {
+                                let port = parser::port(text)
+                                    .ok_or_else(|| ExpandError::InvalidValue(text.to_string()))?;
+                                ports.push(ExposedPort::Port(port));
+                            }
+                        }
+                    }
+                }
+                InstructionKind::Expose(Expose { ports })
+            }
+            InstructionKind::StopSignal(signal) => InstructionKind::StopSignal(StopSignal {
+                signal: raw(&signal.signal)?,
+            }),
+            InstructionKind::Add(add) => InstructionKind::Add(Add {
+                src: sources(&add.src)?,
+                dst: raw(&add.dst)?,
+                chown: add.chown.as_ref().map(user).transpose()?,
+                checksum: add.checksum.as_deref().map(raw).transpose()?,
+                exclude: exclude(&add.exclude)?,
+                ..add.clone()
+            }),
+            InstructionKind::Copy(copy) => InstructionKind::Copy(Copy {
+                src: sources(&copy.src)?,
+                dst: raw(&copy.dst)?,
+                from: copy.from.as_deref().map(raw).transpose()?,
+                chown: copy.chown.as_ref().map(user).transpose()?,
+                exclude: exclude(&copy.exclude)?,
+                ..copy.clone()
+            }),
+            instruction => instruction.clone(),
+        };
+        Ok(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tag;
+
+    #[test]
+    fn modifiers() {
+        let mut variables = Variables::new();
+        variables.set_arg("EMPTY", "");
+        variables.set_arg("NAME", "value");
+        variables.set_env("PATH", "/usr/local/bin:/usr/bin:/bin");
+        let expand = |s: &str| super::expand(s, &variables);
+
+        assert_eq!(
+            expand("$NAME-${NAME}_$NAME_ $ $5").unwrap(),
+            "value-value_ $ "
+        );
+        assert_eq!(
+            expand("${MISSING:-a}${EMPTY:-b}${NAME:-c}").unwrap(),
+            "abvalue"
+        );
+        assert_eq!(expand("${MISSING-a}${EMPTY-b}${NAME-c}").unwrap(), "avalue");
+        assert_eq!(expand("${MISSING:+a}${EMPTY:+b}${NAME:+c}").unwrap(), "c");
+        assert_eq!(expand("${MISSING+a}${EMPTY+b}${NAME+c}").unwrap(), "bc");
+        assert_eq!(expand("${MISSING:-${NAME:+nested}}").unwrap(), "nested");
+        assert_eq!(expand(r"\$NAME \\${NAME}").unwrap(), r"$NAME \value");
+
+        assert_eq!(expand("${PATH#*:}").unwrap(), "/usr/bin:/bin");
+        assert_eq!(expand("${PATH##*:}").unwrap(), "/bin");
+        assert_eq!(expand("${PATH%:*}").unwrap(), "/usr/local/bin:/usr/bin");
+        assert_eq!(expand("${PATH%%:*}").unwrap(), "/usr/local/bin");
+        assert_eq!(expand("${PATH#/usr}").unwrap(), "/local/bin:/usr/bin:/bin");
+        assert_eq!(
+            expand("${PATH#nothing}").unwrap(),
+            "/usr/local/bin:/usr/bin:/bin"
+        );
+        assert_eq!(expand("${NAME#?a}").unwrap(), "lue");
+        assert_eq!(expand("${NAME/a/A}").unwrap(), "vAlue");
+        assert_eq!(
+            expand("${PATH//bin/sbin}").unwrap(),
+            "/usr/local/sbin:/usr/sbin:/sbin"
+        );
+        assert_eq!(expand("${PATH/:*/}").unwrap(), "/usr/local/bin");
+        assert_eq!(expand("${NAME/l*}").unwrap(), "va");
+        assert_eq!(expand(r"${NAME/\*/x}").unwrap(), "value");
+
+        assert_eq!(
+            expand("${EMPTY:?is required}"),
+            Err(ExpandError::Unset {
+                name: "EMPTY".to_string(),
+                message: "is required".to_string(),
+            })
+        );
+        assert_eq!(expand("${EMPTY?is required}").unwrap(), "");
+        assert_eq!(
+            expand("${NAME"),
+            Err(ExpandError::Unterminated("${NAME".to_string()))
+        );
+        assert_eq!(
+            expand("${NAME:x}"),
+            Err(ExpandError::BadSubstitution("${NAME:x}".to_string()))
+        );
+        assert_eq!(
+            expand("${}"),
+            Err(ExpandError::BadSubstitution("${}".to_string()))
+        );
+    }
+
+    #[test]
+    fn scopes() {
+        let content = r#"ARG BASE=debian
+ARG TAG=stable
+ARG UNUSED=1
+FROM $BASE:$TAG AS base
+ENV HOME=/root APP=app
+ENV DIR=$HOME/$APP OLD=$DIR
+LABEL "$APP.version"="${VERSION:-dev}"
+ARG TAG
+ARG USER=nobody
+USER $USER:${GROUP:-$USER}
+COPY --chown=$USER --from=${BASE:-builder} --exclude=$APP.md ./$APP $DIR/
+RUN echo $HOME
+
+FROM base
+ARG HOME=/home
+WORKDIR $HOME/$TAG
+VOLUME $UNUSED
+"#;
+        let docker_file = DockerFile::parse(content).unwrap();
+        let evaluator = Evaluator::default().build_arg("TAG", "bookworm");
+        assert_eq!(
+            evaluator.substitute(&docker_file).unwrap().to_string(),
+            r#"ARG BASE="debian"
+ARG TAG="bookworm"
+ARG UNUSED="1"
+
+FROM debian:bookworm AS base
+
+ENV HOME="/root" APP="app"
+ENV DIR="/root/app" OLD=""
+LABEL app.version="dev"
+ARG TAG="bookworm"
+ARG USER="nobody"
+USER nobody:nobody
+COPY --from=builder --chown=nobody --exclude=app.md "./app" "/root/app/"
+RUN echo $HOME
+
+FROM base
+
+ARG HOME="/home"
+WORKDIR "/root/"
+VOLUME [""]
+"#
+        );
+
+        let variables = evaluator.variables(&docker_file).unwrap();
+        assert_eq!(
+            variables[1].env().collect::<Vec<_>>(),
+            [
+                ("HOME", "/root"),
+                ("APP", "app"),
+                ("DIR", "/root/app"),
+                ("OLD", "")
+            ]
+        );
+        assert_eq!(variables[1].get("HOME"), Some("/root"));
+        assert_eq!(variables[1].args().collect::<Vec<_>>(), [("HOME", "/home")]);
+    }
+
+    #[test]
+    fn from() {
+        let content =
+            "ARG IMAGE=rust:1.75\nARG DIGEST\nFROM $IMAGE\nFROM ${BASE:-debian:12}\nFROM $IMAGE@$DIGEST";
+        let docker_file = DockerFile::parse(content).unwrap();
+        let substituted = Evaluator::default()
+            .build_arg("DIGEST", "sha256:abc")
+            .substitute(&docker_file)
+            .unwrap();
+        let stages = substituted.stages();
+        assert_eq!(stages[0].base().image, "rust");
+        assert_eq!(
+            stages[0].base().tag_or_digest,
+            Some(Tag("1.75".to_string()))
+        );
+        assert_eq!(stages[1].base().image, "debian");
+        assert_eq!(stages[1].base().tag_or_digest, Some(Tag("12".to_string())));
+        assert_eq!(stages[2].base().to_string(), "FROM rust:1.75@sha256:abc");
+
+        let docker_file = DockerFile::parse(
+            "ARG PLATFORM=linux/arm64\nFROM --platform=$BUILDPLATFORM rust AS b\nFROM --platform=$PLATFORM b",
+        )
+        .unwrap();
+        let substituted = Evaluator::default().substitute(&docker_file).unwrap();
+        assert_eq!(
+            substituted.stages()[0].base().to_string(),
+            "FROM --platform=$BUILDPLATFORM rust AS b"
+        );
+        assert_eq!(
+            substituted.stages()[1].base().to_string(),
+            "FROM --platform=linux/arm64 b"
+        );
+    }
+
+    #[test]
+    fn expose() {
+        let docker_file = DockerFile::parse(
+            "FROM rust
+ARG PORT=8080
+ARG PORTS
+EXPOSE 80 $PORT/udp $PORTS",
+        )
+        .unwrap();
+        let substituted = Evaluator::default()
+            .build_arg("PORTS", "53/udp 9000-9001")
+            .substitute(&docker_file)
+            .unwrap();
+        assert_eq!(
+            substituted.stages()[0].instructions()[2].to_string(),
+            "EXPOSE 80 8080/udp 53/udp 9000-9001"
+        );
+
+        let error = Evaluator::default()
+            .build_arg("PORT", "http")
+            .substitute(&docker_file)
+            .unwrap_err();
+        assert_eq!(error, ExpandError::InvalidValue("http/udp".to_string()));
+    }
+}
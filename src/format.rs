@@ -0,0 +1,392 @@
+//! Configurable layout of rendered [`DockerFile`]
+//!
+//! `Display` of [`DockerFile`] is the same as [`Formatter::default`]. Formatter can wrap long
+//! shell commands of `RUN` at `&&`, write every pair of `ENV` and `LABEL` on its own line,
+//! write keywords in lowercase, put fewer blank lines and drop comments. Instructions are grouped
+//! like in `Display`: `MAINTAINER`, the rest, `ONBUILD`, `ENTRYPOINT` and `CMD`.
+//! # Example
+//! ```rust
+//! use dockerfile_rs::{format::{BlankLines, Case, Formatter}, DockerFile};
+//!
+//! let docker_file = DockerFile::parse(
+//!     "FROM debian\nENV A=1 B=2\nRUN apt-get update && apt-get install -y curl && rm -rf /var/lib/apt/lists/*\nCMD curl",
+//! ).unwrap();
+//! let formatter = Formatter::default()
+//!     .width(60)
+//!     .align(true)
+//!     .case(Case::Lower)
+//!     .blank_lines(BlankLines::Stages);
+//! assert_eq!(
+//!     docker_file.format(&formatter),
+//!     r#"from debian
+//! env A="1" \
+//!     B="2"
+//! run apt-get update \
+//!     && apt-get install -y curl \
+//!     && rm -rf /var/lib/apt/lists/*
+//! cmd curl
+//! "#
+//! );
+//! assert_eq!(docker_file.format(&Formatter::default()), docker_file.to_string());
+//! ```
+//!
+//! [`DockerFile`]: ../struct.DockerFile.html
+//! [`Formatter::default`]: struct.Formatter.html#impl-Default
+
+use crate::{quote, CommandForm, DockerFile, HealthCheck, InstructionKind, Run};
+
+/// Case of instruction keywords
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Case {
+    Upper,
+    Lower,
+}
+
+/// Where blank lines are written
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BlankLines {
+    /// Between directives, global instructions, stages and groups of instructions in stage
+    Sections,
+    /// Between directives, global instructions and stages
+    Stages,
+    /// Nowhere
+    None,
+}
+
+/// Layout options of [`DockerFile::format`]
+///
+/// [`DockerFile::format`]: ../struct.DockerFile.html#method.format
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Formatter {
+    width: Option<usize>,
+    align_env: bool,
+    align_label: bool,
+    case: Case,
+    blank_lines: BlankLines,
+    comments: bool,
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Formatter {
+            width: None,
+            align_env: false,
+            align_label: true,
+            case: Case::Upper,
+            blank_lines: BlankLines::Sections,
+            comments: true,
+        }
+    }
+}
+
+impl Formatter {
+    /// Everything on as few lines as possible, without blank lines and comments
+    pub fn compact() -> Self {
+        Formatter {
+            align_label: false,
+            blank_lines: BlankLines::None,
+            comments: false,
+            ..Formatter::default()
+        }
+    }
+
+    /// Maximal line width, longer shell commands of `RUN` are wrapped at `&&` and longer `ENV`
+    /// and `LABEL` have a pair per line
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Writes every pair of `ENV` and `LABEL` with several pairs on its own line, keys are
+    /// aligned after the keyword, only `LABEL` is aligned by default
+    pub fn align(mut self, align: bool) -> Self {
+        self.align_env = align;
+        self.align_label = align;
+        self
+    }
+
+    pub fn case(mut self, case: Case) -> Self {
+        self.case = case;
+        self
+    }
+
+    pub fn blank_lines(mut self, blank_lines: BlankLines) -> Self {
+        self.blank_lines = blank_lines;
+        self
+    }
+
+    /// Writes comments, enabled by default
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    pub fn format(&self, docker_file: &DockerFile) -> String {
+        let escape = docker_file.escape();
+        // `Display` of instructions writes escapes with the current escape character
+        quote::with_escape(escape, || {
+            // lines of every section and whether it starts directives, global instructions or stage
+            let mut sections: Vec<(bool, Vec<String>)> = Vec::new();
+            sections.push((
+                true,
+                docker_file
+                    .directives()
+                    .iter()
+                    .map(|d| d.to_string())
+                    .collect(),
+            ));
+            sections.push((true, self.instructions(docker_file.global().iter(), escape)));
+            for stage in docker_file.stages() {
+                sections.push((true, vec![self.keyword_case(&stage.base().to_string())]));
+                for instructions in stage.sections().iter() {
+                    sections.push((
+                        false,
+                        self.instructions(instructions.iter().copied(), escape),
+                    ));
+                }
+            }
+
+            let mut output = String::new();
+            let mut top = false;
+            for (starts, lines) in sections {
+                top |= starts;
+                if lines.is_empty() {
+                    continue;
+                }
+                let blank = match self.blank_lines {
+                    BlankLines::Sections => true,
+                    BlankLines::Stages => top,
+                    BlankLines::None => false,
+                };
+                if blank && !output.is_empty() {
+                    output.push('\n');
+                }
+                top = false;
+                for line in lines {
+                    output.push_str(&line);
+                    output.push('\n');
+                }
+            }
+            output
+        })
+    }
+
+    fn instructions<'a, I>(&self, instructions: I, escape: char) -> Vec<String>
+    where
+        I: Iterator<Item = &'a InstructionKind>,
+    {
+        instructions
+            .filter(|i| self.comments || !matches!(i, InstructionKind::Comment(_)))
+            .map(|i| self.instruction(i, escape))
+            .collect()
+    }
+
+    fn instruction(&self, instruction: &InstructionKind, escape: char) -> String {
+        match instruction {
+            InstructionKind::Run(run) => self.run(run, escape),
+            InstructionKind::Env(env) => self.pairs(
+                "ENV",
+                env.iter().map(|(k, v)| format!("{}={}", k, v)).collect(),
+                self.align_env,
+                escape,
+            ),
+            InstructionKind::Label(label) => self.pairs(
+                "LABEL",
+                label
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", quote::label_key(k, escape), v))
+                    .collect(),
+                self.align_label,
+                escape,
+            ),
+            InstructionKind::OnBuild(on_build) => format!(
+                "{} {}",
+                self.keyword("ONBUILD"),
+                self.instruction(on_build.instruction(), escape)
+            ),
+            InstructionKind::HealthCheck(HealthCheck::Check { cmd, .. }) => {
+                let text = instruction.to_string();
+                let cmd = cmd.to_string();
+                let options = &text[..text.len() - cmd.len()];
+                format!("{}{}", self.keyword_case(options), self.keyword_case(&cmd))
+            }
+            InstructionKind::Comment(comment) => comment.to_string(),
+            instruction => self.keyword_case(&instruction.to_string()),
+        }
+    }
+
+    fn run(&self, run: &Run, escape: char) -> String {
+        let text = self.keyword_case(&run.to_string());
+        let width = match self.width {
+            Some(width) => width,
+            None => return text,
+        };
+        match &run.command {
+            CommandForm::Shell(command)
+                if !command.contains('\n') && text.chars().count() > width =>
+            {
+                let commands = split_and(command);
+                if commands.len() < 2 {
+                    return text;
+                }
+                let flags = &text[..text.len() - command.len()];
+                let separator = format!(" {}\n    && ", escape);
+                format!("{}{}", flags, commands.join(&separator))
+            }
+            _ => text,
+        }
+    }
+
+    fn pairs(&self, keyword: &str, pairs: Vec<String>, align: bool, escape: char) -> String {
+        let keyword = self.keyword(keyword);
+        let line = format!("{} {}", keyword, pairs.join(" "));
+        let too_wide = self.width.is_some_and(|width| line.chars().count() > width);
+        if (align || too_wide) && pairs.len() > 1 {
+            let separator = format!(" {}\n{:width$}", escape, "", width = keyword.len() + 1);
+            format!("{} {}", keyword, pairs.join(&separator))
+        } else {
+            line
+        }
+    }
+
+    fn keyword(&self, keyword: &str) -> String {
+        match self.case {
+            Case::Upper => keyword.to_string(),
+            Case::Lower => keyword.to_lowercase(),
+        }
+    }
+
+    /// Changes case of keyword at the start of `text` and of ` AS ` of `FROM`
+    fn keyword_case(&self, text: &str) -> String {
+        if self.case == Case::Upper {
+            return text.to_string();
+        }
+        let (keyword, rest) = text.split_at(text.find(' ').unwrap_or(text.len()));
+        let rest = if keyword == "FROM" {
+            match rest.rfind(" AS ") {
+                Some(i) => format!("{} as {}", &rest[..i], &rest[i + 4..]),
+                None => rest.to_string(),
+            }
+        } else {
+            rest.to_string()
+        };
+        format!("{}{}", self.keyword(keyword), rest)
+    }
+}
+
+/// Splits shell command at `&&` outside of quotes, parts are trimmed
+fn split_and(command: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = command.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some('"'), '\\') | (None, '\\') => {
+                chars.next();
+            }
+            (None, '&') if matches!(chars.peek(), Some((_, '&'))) => {
+                chars.next();
+                parts.push(command[start..i].trim());
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(command[start..].trim());
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCKER_FILE: &str = r#"# syntax=docker/dockerfile:1
+ARG TAG=1
+# base image
+FROM rust:$TAG AS builder
+MAINTAINER someone
+ENV A=1 B=2
+LABEL a=1 b=2
+RUN --network=none echo "a && b" && echo 'c && d' && echo e\&\&f && true
+HEALTHCHECK --interval=5s CMD curl localhost
+ONBUILD RUN make
+CMD run
+FROM debian
+COPY --from=builder /a /b
+"#;
+
+    #[test]
+    fn default() {
+        let docker_file = DockerFile::parse(DOCKER_FILE).unwrap();
+        assert_eq!(
+            docker_file.format(&Formatter::default()),
+            docker_file.to_string()
+        );
+    }
+
+    #[test]
+    fn options() {
+        let docker_file = DockerFile::parse(DOCKER_FILE).unwrap();
+        let formatter = Formatter::default()
+            .width(40)
+            .case(Case::Lower)
+            .blank_lines(BlankLines::Stages);
+        let formatted = docker_file.format(&formatter);
+        assert_eq!(
+            formatted,
+            r#"# syntax=docker/dockerfile:1
+
+arg TAG="1"
+# base image
+
+from rust:$TAG as builder
+maintainer someone
+env A="1" B="2"
+label a="1" \
+      b="2"
+run --network=none echo "a && b" \
+    && echo 'c && d' \
+    && echo e\&\&f \
+    && true
+healthcheck --interval=5s cmd curl localhost
+onbuild run make
+cmd run
+
+from debian
+copy --from=builder "/a" "/b"
+"#
+        );
+        let reparsed = DockerFile::parse(&formatted).unwrap();
+        assert_eq!(reparsed.format(&formatter), formatted);
+
+        assert_eq!(
+            docker_file.format(&Formatter::compact()),
+            r#"# syntax=docker/dockerfile:1
+ARG TAG="1"
+FROM rust:$TAG AS builder
+MAINTAINER someone
+ENV A="1" B="2"
+LABEL a="1" b="2"
+RUN --network=none echo "a && b" && echo 'c && d' && echo e\&\&f && true
+HEALTHCHECK --interval=5s CMD curl localhost
+ONBUILD RUN make
+CMD run
+FROM debian
+COPY --from=builder "/a" "/b"
+"#
+        );
+
+        let env = DockerFile::parse("FROM a\nENV A=1 B=2").unwrap();
+        assert_eq!(
+            env.format(&Formatter::compact().align(true)),
+            "FROM a\nENV A=\"1\" \\\n    B=\"2\"\n"
+        );
+        assert_eq!(
+            env.format(&Formatter::compact().width(10)),
+            "FROM a\nENV A=\"1\" \\\n    B=\"2\"\n"
+        );
+    }
+}
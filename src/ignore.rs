@@ -0,0 +1,503 @@
+//! `.dockerignore` file that filters build context before it's sent to the builder
+//!
+//! Patterns are matched like Docker does it: `*` and `?` don't cross `/`, `**` matches any
+//! number of directories, `[a-z]` matches a character class and `\` escapes the next character.
+//! Pattern that matches a directory ignores everything inside of it. Patterns are checked in
+//! order, so later `!pattern` includes paths back and later pattern can ignore them again.
+//! # Example
+//! ```rust
+//! use dockerfile_rs::ignore::DockerIgnore;
+//!
+//! let docker_ignore = DockerIgnore::parse("# build output\ntarget\n**/*.md\n!README.md").unwrap();
+//! assert!(docker_ignore.is_ignored("target/debug/app"));
+//! assert!(docker_ignore.is_ignored("docs/guide.md"));
+//! assert!(!docker_ignore.is_ignored("README.md"));
+//! assert!(!docker_ignore.is_ignored("src/main.rs"));
+//! assert_eq!(docker_ignore.to_string(), "target\n**/*.md\n!README.md\n");
+//! ```
+
+use crate::{DockerFile, InstructionKind, ParseError, ParseErrorKind, Source, ValidationError};
+use std::{
+    convert::From as StdFrom,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// Line of `.dockerignore`, `exclusion` is written as `!pattern` and includes matching paths back
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Pattern {
+    pub pattern: String,
+    pub exclusion: bool,
+}
+
+impl Pattern {
+    /// Pattern is cleaned the way Docker does: `.` and `..` are resolved, duplicate and
+    /// trailing `/` are removed as well as leading `/`
+    pub fn new<T: AsRef<str>>(pattern: T) -> Self {
+        Self {
+            pattern: clean_pattern(pattern.as_ref()),
+            exclusion: false,
+        }
+    }
+
+    pub fn exclusion<T: AsRef<str>>(pattern: T) -> Self {
+        Self {
+            pattern: clean_pattern(pattern.as_ref()),
+            exclusion: true,
+        }
+    }
+
+    /// Checks that `[` is closed and pattern doesn't end with `\`
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match compile(&self.pattern) {
+            Some(_) => Ok(()),
+            None => Err(ValidationError::InvalidPattern(self.to_string())),
+        }
+    }
+
+    /// Whether the whole `path` matches, directories of `path` are not checked
+    ///
+    /// Pattern is compiled on every call, [`DockerIgnore::is_ignored`] compiles patterns once for
+    /// the path and all of its directories.
+    ///
+    /// [`DockerIgnore::is_ignored`]: struct.DockerIgnore.html#method.is_ignored
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<char> = path.chars().collect();
+        compile(&self.pattern).is_some_and(|tokens| matches(&tokens, &path))
+    }
+}
+
+impl<'a> StdFrom<&'a str> for Pattern {
+    fn from(pattern: &'a str) -> Self {
+        let pattern = pattern.trim();
+        match pattern.strip_prefix('!') {
+            Some(pattern) => Pattern::exclusion(pattern.trim()),
+            None => Pattern::new(pattern),
+        }
+    }
+}
+
+impl StdFrom<String> for Pattern {
+    fn from(pattern: String) -> Self {
+        Pattern::from(pattern.as_str())
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.exclusion {
+            write!(f, "!")?;
+        }
+        write!(f, "{}", self.pattern)
+    }
+}
+
+/// Model of `.dockerignore`, comments and blank lines are not kept
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DockerIgnore {
+    patterns: Vec<Pattern>,
+}
+
+impl DockerIgnore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse existing `.dockerignore`, lines starting with `#` are comments
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        let mut docker_ignore = DockerIgnore::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim_start_matches('\u{feff}');
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let pattern = Pattern::from(line);
+            if pattern.validate().is_err() {
+                return Err(ParseError {
+                    line: i + 1,
+                    column: line.len() - line.trim_start().len() + 1,
+                    kind: ParseErrorKind::InvalidValue(line.trim().to_string()),
+                });
+            }
+            docker_ignore.patterns.push(pattern);
+        }
+        Ok(docker_ignore)
+    }
+
+    /// Ignore file that keeps only local sources of `ADD` and `COPY` in build context
+    ///
+    /// Everything is ignored with `*` and every source is included back with `!source`. URLs,
+    /// heredocs and `COPY --from` are skipped. If a source is the whole context, nothing is
+    /// ignored. Sources are used as written, so [`DockerFile::substitute`] should be called
+    /// first if they have `$VAR` references.
+    /// # Example
+    /// ```rust
+    /// use dockerfile_rs::{ignore::DockerIgnore, DockerFile};
+    ///
+    /// let docker_file = DockerFile::parse(
+    ///     "FROM rust\nCOPY Cargo.toml Cargo.lock ./\nCOPY ./src/ src/\nCOPY --from=build /app /app",
+    /// )
+    /// .unwrap();
+    /// let docker_ignore = DockerIgnore::proposed(&docker_file);
+    /// assert_eq!(docker_ignore.to_string(), "*\n!Cargo.lock\n!Cargo.toml\n!src\n");
+    /// assert!(docker_ignore.is_ignored("target/debug/app"));
+    /// assert!(!docker_ignore.is_ignored("src/main.rs"));
+    /// ```
+    ///
+    /// [`DockerFile::substitute`]: ../struct.DockerFile.html#method.substitute
+    pub fn proposed(docker_file: &DockerFile) -> Self {
+        let mut sources = Vec::new();
+        for instruction in docker_file
+            .stages()
+            .iter()
+            .flat_map(|stage| stage.instructions())
+        {
+            let src = match instruction {
+                InstructionKind::Add(add) => &add.src,
+                InstructionKind::Copy(copy) if copy.from.is_none() => &copy.src,
+                _ => continue,
+            };
+            for source in src {
+                match source {
+                    Source::Path(path) if path.contains("://") || path.starts_with("git@") => {}
+                    Source::Path(path) => sources.push(clean_pattern(path)),
+                    Source::Heredoc(_) => {}
+                }
+            }
+        }
+
+        if sources.iter().any(|source| source == ".") {
+            return DockerIgnore::new();
+        }
+        sources.sort();
+        sources.dedup();
+        let mut docker_ignore = DockerIgnore::new().pattern("*");
+        docker_ignore
+            .patterns
+            .extend(sources.into_iter().map(Pattern::exclusion));
+        docker_ignore
+    }
+
+    /// Add pattern after existing ones
+    pub fn pattern<T: Into<Pattern>>(mut self, pattern: T) -> Self {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    /// Patterns in the order they are checked
+    pub fn patterns(&self) -> &[Pattern] {
+        &self.patterns
+    }
+
+    pub fn patterns_mut(&mut self) -> &mut Vec<Pattern> {
+        &mut self.patterns
+    }
+
+    /// Validates every pattern, returns the first error
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.patterns.iter().try_for_each(Pattern::validate)
+    }
+
+    /// Whether `path` relative to build context is left out of it
+    ///
+    /// Path is ignored if the last pattern matching it or one of its directories is not an
+    /// exclusion.
+    pub fn is_ignored(&self, path: &str) -> bool {
+        self.matcher().is_ignored(path)
+    }
+
+    /// Patterns compiled once to check many paths, invalid patterns match nothing
+    pub(crate) fn matcher(&self) -> Matcher {
+        let patterns = self
+            .patterns
+            .iter()
+            .filter_map(|pattern| Some((compile(&pattern.pattern)?, pattern.exclusion)))
+            .collect();
+        Matcher { patterns }
+    }
+}
+
+/// Compiled patterns of [`DockerIgnore`]
+///
+/// [`DockerIgnore`]: struct.DockerIgnore.html
+pub(crate) struct Matcher {
+    /// Tokens of patterns and whether they are exclusions
+    patterns: Vec<(Vec<Token>, bool)>,
+}
+
+impl Matcher {
+    pub(crate) fn has_exclusions(&self) -> bool {
+        self.patterns.iter().any(|(_, exclusion)| *exclusion)
+    }
+
+    /// See [`DockerIgnore::is_ignored`]
+    ///
+    /// [`DockerIgnore::is_ignored`]: struct.DockerIgnore.html#method.is_ignored
+    pub(crate) fn is_ignored(&self, path: &str) -> bool {
+        let path = clean(path);
+        let path = path.trim_start_matches('/');
+        if path.is_empty() || path == "." {
+            return false;
+        }
+        let path: Vec<char> = path.chars().collect();
+        let parents: Vec<&[char]> = (0..path.len())
+            .filter(|&i| path[i] == '/')
+            .map(|i| &path[..i])
+            .collect();
+
+        let mut ignored = false;
+        for (tokens, exclusion) in &self.patterns {
+            // exclusion can only change ignored path and the other way around
+            if *exclusion != ignored {
+                continue;
+            }
+            if matches(tokens, &path) || parents.iter().any(|parent| matches(tokens, parent)) {
+                ignored = !exclusion;
+            }
+        }
+        ignored
+    }
+}
+
+impl FromStr for DockerIgnore {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl<T: Into<Pattern>> StdFrom<Vec<T>> for DockerIgnore {
+    fn from(patterns: Vec<T>) -> Self {
+        Self {
+            patterns: patterns.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Display for DockerIgnore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for pattern in &self.patterns {
+            writeln!(f, "{}", pattern)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves `.` and `..`, removes duplicate and trailing `/`, empty path becomes `.`
+pub(crate) fn clean(path: &str) -> String {
+    let rooted = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            ".." if rooted => {}
+            part => parts.push(part),
+        }
+    }
+
+    let path = parts.join("/");
+    if rooted {
+        format!("/{}", path)
+    } else if path.is_empty() {
+        ".".to_string()
+    } else {
+        path
+    }
+}
+
+fn clean_pattern(pattern: &str) -> String {
+    let pattern = clean(pattern);
+    match pattern.strip_prefix('/') {
+        Some(stripped) if !stripped.is_empty() => stripped.to_string(),
+        _ => pattern,
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Char(char),
+    /// `*`, any characters except `/`
+    Any,
+    /// `?`, any character except `/`
+    One,
+    /// `[...]`, inclusive ranges of characters
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    /// `**` followed by more pattern, nothing or any path ending with `/`
+    Dirs,
+    /// `**` at the end, anything
+    All,
+}
+
+fn compile(pattern: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                }
+                if chars.peek().is_none() {
+                    Token::All
+                } else {
+                    Token::Dirs
+                }
+            }
+            '*' => Token::Any,
+            '?' => Token::One,
+            '\\' => Token::Char(chars.next()?),
+            '[' => {
+                let negated = chars.peek() == Some(&'^');
+                if negated {
+                    chars.next();
+                }
+                let mut ranges = Vec::new();
+                loop {
+                    let start = match chars.next()? {
+                        ']' if !ranges.is_empty() => break,
+                        '\\' => chars.next()?,
+                        c => c,
+                    };
+                    let end = if chars.peek() == Some(&'-') {
+                        chars.next();
+                        match chars.next()? {
+                            '\\' => chars.next()?,
+                            ']' => return None,
+                            c => c,
+                        }
+                    } else {
+                        start
+                    };
+                    ranges.push((start, end));
+                }
+                Token::Class { negated, ranges }
+            }
+            c => Token::Char(c),
+        };
+        tokens.push(token);
+    }
+    Some(tokens)
+}
+
+fn matches(tokens: &[Token], text: &[char]) -> bool {
+    match tokens.split_first() {
+        None => text.is_empty(),
+        Some((Token::All, rest)) => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+        Some((Token::Dirs, rest)) => {
+            matches(rest, text)
+                || (0..text.len()).any(|i| text[i] == '/' && matches(rest, &text[i + 1..]))
+        }
+        Some((Token::Any, rest)) => {
+            let segment = text.iter().take_while(|c| **c != '/').count();
+            (0..=segment).any(|i| matches(rest, &text[i..]))
+        }
+        Some((token, rest)) => {
+            let c = match text.first() {
+                Some(c) => *c,
+                None => return false,
+            };
+            let matched = match token {
+                Token::Char(expected) => c == *expected,
+                Token::One => c != '/',
+                Token::Class { negated, ranges } => {
+                    ranges.iter().any(|(start, end)| *start <= c && c <= *end) != *negated
+                }
+                _ => unreachable!(),
+            };
+            matched && matches(rest, &text[1..])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        let matches = |pattern: &str, path: &str| Pattern::from(pattern).matches(path);
+
+        assert!(matches("*.md", "README.md"));
+        assert!(!matches("*.md", "docs/README.md"));
+        assert!(matches("*/*.md", "docs/README.md"));
+        assert!(matches("a?c", "abc"));
+        assert!(!matches("a?c", "a/c"));
+        assert!(matches("**", "a/b/c"));
+        assert!(matches("**/c", "c"));
+        assert!(matches("**/c", "a/b/c"));
+        assert!(matches("a/**/c", "a/c"));
+        assert!(matches("a/**/c", "a/b/b/c"));
+        assert!(!matches("a/**/c", "ab/c"));
+        assert!(matches("a/**", "a/b"));
+        assert!(!matches("a/**", "a"));
+        assert!(matches("a**c", "ac"));
+        assert!(!matches("a**c", "abc"));
+        assert!(matches("a**c", "a/b/c"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[^a-c]x", "bx"));
+        assert!(matches("[]]", "]"));
+        assert!(matches(r"\*", "*"));
+        assert!(!matches(r"\*", "a"));
+        assert!(matches("/./a//b/../c/", "a/c"));
+
+        assert_eq!(Pattern::from(" ! /target/ ").to_string(), "!target");
+        assert_eq!(Pattern::from("/").to_string(), "/");
+        assert_eq!(
+            Pattern::from("[a-").validate(),
+            Err(ValidationError::InvalidPattern("[a-".to_string()))
+        );
+        assert!(Pattern::from("a\\").validate().is_err());
+    }
+
+    #[test]
+    fn ignored() {
+        let docker_ignore = DockerIgnore::parse(
+            "\u{feff}# comment\n  #not comment\n\n*.log\ndocs\n!docs/keep.md\n**/tmp\n!**/tmp/keep\ntmp/keep\n",
+        )
+        .unwrap();
+        assert_eq!(docker_ignore.patterns().len(), 7);
+        assert!(docker_ignore.is_ignored("#not comment"));
+        assert!(docker_ignore.is_ignored("build.log"));
+        assert!(docker_ignore.is_ignored("./build.log"));
+        assert!(!docker_ignore.is_ignored("logs/build.log"));
+        assert!(docker_ignore.is_ignored("docs"));
+        assert!(docker_ignore.is_ignored("docs/guide.md"));
+        assert!(!docker_ignore.is_ignored("docs/keep.md"));
+        assert!(docker_ignore.is_ignored("a/b/tmp/file"));
+        assert!(!docker_ignore.is_ignored("a/tmp/keep"));
+        assert!(docker_ignore.is_ignored("tmp/keep/file"));
+        assert!(!docker_ignore.is_ignored("."));
+        assert!(!docker_ignore.is_ignored("src/main.rs"));
+
+        assert_eq!(
+            DockerIgnore::parse("target\n  [a").unwrap_err(),
+            ParseError {
+                line: 2,
+                column: 3,
+                kind: ParseErrorKind::InvalidValue("[a".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn proposed() {
+        let docker_file = DockerFile::parse(
+            "FROM rust\nADD https://example.com/a.tar.gz ./\nCOPY <<EOF /a\nEOF\nCOPY . /app",
+        )
+        .unwrap();
+        assert_eq!(DockerIgnore::proposed(&docker_file), DockerIgnore::new());
+
+        let docker_file = DockerFile::parse("FROM rust\nRUN true").unwrap();
+        let docker_ignore = DockerIgnore::proposed(&docker_file);
+        assert_eq!(docker_ignore.to_string(), "*\n");
+        assert!(docker_ignore.is_ignored("Cargo.toml"));
+    }
+}
@@ -0,0 +1,440 @@
+//! Configuration of the image [`DockerFile`] builds, known without building it
+//!
+//! [`ImageConfig`] is what the last stage leaves in the image: variables, labels, user, working
+//! directory, exposed ports, volumes, stop signal, health check, entrypoint and command.
+//! Base images from a registry are unknown, so only stages of the same file are inherited.
+//! # Example
+//! ```rust
+//! use dockerfile_rs::DockerFile;
+//!
+//! let docker_file = DockerFile::parse(
+//!     "FROM debian AS base\nENV HOME=/root\nWORKDIR $HOME\nCMD [\"bash\"]\n\
+//!      FROM base\nWORKDIR app\nEXPOSE 80 53/udp\nENTRYPOINT exec server",
+//! )
+//! .unwrap();
+//! let config = docker_file.image_config().unwrap();
+//! assert_eq!(config.working_dir.as_deref(), Some("/root/app"));
+//! assert_eq!(config.cmd, None);
+//! assert_eq!(
+//!     config.to_json(),
+//!     r#"{"config":{"ExposedPorts":{"53/udp":{},"80/tcp":{}},"Env":["HOME=/root"],"#.to_string()
+//!         + r#""Entrypoint":["/bin/sh","-c","exec server"],"WorkingDir":"/root/app"}}"#
+//! );
+//! ```
+//!
+//! [`DockerFile`]: ../struct.DockerFile.html
+//! [`ImageConfig`]: struct.ImageConfig.html
+
+use crate::{
+    eval::ExpandError, ignore, quote, CommandForm, DockerFile, ExposedPort, HealthCheck,
+    InstructionKind, Platform, Protocol,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    time::Duration,
+};
+
+/// Health check of image, `test` is `["NONE"]`, `["CMD", args...]` or `["CMD-SHELL", command]`
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HealthConfig {
+    pub test: Vec<String>,
+    pub interval: Option<Duration>,
+    pub timeout: Option<Duration>,
+    pub start_period: Option<Duration>,
+    pub start_interval: Option<Duration>,
+    pub retries: Option<i32>,
+}
+
+/// Image configuration in the shape of OCI image config
+///
+/// Shell form of `CMD` and `ENTRYPOINT` is stored with the shell set by `SHELL`, `/bin/sh -c`
+/// by default. `on_build` has triggers of `ONBUILD` that run in images based on this one.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ImageConfig {
+    pub architecture: Option<String>,
+    pub os: Option<String>,
+    pub variant: Option<String>,
+    /// Set by `MAINTAINER`
+    pub author: Option<String>,
+    pub user: Option<String>,
+    /// Ports like `80/tcp`, ranges are expanded
+    pub exposed_ports: BTreeSet<String>,
+    pub env: Vec<(String, String)>,
+    pub entrypoint: Option<Vec<String>>,
+    pub cmd: Option<Vec<String>>,
+    pub volumes: BTreeSet<String>,
+    pub working_dir: Option<String>,
+    pub labels: BTreeMap<String, String>,
+    pub stop_signal: Option<String>,
+    pub healthcheck: Option<HealthConfig>,
+    pub on_build: Vec<String>,
+    pub shell: Option<Vec<String>>,
+}
+
+impl ImageConfig {
+    /// Value of `ENV` variable
+    pub fn env(&self, name: &str) -> Option<&str> {
+        self.env
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// OCI image config JSON, empty fields are omitted and durations are in nanoseconds
+    pub fn to_json(&self) -> String {
+        let mut config = Vec::new();
+        let mut field = |name: &str, value: String| {
+            config.push(format!("{}:{}", quote::json_string(name), value))
+        };
+        if let Some(user) = &self.user {
+            field("User", quote::json_string(user));
+        }
+        if !self.exposed_ports.is_empty() {
+            field("ExposedPorts", set(&self.exposed_ports));
+        }
+        if !self.env.is_empty() {
+            let env: Vec<String> = self
+                .env
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect();
+            field("Env", array(&env));
+        }
+        if let Some(entrypoint) = &self.entrypoint {
+            field("Entrypoint", array(entrypoint));
+        }
+        if let Some(cmd) = &self.cmd {
+            field("Cmd", array(cmd));
+        }
+        if !self.volumes.is_empty() {
+            field("Volumes", set(&self.volumes));
+        }
+        if let Some(working_dir) = &self.working_dir {
+            field("WorkingDir", quote::json_string(working_dir));
+        }
+        if !self.labels.is_empty() {
+            let labels: Vec<String> = self
+                .labels
+                .iter()
+                .map(|(k, v)| format!("{}:{}", quote::json_string(k), quote::json_string(v)))
+                .collect();
+            field("Labels", format!("{{{}}}", labels.join(",")));
+        }
+        if let Some(stop_signal) = &self.stop_signal {
+            field("StopSignal", quote::json_string(stop_signal));
+        }
+        if let Some(healthcheck) = &self.healthcheck {
+            let mut health = vec![format!("\"Test\":{}", array(&healthcheck.test))];
+            let durations = [
+                ("Interval", healthcheck.interval),
+                ("Timeout", healthcheck.timeout),
+                ("StartPeriod", healthcheck.start_period),
+                ("StartInterval", healthcheck.start_interval),
+            ];
+            for (name, duration) in durations.iter() {
+                if let Some(duration) = duration {
+                    health.push(format!("\"{}\":{}", name, duration.as_nanos()));
+                }
+            }
+            if let Some(retries) = healthcheck.retries {
+                health.push(format!("\"Retries\":{}", retries));
+            }
+            field("Healthcheck", format!("{{{}}}", health.join(",")));
+        }
+        if !self.on_build.is_empty() {
+            field("OnBuild", array(&self.on_build));
+        }
+        if let Some(shell) = &self.shell {
+            field("Shell", array(shell));
+        }
+
+        let mut image = Vec::new();
+        let fields = [
+            ("architecture", &self.architecture),
+            ("os", &self.os),
+            ("variant", &self.variant),
+            ("author", &self.author),
+        ];
+        for (name, value) in fields.iter() {
+            if let Some(value) = value {
+                image.push(format!("\"{}\":{}", name, quote::json_string(value)));
+            }
+        }
+        image.push(format!("\"config\":{{{}}}", config.join(",")));
+        format!("{{{}}}", image.join(","))
+    }
+
+    /// Applies instruction of stage built on top of this config
+    fn apply(&mut self, instruction: &InstructionKind, cmd_inherited: &mut bool) {
+        match instruction {
+            InstructionKind::Env(env) => {
+                for (name, value) in env.iter() {
+                    match self.env.iter_mut().find(|(k, _)| k == name) {
+                        Some((_, v)) => *v = value.value.clone(),
+                        None => self.env.push((name.to_string(), value.value.clone())),
+                    }
+                }
+            }
+            InstructionKind::Label(label) => {
+                for (key, value) in label.iter() {
+                    self.labels.insert(key.to_string(), value.value.clone());
+                }
+            }
+            InstructionKind::Maintainer(maintainer) => {
+                self.author = Some(maintainer.name.clone());
+            }
+            InstructionKind::User(user) => {
+                self.user = Some(match &user.group {
+                    Some(group) => format!("{}:{}", user.user, group),
+                    None => user.user.clone(),
+                });
+            }
+            InstructionKind::WorkDir(work_dir) => {
+                let path = match &self.working_dir {
+                    Some(current) if !work_dir.path.starts_with('/') => {
+                        format!("{}/{}", current, work_dir.path)
+                    }
+                    _ => format!("/{}", work_dir.path),
+                };
+                self.working_dir = Some(ignore::clean(&path));
+            }
+            InstructionKind::Expose(expose) => {
+                // variables are expanded by substitution before
+                let ports = expose.ports.iter().filter_map(|port| match port {
+                    ExposedPort::Port(port) => Some(port),
+                    ExposedPort::Variable(_) => None,
+                });
+                for port in ports {
+                    let protocol = match port.protocol {
+                        Some(Protocol::Udp) => "udp",
+                        Some(Protocol::Sctp) => "sctp",
+                        Some(Protocol::Tcp) | None => "tcp",
+                    };
+                    for number in port.port..=port.end.unwrap_or(port.port) {
+                        self.exposed_ports
+                            .insert(format!("{}/{}", number, protocol));
+                    }
+                }
+            }
+            InstructionKind::Volume(volume) => {
+                self.volumes.extend(volume.paths.iter().cloned());
+            }
+            InstructionKind::StopSignal(signal) => {
+                self.stop_signal = Some(signal.signal.clone());
+            }
+            InstructionKind::HealthCheck(HealthCheck::None) => {
+                self.healthcheck = Some(HealthConfig {
+                    test: vec!["NONE".to_string()],
+                    ..HealthConfig::default()
+                });
+            }
+            InstructionKind::HealthCheck(HealthCheck::Check {
+                cmd,
+                interval,
+                timeout,
+                start_period,
+                start_interval,
+                retries,
+            }) => {
+                let test = match &cmd.command {
+                    CommandForm::Exec(params) => {
+                        let mut test = vec!["CMD".to_string()];
+                        test.extend(params.iter().cloned());
+                        test
+                    }
+                    command => vec!["CMD-SHELL".to_string(), shell_command(command)],
+                };
+                self.healthcheck = Some(HealthConfig {
+                    test,
+                    interval: *interval,
+                    timeout: *timeout,
+                    start_period: *start_period,
+                    start_interval: *start_interval,
+                    retries: *retries,
+                });
+            }
+            InstructionKind::EntryPoint(entrypoint) => {
+                self.entrypoint = Some(self.command(&entrypoint.command));
+                // `ENTRYPOINT` resets `CMD` of the base image
+                if *cmd_inherited {
+                    self.cmd = None;
+                }
+            }
+            InstructionKind::Cmd(cmd) => {
+                self.cmd = Some(self.command(&cmd.command));
+                *cmd_inherited = false;
+            }
+            InstructionKind::Shell(shell) => self.shell = Some(shell.params.clone()),
+            InstructionKind::OnBuild(on_build) => {
+                self.on_build.push(on_build.instruction().to_string());
+            }
+            _ => {}
+        }
+    }
+
+    fn command(&self, command: &CommandForm) -> Vec<String> {
+        match command {
+            CommandForm::Exec(params) => params.clone(),
+            command => {
+                let mut params = match &self.shell {
+                    Some(shell) => shell.clone(),
+                    None => vec!["/bin/sh".to_string(), "-c".to_string()],
+                };
+                params.push(shell_command(command));
+                params
+            }
+        }
+    }
+}
+
+fn shell_command(command: &CommandForm) -> String {
+    match command {
+        CommandForm::Shell(command) => command.clone(),
+        command => command.to_string(),
+    }
+}
+
+fn array(items: &[String]) -> String {
+    let items: Vec<String> = items.iter().map(|i| quote::json_string(i)).collect();
+    format!("[{}]", items.join(","))
+}
+
+fn set(items: &BTreeSet<String>) -> String {
+    let items: Vec<String> = items
+        .iter()
+        .map(|i| format!("{}:{{}}", quote::json_string(i)))
+        .collect();
+    format!("{{{}}}", items.join(","))
+}
+
+/// Folds stages of substituted copy of `docker_file`, the last stage is the image
+pub(crate) fn image_config(
+    docker_file: &DockerFile,
+    build_args: &HashMap<String, String>,
+) -> Result<ImageConfig, ExpandError> {
+    let docker_file = docker_file.substitute(build_args)?;
+    let mut stages: Vec<(Option<&str>, ImageConfig, Vec<&InstructionKind>)> = Vec::new();
+    for stage in docker_file.stages() {
+        let base = stage.base();
+        let parent = stages
+            .iter()
+            .find(|(name, _, _)| name.is_some_and(|name| name.eq_ignore_ascii_case(&base.image)));
+        let mut config = ImageConfig::default();
+        let mut cmd_inherited = false;
+        if let Some((_, parent, triggers)) = parent {
+            config = ImageConfig {
+                on_build: Vec::new(),
+                ..parent.clone()
+            };
+            cmd_inherited = config.cmd.is_some();
+            // triggers of the base run before the first instruction of the stage
+            for trigger in triggers {
+                config.apply(trigger, &mut cmd_inherited);
+            }
+        }
+        // platform is set only when it is known, variable like `$BUILDPLATFORM` is left to builder
+        if let Some(Platform::Target {
+            os,
+            architecture,
+            variant,
+        }) = &base.platform
+        {
+            if !os.is_empty() {
+                config.os = Some(os.clone());
+                config.architecture = architecture.clone();
+                config.variant = variant.clone();
+            }
+        }
+
+        let mut triggers = Vec::new();
+        for instruction in stage.instructions() {
+            config.apply(instruction, &mut cmd_inherited);
+            if let InstructionKind::OnBuild(on_build) = instruction {
+                triggers.push(on_build.instruction());
+            }
+        }
+        stages.push((stage.name(), config, triggers));
+    }
+
+    Ok(stages
+        .pop()
+        .map(|(_, config, _)| config)
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages() {
+        let docker_file = DockerFile::parse(
+            r#"FROM --platform=linux/arm64/v8 alpine AS base
+MAINTAINER someone
+ENV A=1 B=2
+LABEL version=1 "description"="base image"
+USER nobody:nogroup
+EXPOSE 8000-8002/udp
+VOLUME /data
+STOPSIGNAL SIGINT
+SHELL ["/bin/ash", "-eo", "pipefail", "-c"]
+CMD ["serve"]
+HEALTHCHECK --interval=30s --retries=3 CMD curl -f http://localhost/
+ONBUILD ENV TRIGGERED=yes
+ONBUILD WORKDIR /onbuild
+
+FROM base
+ENV A=3
+LABEL version=2
+VOLUME ["/cache", "/data"]
+WORKDIR src
+ENTRYPOINT run "$A"
+ONBUILD RUN make
+"#,
+        )
+        .unwrap();
+        let config = docker_file.image_config().unwrap();
+        assert_eq!(config.os.as_deref(), Some("linux"));
+        assert_eq!(config.architecture.as_deref(), Some("arm64"));
+        assert_eq!(config.variant.as_deref(), Some("v8"));
+        assert_eq!(config.author.as_deref(), Some("someone"));
+        assert_eq!(config.env("TRIGGERED"), Some("yes"));
+        assert_eq!(config.working_dir.as_deref(), Some("/onbuild/src"));
+        assert_eq!(config.cmd, None);
+        assert_eq!(
+            config.entrypoint,
+            Some(vec![
+                "/bin/ash".to_string(),
+                "-eo".to_string(),
+                "pipefail".to_string(),
+                "-c".to_string(),
+                "run \"$A\"".to_string(),
+            ])
+        );
+        assert_eq!(config.on_build, ["RUN make"]);
+        assert_eq!(
+            config.to_json(),
+            r#"{"architecture":"arm64","os":"linux","variant":"v8","author":"someone","config":{"User":"nobody:nogroup","ExposedPorts":{"8000/udp":{},"8001/udp":{},"8002/udp":{}},"Env":["A=3","B=2","TRIGGERED=yes"],"Entrypoint":["/bin/ash","-eo","pipefail","-c","run \"$A\""],"Volumes":{"/cache":{},"/data":{}},"WorkingDir":"/onbuild/src","Labels":{"description":"base image","version":"2"},"StopSignal":"SIGINT","Healthcheck":{"Test":["CMD-SHELL","curl -f http://localhost/"],"Interval":30000000000,"Retries":3},"OnBuild":["RUN make"],"Shell":["/bin/ash","-eo","pipefail","-c"]}}"#
+        );
+
+        let docker_file =
+            DockerFile::parse("FROM base\nCMD a\nFROM rust\nENTRYPOINT [\"b\"]").unwrap();
+        let config = docker_file.image_config().unwrap();
+        assert_eq!(config.cmd, None);
+        assert_eq!(config.to_json(), r#"{"config":{"Entrypoint":["b"]}}"#);
+
+        let docker_file =
+            DockerFile::parse("FROM --platform=$BUILDPLATFORM rust AS build\nCMD [\"a\"]").unwrap();
+        let config = docker_file.image_config().unwrap();
+        assert_eq!(config.os, None);
+        assert_eq!(config.architecture, None);
+        assert_eq!(config.to_json(), r#"{"config":{"Cmd":["a"]}}"#);
+
+        let docker_file = DockerFile::parse("FROM rust\nARG PORT=8080\nEXPOSE $PORT").unwrap();
+        let config = docker_file.image_config().unwrap();
+        assert_eq!(
+            config.to_json(),
+            r#"{"config":{"ExposedPorts":{"8080/tcp":{}}}}"#
+        );
+    }
+}
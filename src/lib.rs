@@ -1,21 +1,146 @@
 mod builder;
-
+mod parser;
+mod quote;
+
+pub mod context;
+pub mod eval;
+pub mod format;
+pub mod ignore;
+pub mod image;
+pub mod lint;
 pub mod macros;
+#[cfg(feature = "spec")]
+pub mod spec;
 
-pub use builder::DockerFile;
+pub use builder::{DockerFile, Stage};
+pub use parser::{ParseError, ParseErrorKind};
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     convert::From as StdFrom,
+    error::Error,
     fmt::{self, Display},
     hash::Hash,
+    ops::RangeInclusive,
+    time::Duration,
 };
 
+/// Marker of instructions that can be used as a trigger of [`OnBuild`]
+///
+/// [`OnBuild`]: struct.OnBuild.html
 pub trait Instruction: Display {}
 
-trait StorageInstruction: Instruction {}
+/// Any instruction, stored representation of [`DockerFile`]
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{DockerFile, InstructionKind, FROM};
+///
+/// let docker_file = DockerFile::from(FROM!(rust)).run(vec!["cargo", "build"]);
+/// for instruction in docker_file.stages()[0].instructions() {
+///     if let InstructionKind::Run(run) = instruction {
+///         assert_eq!(run.command, vec!["cargo", "build"].into());
+///     }
+/// }
+/// ```
+///
+/// [`DockerFile`]: struct.DockerFile.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum InstructionKind {
+    From(From),
+    Run(Run),
+    Cmd(Cmd),
+    Label(Label),
+    Maintainer(Maintainer),
+    Expose(Expose),
+    Env(Env),
+    Add(Add),
+    Copy(Copy),
+    EntryPoint(EntryPoint),
+    Volume(Volume),
+    User(User),
+    WorkDir(WorkDir),
+    Arg(Arg),
+    OnBuild(OnBuild),
+    StopSignal(StopSignal),
+    HealthCheck(HealthCheck),
+    Shell(Shell),
+    Comment(Comment),
+}
+
+impl InstructionKind {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self {
+            InstructionKind::From(i) => i.validate(),
+            InstructionKind::Run(i) => i.validate(),
+            InstructionKind::Cmd(i) => i.validate(),
+            InstructionKind::Label(i) => i.validate(),
+            InstructionKind::Env(i) => i.validate(),
+            InstructionKind::Expose(i) => i.validate(),
+            InstructionKind::Add(i) => i.validate(),
+            InstructionKind::Copy(i) => i.validate(),
+            InstructionKind::EntryPoint(i) => i.validate(),
+            InstructionKind::Arg(i) => i.validate(),
+            InstructionKind::HealthCheck(i) => i.validate(),
+            InstructionKind::OnBuild(i) => i.instruction().validate(),
+            InstructionKind::Comment(i) => i.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+macro_rules! instruction_kind {
+    ($($name:ident),+) => {
+        $(
+            impl StdFrom<$name> for InstructionKind {
+                fn from(i: $name) -> Self {
+                    InstructionKind::$name(i)
+                }
+            }
+        )+
+
+        impl Display for InstructionKind {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                match self {
+                    $(InstructionKind::$name(i) => i.fmt(f),)+
+                }
+            }
+        }
+    };
+}
+
+instruction_kind!(
+    From,
+    Run,
+    Cmd,
+    Label,
+    Maintainer,
+    Expose,
+    Env,
+    Add,
+    Copy,
+    EntryPoint,
+    Volume,
+    User,
+    WorkDir,
+    Arg,
+    OnBuild,
+    StopSignal,
+    HealthCheck,
+    Shell,
+    Comment
+);
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum TagOrDigest {
     Tag(String),
     Digest(String),
@@ -23,145 +148,1055 @@ pub enum TagOrDigest {
 
 pub use TagOrDigest::*;
 
+/// Value of [`Env`], [`Label`] or [`Arg`]
+///
+/// Literal value is written as is: quotes, `\` and `$` are escaped.
+/// Expanding value keeps `$VAR` and `${VAR}` references, `\` escapes the next character in it,
+/// so `\$` is a literal dollar sign and `\\` is a backslash.
+/// Newlines are written as line continuations because `Dockerfile` can't hold them in values.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{Env, Value};
+///
+/// let env = Env::from(("GREETING", r#"say "hi" for $5"#));
+/// assert_eq!(env.to_string(), r#"ENV GREETING="say \"hi\" for \$5""#);
+///
+/// let env = Env::from(("PATH", Value::expand(r"$PATH:/opt/bin:\$HOME")));
+/// assert_eq!(env.to_string(), r#"ENV PATH="$PATH:/opt/bin:\$HOME""#);
+/// ```
+///
+/// [`Env`]: struct.Env.html
+/// [`Label`]: struct.Label.html
+/// [`Arg`]: struct.Arg.html
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Value {
+    pub value: String,
+    pub expand: bool,
+}
+
+impl Value {
+    pub fn literal<T: Into<String>>(value: T) -> Self {
+        Value {
+            value: value.into(),
+            expand: false,
+        }
+    }
+
+    pub fn expand<T: Into<String>>(value: T) -> Self {
+        Value {
+            value: value.into(),
+            expand: true,
+        }
+    }
+}
+
+impl<T> StdFrom<T> for Value
+where
+    T: Into<String>,
+{
+    fn from(value: T) -> Self {
+        Value::literal(value)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            quote::double_quoted(&self.value, self.expand, quote::escape())
+        )
+    }
+}
+
+/// Error returned by `validate` functions of instructions, [`Stage`] and [`DockerFile`]
+///
+/// [`Stage`]: struct.Stage.html#method.validate
+/// [`DockerFile`]: struct.DockerFile.html#method.validate
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ValidationError {
+    /// Key of `ENV`, `LABEL` or `ARG` is empty or contains characters that can't be used in it
+    InvalidKey(String),
+    /// Heredoc delimiter is empty or contains characters that can't be used in it
+    InvalidDelimiter(String),
+    /// Heredoc is used in instruction that doesn't support it
+    UnexpectedHeredoc(String),
+    /// Mount has empty target or secret has neither id nor target
+    InvalidMount(String),
+    /// `ADD` or `COPY` has no sources
+    MissingSource(String),
+    /// `ADD` or `COPY` has several sources, but destination doesn't end with `/`
+    InvalidDestination(String),
+    /// Parser directive has value Docker doesn't accept
+    InvalidDirective(String),
+    /// Comment looks like parser directive, but it isn't at the top of the file
+    MisplacedDirective(String),
+    /// Platform has empty or extra parts
+    InvalidPlatform(String),
+    /// Image, tag or digest of `FROM` is empty or contains whitespace
+    InvalidImage(String),
+    /// Stage name doesn't start with a letter or contains characters other than letters, digits,
+    /// `-`, `_` and `.`
+    InvalidStageName(String),
+    /// `HEALTHCHECK` has negative `--retries`
+    NegativeRetries(i32),
+    /// `EXPOSE` has no ports
+    MissingPort,
+    /// Port is 0 or range of ports is empty
+    InvalidPort(String),
+    /// `.dockerignore` pattern has unclosed `[` or ends with `\`
+    InvalidPattern(String),
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::InvalidKey(key) => write!(f, "invalid key `{}`", key),
+            ValidationError::InvalidDelimiter(delimiter) => {
+                write!(f, "invalid heredoc delimiter `{}`", delimiter)
+            }
+            ValidationError::UnexpectedHeredoc(name) => {
+                write!(f, "`{}` doesn't support heredocs", name)
+            }
+            ValidationError::InvalidMount(mount) => write!(f, "invalid mount `{}`", mount),
+            ValidationError::MissingSource(name) => write!(f, "`{}` has no sources", name),
+            ValidationError::InvalidDestination(dst) => {
+                write!(
+                    f,
+                    "destination `{}` of several sources must end with `/`",
+                    dst
+                )
+            }
+            ValidationError::InvalidDirective(directive) => {
+                write!(f, "invalid directive `{}`", directive)
+            }
+            ValidationError::MisplacedDirective(comment) => {
+                write!(
+                    f,
+                    "comment `{}` would be a directive, but it's not at the top of the file",
+                    comment
+                )
+            }
+            ValidationError::InvalidPlatform(platform) => {
+                write!(f, "invalid platform `{}`", platform)
+            }
+            ValidationError::InvalidImage(image) => write!(f, "invalid image `{}`", image),
+            ValidationError::InvalidStageName(name) => {
+                write!(f, "invalid stage name `{}`", name)
+            }
+            ValidationError::NegativeRetries(retries) => {
+                write!(f, "retries can't be negative, got {}", retries)
+            }
+            ValidationError::MissingPort => write!(f, "`EXPOSE` has no ports"),
+            ValidationError::InvalidPort(port) => write!(f, "invalid port `{}`", port),
+            ValidationError::InvalidPattern(pattern) => {
+                write!(f, "invalid pattern `{}`", pattern)
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Base image of stage
+///
+/// `platform` selects image of multi-platform base, `$BUILDPLATFORM` is used to build on the
+/// native platform of the builder and cross-compile for `$TARGETPLATFORM`.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{From, Platform, Tag};
+///
+/// let from = From {
+///     image: String::from("rust"),
+///     tag_or_digest: Some(Tag(String::from("1.33"))),
+///     name: Some(String::from("builder")),
+///     platform: Some(Platform::from("$BUILDPLATFORM")),
+/// };
+/// assert_eq!(from.to_string(), "FROM --platform=$BUILDPLATFORM rust:1.33 AS builder");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct From {
     pub image: String,
     pub tag_or_digest: Option<TagOrDigest>,
     pub name: Option<String>,
+    pub platform: Option<Platform>,
+}
+
+impl From {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let valid = |part: &str| !part.is_empty() && !part.contains(char::is_whitespace);
+        let tag_or_digest = match &self.tag_or_digest {
+            Some(Tag(value)) | Some(Digest(value)) => Some(value.as_str()),
+            None => None,
+        };
+        if !valid(&self.image) || !tag_or_digest.is_none_or(valid) {
+            return Err(ValidationError::InvalidImage(self.image_reference()));
+        }
+        if let Some(name) = &self.name {
+            let mut chars = name.chars();
+            let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+            if !valid {
+                return Err(ValidationError::InvalidStageName(name.clone()));
+            }
+        }
+        self.platform.as_ref().map_or(Ok(()), Platform::validate)
+    }
+
+    /// Image with tag or digest as written after `FROM`
+    pub(crate) fn image_reference(&self) -> String {
+        match &self.tag_or_digest {
+            Some(Tag(tag)) => format!("{}:{}", self.image, tag),
+            Some(Digest(digest)) => format!("{}@{}", self.image, digest),
+            None => self.image.clone(),
+        }
+    }
 }
 
 impl Display for From {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match (&self.tag_or_digest, &self.name) {
-            (Some(Tag(tag)), None) => write!(f, "FROM {}:{}", self.image, tag),
-            (Some(Tag(tag)), Some(name)) => write!(f, "FROM {}:{} AS {}", self.image, tag, name),
-            (Some(Digest(digest)), None) => write!(f, "FROM {}@{}", self.image, digest),
-            (Some(Digest(digest)), Some(name)) => {
-                write!(f, "FROM {}@{} AS {}", self.image, digest, name)
+        write!(f, "FROM ")?;
+        flag(f, "platform", self.platform.as_ref())?;
+        write!(f, "{}", self.image_reference())?;
+        match &self.name {
+            Some(name) => write!(f, " AS {}", name),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `--platform` flag of [`From`], created from `os[/architecture[/variant]]` or variable reference
+/// # Example
+/// ```rust
+/// use dockerfile_rs::Platform;
+///
+/// assert_eq!(
+///     Platform::from("linux/arm64/v8"),
+///     Platform::Target {
+///         os: "linux".to_string(),
+///         architecture: Some("arm64".to_string()),
+///         variant: Some("v8".to_string()),
+///     }
+/// );
+/// assert_eq!(
+///     Platform::from("${TARGETPLATFORM}"),
+///     Platform::Variable("TARGETPLATFORM".to_string())
+/// );
+/// ```
+///
+/// [`From`]: struct.From.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum Platform {
+    /// `linux`, `linux/amd64` or `linux/arm/v7`
+    Target {
+        os: String,
+        architecture: Option<String>,
+        variant: Option<String>,
+    },
+    /// Name of build argument like `BUILDPLATFORM`, written as `$BUILDPLATFORM`
+    Variable(String),
+}
+
+impl Platform {
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let valid = |part: &str| {
+            !part.is_empty() && !part.contains(|c: char| c == '/' || c.is_whitespace())
+        };
+        let valid = match self {
+            Platform::Target {
+                os,
+                architecture,
+                variant,
+            } => {
+                valid(os)
+                    && architecture.as_deref().map_or(variant.is_none(), valid)
+                    && variant.as_deref().is_none_or(valid)
             }
-            (None, None) => write!(f, "FROM {}", self.image),
-            (None, Some(name)) => write!(f, "FROM {} AS {}", self.image, name),
+            Platform::Variable(name) => quote::is_valid_name(name, quote::escape()),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidPlatform(self.to_string()))
         }
     }
 }
 
+impl StdFrom<&str> for Platform {
+    fn from(platform: &str) -> Self {
+        if let Some(name) = platform.strip_prefix('$') {
+            let name = name
+                .strip_prefix('{')
+                .and_then(|name| name.strip_suffix('}'))
+                .unwrap_or(name);
+            return Platform::Variable(name.to_string());
+        }
+
+        let mut parts = platform.splitn(3, '/').map(String::from);
+        Platform::Target {
+            os: parts.next().unwrap_or_default(),
+            architecture: parts.next(),
+            variant: parts.next(),
+        }
+    }
+}
+
+impl StdFrom<String> for Platform {
+    fn from(platform: String) -> Self {
+        Platform::from(platform.as_str())
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Platform::Target {
+                os,
+                architecture,
+                variant,
+            } => {
+                write!(f, "{}", os)?;
+                for part in architecture.iter().chain(variant) {
+                    write!(f, "/{}", part)?;
+                }
+                Ok(())
+            }
+            Platform::Variable(name)
+                if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') =>
+            {
+                write!(f, "${}", name)
+            }
+            Platform::Variable(name) => write!(f, "${{{}}}", name),
+        }
+    }
+}
+
+/// Exec or shell form of [`Run`], [`Cmd`] and [`EntryPoint`]
+///
+/// Exec form is created from arrays and vectors, shell form from strings.
+/// Newlines in shell form are written as line continuations, use heredocs for multi-line scripts.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::Run;
+///
+/// let run = Run::from(vec!["cargo", "build"]);
+/// assert_eq!(run.to_string(), r#"RUN ["cargo", "build"]"#);
+///
+/// let run = Run::from("apt-get update && apt-get install -y curl");
+/// assert_eq!(run.to_string(), "RUN apt-get update && apt-get install -y curl");
+/// ```
+///
+/// [`Run`]: struct.Run.html
+/// [`Cmd`]: struct.Cmd.html
+/// [`EntryPoint`]: struct.EntryPoint.html
 #[derive(Debug, Clone, Eq, PartialEq)]
-pub struct Run {
-    pub params: Vec<String>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum CommandForm {
+    /// `["executable", "param1", "param2"]`
+    Exec(Vec<String>),
+    /// `command param1 param2`, run with the default shell
+    Shell(String),
+    /// `command <<EOF suffix`, heredoc is passed to stdin of `command` or run as a script without
+    /// it, supported by `RUN` only
+    ///
+    /// `suffix` is the rest of the line after the marker, like `> /file` of `cat <<EOF > /file`
+    /// or `bash` of `<<EOF bash`.
+    ///
+    /// Only one heredoc is supported, so [`DockerFile::parse`] rejects `RUN <<A <<B` that
+    /// BuildKit accepts.
+    ///
+    /// [`DockerFile::parse`]: struct.DockerFile.html#method.parse
+    Heredoc {
+        command: Option<String>,
+        heredoc: Heredoc,
+        suffix: Option<String>,
+    },
 }
 
-impl<I, S> StdFrom<I> for Run
+impl<S> StdFrom<Vec<S>> for CommandForm
 where
-    I: IntoIterator<Item = S>,
     S: Into<String>,
 {
-    fn from(iter: I) -> Self {
-        let params = iter.into_iter().map(Into::into).collect();
-        Run { params }
+    fn from(params: Vec<S>) -> Self {
+        CommandForm::Exec(params.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<S, const N: usize> StdFrom<[S; N]> for CommandForm
+where
+    S: Into<String>,
+{
+    fn from(params: [S; N]) -> Self {
+        let params = IntoIterator::into_iter(params);
+        CommandForm::Exec(params.map(Into::into).collect())
+    }
+}
+
+impl<'a> StdFrom<&'a str> for CommandForm {
+    fn from(command: &'a str) -> Self {
+        CommandForm::Shell(command.to_string())
+    }
+}
+
+impl StdFrom<String> for CommandForm {
+    fn from(command: String) -> Self {
+        CommandForm::Shell(command)
+    }
+}
+
+impl StdFrom<Heredoc> for CommandForm {
+    fn from(heredoc: Heredoc) -> Self {
+        CommandForm::Heredoc {
+            command: None,
+            heredoc,
+            suffix: None,
+        }
+    }
+}
+
+impl CommandForm {
+    /// Checks heredoc delimiter, `name` is used in error if instruction doesn't support heredocs
+    fn validate(&self, name: &str) -> Result<(), ValidationError> {
+        match self {
+            CommandForm::Heredoc { .. } if name != "RUN" => {
+                Err(ValidationError::UnexpectedHeredoc(name.to_string()))
+            }
+            CommandForm::Heredoc { heredoc, .. } => heredoc.validate(),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for CommandForm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandForm::Exec(params) => write!(f, "{}", quote::exec_form(params)),
+            CommandForm::Heredoc {
+                command,
+                heredoc,
+                suffix,
+            } => {
+                let delimiter = heredoc.unique_delimiter();
+                if let Some(command) = command {
+                    write!(f, "{} ", command)?;
+                }
+                write!(f, "<<{}", delimiter)?;
+                if let Some(suffix) = suffix {
+                    write!(f, " {}", suffix)?;
+                }
+                heredoc.write_body(f, &delimiter)
+            }
+            CommandForm::Shell(command) => {
+                let escape = quote::escape();
+                let mut prev = None;
+                for c in command.chars() {
+                    if c == '\n' && prev != Some(escape) {
+                        write!(f, "{}", escape)?;
+                    }
+                    write!(f, "{}", c)?;
+                    prev = Some(c);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Inline file of `RUN` and `COPY`, requires `Dockerfile` syntax 1.4 or newer
+///
+/// `content` is written as is, newline is added before closing delimiter if it's missing.
+/// `interpreter` is written as a shebang line, so the script is run by it instead of the shell.
+/// If some line is equal to `delimiter`, number is appended to it to keep heredoc closed
+/// at the right place.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{Heredoc, Run};
+///
+/// let run = Run::from(Heredoc::from("apt-get update\napt-get install -y curl\n"));
+/// assert_eq!(run.to_string(), "RUN <<EOF\napt-get update\napt-get install -y curl\nEOF");
+///
+/// let run = Run::from(Heredoc {
+///     content: "print('Hello, world!')".to_string(),
+///     delimiter: "PY".to_string(),
+///     interpreter: Some("/usr/bin/env python3".to_string()),
+/// });
+/// assert_eq!(
+///     run.to_string(),
+///     "RUN <<PY\n#!/usr/bin/env python3\nprint('Hello, world!')\nPY"
+/// );
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heredoc {
+    pub content: String,
+    pub delimiter: String,
+    pub interpreter: Option<String>,
+}
+
+impl<T> StdFrom<T> for Heredoc
+where
+    T: Into<String>,
+{
+    fn from(content: T) -> Self {
+        Heredoc {
+            content: content.into(),
+            delimiter: "EOF".to_string(),
+            interpreter: None,
+        }
+    }
+}
+
+impl Heredoc {
+    /// Checks that delimiter is not empty and has only alphanumeric characters, `_`, `-` and `.`
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.';
+        if !self.delimiter.is_empty() && self.delimiter.chars().all(valid) {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidDelimiter(self.delimiter.clone()))
+        }
+    }
+
+    /// Delimiter that is written, `delimiter` with number appended if it clashes with content
+    pub fn unique_delimiter(&self) -> String {
+        let script = self.script();
+        let clashes = |delimiter: &str| script.lines().any(|line| line == delimiter);
+        if !clashes(&self.delimiter) {
+            return self.delimiter.clone();
+        }
+        (1..)
+            .map(|n| format!("{}{}", self.delimiter, n))
+            .find(|delimiter| !clashes(delimiter))
+            .unwrap()
+    }
+
+    fn script(&self) -> String {
+        match &self.interpreter {
+            Some(interpreter) => format!("#!{}\n{}", interpreter, self.content),
+            None => self.content.clone(),
+        }
+    }
+
+    /// Writes lines after instruction, `delimiter` must be the result of `unique_delimiter`
+    fn write_body(&self, f: &mut fmt::Formatter, delimiter: &str) -> fmt::Result {
+        let script = self.script();
+        writeln!(f)?;
+        write!(f, "{}", script)?;
+        if !script.is_empty() && !script.ends_with('\n') {
+            writeln!(f)?;
+        }
+        write!(f, "{}", delimiter)
+    }
+}
+
+/// `sharing` option of cache [`Mount`]
+///
+/// [`Mount`]: enum.Mount.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "spec", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Sharing {
+    Shared,
+    Private,
+    Locked,
+}
+
+impl Display for Sharing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sharing::Shared => write!(f, "shared"),
+            Sharing::Private => write!(f, "private"),
+            Sharing::Locked => write!(f, "locked"),
+        }
+    }
+}
+
+/// `--mount` flag of [`Run`], requires BuildKit
+///
+/// Options are always written in the same order: `type`, `id`, `target`, `source`, `from`,
+/// `sharing`, `mode`, `uid`, `gid`, `size`, `required`, `readonly` and `rw`.
+/// `mode` is written in octal.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{Mount, Run};
+///
+/// let mut run = Run::from(vec!["cargo", "build", "--release"]);
+/// run.mounts.push(Mount::cache("/usr/local/cargo/registry"));
+/// run.mounts.push(Mount::Secret {
+///     id: Some("token".to_string()),
+///     target: None,
+///     required: true,
+///     mode: Some(0o400),
+///     uid: None,
+///     gid: None,
+/// });
+/// assert_eq!(
+///     run.to_string(),
+///     "RUN --mount=type=cache,target=/usr/local/cargo/registry \
+///          --mount=type=secret,id=token,mode=0400,required \
+///          [\"cargo\", \"build\", \"--release\"]"
+/// );
+/// ```
+///
+/// [`Run`]: struct.Run.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum Mount {
+    /// Read-only unless `readwrite`, mounts build context or stage given in `from`
+    Bind {
+        target: String,
+        source: Option<String>,
+        from: Option<String>,
+        readwrite: bool,
+    },
+    /// Directory kept between builds
+    Cache {
+        id: Option<String>,
+        target: String,
+        source: Option<String>,
+        from: Option<String>,
+        sharing: Option<Sharing>,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        readonly: bool,
+    },
+    /// `size` is in bytes
+    Tmpfs { target: String, size: Option<u64> },
+    /// Secret file, mounted to `/run/secrets/<id>` if `target` is not set
+    Secret {
+        id: Option<String>,
+        target: Option<String>,
+        required: bool,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    },
+    /// SSH agent socket
+    Ssh {
+        id: Option<String>,
+        target: Option<String>,
+        required: bool,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    },
+}
+
+impl Mount {
+    /// Read-only bind mount of build context
+    pub fn bind<T: Into<String>>(target: T) -> Self {
+        Mount::Bind {
+            target: target.into(),
+            source: None,
+            from: None,
+            readwrite: false,
+        }
+    }
+
+    pub fn cache<T: Into<String>>(target: T) -> Self {
+        Mount::Cache {
+            id: None,
+            target: target.into(),
+            source: None,
+            from: None,
+            sharing: None,
+            mode: None,
+            uid: None,
+            gid: None,
+            readonly: false,
+        }
+    }
+
+    pub fn tmpfs<T: Into<String>>(target: T) -> Self {
+        Mount::Tmpfs {
+            target: target.into(),
+            size: None,
+        }
+    }
+
+    pub fn secret<T: Into<String>>(id: T) -> Self {
+        Mount::Secret {
+            id: Some(id.into()),
+            target: None,
+            required: false,
+            mode: None,
+            uid: None,
+            gid: None,
+        }
+    }
+
+    /// Mount of default SSH agent socket
+    pub fn ssh() -> Self {
+        Mount::Ssh {
+            id: None,
+            target: None,
+            required: false,
+            mode: None,
+            uid: None,
+            gid: None,
+        }
+    }
+
+    /// Checks that target is not empty, secret must have either id or target
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let valid = match self {
+            Mount::Bind { target, .. }
+            | Mount::Cache { target, .. }
+            | Mount::Tmpfs { target, .. } => !target.is_empty(),
+            Mount::Secret { id, target, .. } => id.is_some() || target.is_some(),
+            Mount::Ssh { .. } => true,
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidMount(self.to_string()))
+        }
+    }
+}
+
+impl Display for Mount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, id, target, source, from) = match self {
+            Mount::Bind {
+                target,
+                source,
+                from,
+                ..
+            } => ("bind", None, Some(target), source.as_ref(), from.as_ref()),
+            Mount::Cache {
+                id,
+                target,
+                source,
+                from,
+                ..
+            } => (
+                "cache",
+                id.as_ref(),
+                Some(target),
+                source.as_ref(),
+                from.as_ref(),
+            ),
+            Mount::Tmpfs { target, .. } => ("tmpfs", None, Some(target), None, None),
+            Mount::Secret { id, target, .. } => {
+                ("secret", id.as_ref(), target.as_ref(), None, None)
+            }
+            Mount::Ssh { id, target, .. } => ("ssh", id.as_ref(), target.as_ref(), None, None),
+        };
+        let (sharing, size) = match self {
+            Mount::Cache { sharing, .. } => (*sharing, None),
+            Mount::Tmpfs { size, .. } => (None, *size),
+            _ => (None, None),
+        };
+        let (mode, uid, gid) = match self {
+            Mount::Cache { mode, uid, gid, .. }
+            | Mount::Secret { mode, uid, gid, .. }
+            | Mount::Ssh { mode, uid, gid, .. } => (*mode, *uid, *gid),
+            _ => (None, None, None),
+        };
+        let (required, readonly, readwrite) = match self {
+            Mount::Bind { readwrite, .. } => (false, false, *readwrite),
+            Mount::Cache { readonly, .. } => (false, *readonly, false),
+            Mount::Secret { required, .. } | Mount::Ssh { required, .. } => {
+                (*required, false, false)
+            }
+            Mount::Tmpfs { .. } => (false, false, false),
+        };
+
+        let mut options = vec![format!("type={}", kind)];
+        let mut option = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                options.push(quote::csv_field(&format!("{}={}", name, value)));
+            }
+        };
+        option("id", id.cloned());
+        option("target", target.cloned());
+        option("source", source.cloned());
+        option("from", from.cloned());
+        option("sharing", sharing.map(|sharing| sharing.to_string()));
+        option("mode", mode.map(|mode| format!("0{:o}", mode)));
+        option("uid", uid.map(|uid| uid.to_string()));
+        option("gid", gid.map(|gid| gid.to_string()));
+        option("size", size.map(|size| size.to_string()));
+        let flags = [
+            ("required", required),
+            ("readonly", readonly),
+            ("rw", readwrite),
+        ];
+        for (name, _) in flags.iter().filter(|(_, set)| *set) {
+            options.push(name.to_string());
+        }
+
+        write!(f, "{}", options.join(","))
+    }
+}
+
+/// `--network` flag of [`Run`]
+///
+/// [`Run`]: struct.Run.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "spec", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Network {
+    Default,
+    /// No network access, command can't download anything
+    None,
+    Host,
+}
+
+impl Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Network::Default => write!(f, "default"),
+            Network::None => write!(f, "none"),
+            Network::Host => write!(f, "host"),
+        }
+    }
+}
+
+/// `--security` flag of [`Run`], `Insecure` requires `security.insecure` entitlement
+///
+/// [`Run`]: struct.Run.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "spec", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Security {
+    Sandbox,
+    Insecure,
+}
+
+impl Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Security::Sandbox => write!(f, "sandbox"),
+            Security::Insecure => write!(f, "insecure"),
+        }
+    }
+}
+
+/// Flags are written in order: `--mount`, `--network`, `--security`
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{Network, Run};
+///
+/// let mut run = Run::from(vec!["cargo", "build", "--offline"]);
+/// run.network = Some(Network::None);
+/// assert_eq!(run.to_string(), r#"RUN --network=none ["cargo", "build", "--offline"]"#);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Run {
+    pub mounts: Vec<Mount>,
+    pub network: Option<Network>,
+    pub security: Option<Security>,
+    pub command: CommandForm,
+}
+
+impl Run {
+    /// Checks heredoc delimiter and mounts
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.mounts.iter().try_for_each(Mount::validate)?;
+        self.command.validate("RUN")
+    }
+}
+
+impl<T> StdFrom<T> for Run
+where
+    T: Into<CommandForm>,
+{
+    fn from(command: T) -> Self {
+        Run {
+            mounts: Vec::new(),
+            network: None,
+            security: None,
+            command: command.into(),
+        }
     }
 }
 
 impl Display for Run {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "RUN [{}]",
-            self.params
-                .iter()
-                .map(|i| format!(r#""{}""#, i))
-                .collect::<Vec<String>>()
-                .join(", ")
-        )
+        write!(f, "RUN ")?;
+        for mount in &self.mounts {
+            flag(f, "mount", Some(mount))?;
+        }
+        flag(f, "network", self.network)?;
+        flag(f, "security", self.security)?;
+        write!(f, "{}", self.command)
     }
 }
 
 impl Instruction for Run {}
-impl StorageInstruction for Run {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cmd {
-    pub params: Vec<String>,
+    pub command: CommandForm,
 }
 
-impl<I, S> StdFrom<I> for Cmd
+impl Cmd {
+    /// Checks that command is not a heredoc
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.command.validate("CMD")
+    }
+}
+
+impl<T> StdFrom<T> for Cmd
 where
-    I: IntoIterator<Item = S>,
-    S: Into<String>,
+    T: Into<CommandForm>,
 {
-    fn from(iter: I) -> Self {
-        let params = iter.into_iter().map(Into::into).collect();
-        Cmd { params }
+    fn from(command: T) -> Self {
+        Cmd {
+            command: command.into(),
+        }
     }
 }
 
 impl Display for Cmd {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "CMD [{}]",
-            self.params
-                .iter()
-                .map(|i| format!(r#""{}""#, i))
-                .collect::<Vec<String>>()
-                .join(", ")
-        )
+        write!(f, "CMD {}", self.command)
     }
 }
 
 impl Instruction for Cmd {}
 
+/// `LABEL` instruction, labels are written in insertion order
+///
+/// Repeated key keeps its first position and the last value. Labels created from `HashMap`
+/// are sorted by key, so the output doesn't depend on hashing.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::Label;
+///
+/// let label = Label::from(vec![("version", "1.0"), ("description", "app")]);
+/// assert_eq!(label.to_string(), "LABEL version=\"1.0\" \\\n      description=\"app\"");
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Label {
-    inner: HashMap<String, String>,
+    #[cfg_attr(feature = "serde", serde(rename = "pairs"))]
+    inner: Vec<(String, Value)>,
+}
+
+impl<K, V> StdFrom<Vec<(K, V)>> for Label
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    fn from(pairs: Vec<(K, V)>) -> Self {
+        let mut label = Label { inner: Vec::new() };
+        for (k, v) in pairs {
+            label.insert(k, v);
+        }
+        label
+    }
 }
 
 impl<K, V> StdFrom<HashMap<K, V>> for Label
 where
-    K: Into<String> + Eq + Hash,
-    V: AsRef<str>,
+    K: Into<String> + Eq + Hash,
+    V: Into<Value>,
+{
+    fn from(map: HashMap<K, V>) -> Self {
+        Label::from(map.into_iter().collect::<Vec<_>>()).sorted()
+    }
+}
+
+impl<K, V> StdFrom<BTreeMap<K, V>> for Label
+where
+    K: Into<String>,
+    V: Into<Value>,
 {
-    fn from(map: HashMap<K, V>) -> Self {
-        let inner = map
-            .into_iter()
-            .map(|(k, v)| (k.into(), v.as_ref().replace('\n', "\\\n")))
-            .collect();
-        Label { inner }
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Label::from(map.into_iter().collect::<Vec<_>>())
     }
 }
 
 impl<K, V> StdFrom<(K, V)> for Label
 where
-    K: Into<String> + Eq + Hash,
-    V: Into<String>,
+    K: Into<String>,
+    V: Into<Value>,
 {
-    fn from((k, v): (K, V)) -> Self {
-        let mut inner = HashMap::new();
-        inner.insert(k.into(), v.into());
-        Label { inner }
+    fn from(pair: (K, V)) -> Self {
+        Label::from(vec![pair])
+    }
+}
+
+impl Label {
+    /// Adds label or replaces value of existing one keeping its position
+    pub fn insert<K: Into<String>, V: Into<Value>>(&mut self, key: K, value: V) {
+        insert(&mut self.inner, key.into(), value.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.inner.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    /// Labels in the order they are written
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.inner.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Sorts labels by key
+    pub fn sorted(mut self) -> Self {
+        self.inner.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self
+    }
+
+    /// Checks that keys are not empty and have no `=` and newlines
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self
+            .inner
+            .iter()
+            .map(|(k, _)| k)
+            .find(|k| !quote::is_valid_label_key(k))
+        {
+            Some(key) => Err(ValidationError::InvalidKey(key.clone())),
+            None => Ok(()),
+        }
     }
 }
 
 impl Display for Label {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let escape = quote::escape();
         write!(
             f,
             "LABEL {}",
             self.inner
                 .iter()
-                .map(|(k, v)| format!(r#"{}="{}""#, k, v))
+                .map(|(k, v)| format!("{}={}", quote::label_key(k, escape), v))
                 .collect::<Vec<String>>()
-                .join(" \\\n      ")
+                .join(&format!(" {}\n      ", escape))
         )
     }
 }
 
 impl Instruction for Label {}
-impl StorageInstruction for Label {}
 
 /// Deprecated, use [`Label`] with `maintainer` key instead
 ///
 /// [`Label`]: struct.Label.html
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Maintainer {
     pub name: String,
 }
@@ -177,8 +1212,8 @@ where
 
 impl PartialEq<Label> for Maintainer {
     fn eq(&self, other: &Label) -> bool {
-        if let Some(name) = other.inner.get("maintainer") {
-            self.name == *name
+        if let Some(name) = other.get("maintainer") {
+            self.name == name.value
         } else {
             false
         }
@@ -191,60 +1226,302 @@ impl Display for Maintainer {
     }
 }
 
+/// `EXPOSE` instruction with one or several ports
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{Expose, ExposedPort, Port, Protocol};
+///
+/// let expose = Expose::from(vec![Port::from((8000..=8010, Protocol::Udp)), Port::from(9090)]);
+/// assert_eq!(expose.to_string(), "EXPOSE 8000-8010/udp 9090");
+///
+/// let expose = Expose::from(ExposedPort::Variable("${PORT}/udp".to_string()));
+/// assert_eq!(expose.to_string(), "EXPOSE ${PORT}/udp");
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Expose {
+    pub ports: Vec<ExposedPort>,
+}
+
+impl Expose {
+    /// Checks that there is at least one port and every port is valid
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.ports.is_empty() {
+            return Err(ValidationError::MissingPort);
+        }
+        self.ports.iter().try_for_each(ExposedPort::validate)
+    }
+}
+
+impl<T> StdFrom<T> for Expose
+where
+    T: Into<ExposedPort>,
+{
+    fn from(port: T) -> Self {
+        Expose {
+            ports: vec![port.into()],
+        }
+    }
+}
+
+impl<T> StdFrom<Vec<T>> for Expose
+where
+    T: Into<ExposedPort>,
+{
+    fn from(ports: Vec<T>) -> Self {
+        Expose {
+            ports: ports.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl Display for Expose {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EXPOSE")?;
+        for port in &self.ports {
+            write!(f, " {}", port)?;
+        }
+        Ok(())
+    }
+}
+
+/// Port of [`Expose`] or word with variable reference like `$PORT` or `${PORT}/udp`
+///
+/// Variable is expanded and parsed as port by [`DockerFile::substitute`].
+///
+/// [`Expose`]: struct.Expose.html
+/// [`DockerFile::substitute`]: struct.DockerFile.html#method.substitute
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum ExposedPort {
+    Port(Port),
+    /// Word as written, `$` that is not escaped starts a reference
+    Variable(String),
+}
+
+impl ExposedPort {
+    /// Checks port, variable must have a reference and no whitespace
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self {
+            ExposedPort::Port(port) => port.validate(),
+            ExposedPort::Variable(word)
+                if quote::has_expansion(word) && !word.contains(char::is_whitespace) =>
+            {
+                Ok(())
+            }
+            ExposedPort::Variable(word) => Err(ValidationError::InvalidPort(word.clone())),
+        }
+    }
+}
+
+impl<T> StdFrom<T> for ExposedPort
+where
+    T: Into<Port>,
+{
+    fn from(port: T) -> Self {
+        ExposedPort::Port(port.into())
+    }
+}
+
+impl Display for ExposedPort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExposedPort::Port(port) => write!(f, "{}", port),
+            ExposedPort::Variable(word) => write!(f, "{}", word),
+        }
+    }
+}
+
+/// Port or inclusive range of ports of [`Expose`], `end` is the last port of the range
+///
+/// [`Expose`]: struct.Expose.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Port {
     pub port: u16,
-    pub proto: Option<String>,
+    pub end: Option<u16>,
+    pub protocol: Option<Protocol>,
+}
+
+impl Port {
+    /// Rejects port 0 and ranges that end before they start
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.port == 0 || self.end.is_some_and(|end| end < self.port) {
+            Err(ValidationError::InvalidPort(self.to_string()))
+        } else {
+            Ok(())
+        }
+    }
 }
 
-impl StdFrom<u16> for Expose {
+impl StdFrom<u16> for Port {
     fn from(port: u16) -> Self {
-        Expose { port, proto: None }
+        Port {
+            port,
+            end: None,
+            protocol: None,
+        }
     }
 }
 
-impl Display for Expose {
+impl StdFrom<(u16, Protocol)> for Port {
+    fn from((port, protocol): (u16, Protocol)) -> Self {
+        Port {
+            protocol: Some(protocol),
+            ..Port::from(port)
+        }
+    }
+}
+
+impl StdFrom<RangeInclusive<u16>> for Port {
+    fn from(range: RangeInclusive<u16>) -> Self {
+        Port {
+            port: *range.start(),
+            end: Some(*range.end()),
+            protocol: None,
+        }
+    }
+}
+
+impl StdFrom<(RangeInclusive<u16>, Protocol)> for Port {
+    fn from((range, protocol): (RangeInclusive<u16>, Protocol)) -> Self {
+        Port {
+            protocol: Some(protocol),
+            ..Port::from(range)
+        }
+    }
+}
+
+impl Display for Port {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "EXPOSE {}{}",
-            self.port,
-            self.proto
-                .clone()
-                .map(|s| format!("/{}", s))
-                .unwrap_or_default()
-        )
+        write!(f, "{}", self.port)?;
+        if let Some(end) = self.end {
+            write!(f, "-{}", end)?;
+        }
+        if let Some(protocol) = self.protocol {
+            write!(f, "/{}", protocol)?;
+        }
+        Ok(())
+    }
+}
+
+/// Protocol of [`Port`], Docker uses `tcp` if it's not set
+///
+/// [`Port`]: struct.Port.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Protocol {
+    Tcp,
+    Udp,
+    Sctp,
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+            Protocol::Sctp => write!(f, "sctp"),
+        }
     }
 }
 
 impl Instruction for Expose {}
-impl StorageInstruction for Expose {}
 
+/// `ENV` instruction, variables are written in insertion order
+///
+/// Repeated name keeps its first position and the last value. Variables created from `HashMap`
+/// are sorted by name.
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Env {
-    inner: HashMap<String, String>,
+    #[cfg_attr(feature = "serde", serde(rename = "pairs"))]
+    inner: Vec<(String, Value)>,
+}
+
+impl<K, V> StdFrom<Vec<(K, V)>> for Env
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    fn from(pairs: Vec<(K, V)>) -> Self {
+        let mut env = Env { inner: Vec::new() };
+        for (k, v) in pairs {
+            env.insert(k, v);
+        }
+        env
+    }
 }
 
 impl<K, V> StdFrom<HashMap<K, V>> for Env
 where
     K: Into<String> + Eq + Hash,
-    V: Into<String>,
+    V: Into<Value>,
 {
     fn from(map: HashMap<K, V>) -> Self {
-        let inner = map.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
-        Env { inner }
+        Env::from(map.into_iter().collect::<Vec<_>>()).sorted()
+    }
+}
+
+impl<K, V> StdFrom<BTreeMap<K, V>> for Env
+where
+    K: Into<String>,
+    V: Into<Value>,
+{
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Env::from(map.into_iter().collect::<Vec<_>>())
     }
 }
 
 impl<K, V> StdFrom<(K, V)> for Env
 where
-    K: Into<String> + Eq + Hash,
-    V: Into<String>,
+    K: Into<String>,
+    V: Into<Value>,
 {
-    fn from((k, v): (K, V)) -> Self {
-        let mut inner = HashMap::new();
-        inner.insert(k.into(), v.into());
-        Env { inner }
+    fn from(pair: (K, V)) -> Self {
+        Env::from(vec![pair])
+    }
+}
+
+impl Env {
+    /// Adds variable or replaces value of existing one keeping its position
+    pub fn insert<K: Into<String>, V: Into<Value>>(&mut self, name: K, value: V) {
+        insert(&mut self.inner, name.into(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.inner.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    /// Variables in the order they are written
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.inner.iter().map(|(k, v)| (k.as_str(), v))
+    }
+
+    /// Sorts variables by name
+    pub fn sorted(mut self) -> Self {
+        self.inner.sort_by(|(a, _), (b, _)| a.cmp(b));
+        self
+    }
+
+    /// Checks that names are not empty and have no whitespaces, quotes, `=`, `$`, `\` and escape
+    /// character
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let escape = quote::escape();
+        match self
+            .inner
+            .iter()
+            .map(|(k, _)| k)
+            .find(|k| !quote::is_valid_name(k, escape))
+        {
+            Some(key) => Err(ValidationError::InvalidKey(key.clone())),
+            None => Ok(()),
+        }
     }
 }
 
@@ -255,7 +1532,7 @@ impl Display for Env {
             "ENV {}",
             self.inner
                 .iter()
-                .map(|(k, v)| format!(r#"{}="{}""#, k, v))
+                .map(|(k, v)| format!("{}={}", k, v))
                 .collect::<Vec<String>>()
                 .join(" ")
         )
@@ -263,147 +1540,401 @@ impl Display for Env {
 }
 
 impl Instruction for Env {}
-impl StorageInstruction for Env {}
 
+/// Replaces value of existing key or appends new pair
+fn insert(pairs: &mut Vec<(String, Value)>, key: String, value: Value) {
+    match pairs.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, v)) => *v = value,
+        None => pairs.push((key, value)),
+    }
+}
+
+/// Writes `--name=value ` if value is present
+fn flag<T: Display>(f: &mut fmt::Formatter, name: &str, value: Option<T>) -> fmt::Result {
+    match value {
+        Some(value) => write!(f, "--{}={} ", name, value),
+        None => Ok(()),
+    }
+}
+
+/// Formats duration the way Docker parses it, e.g. `1m30s` or `1s500ms`
+fn duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let nanos = duration.subsec_nanos();
+    if secs == 0 && nanos == 0 {
+        return "0s".to_string();
+    }
+
+    let parts = [
+        (secs / 3600, "h"),
+        (secs % 3600 / 60, "m"),
+        (secs % 60, "s"),
+        (u64::from(nanos / 1_000_000), "ms"),
+        (u64::from(nanos / 1000 % 1000), "us"),
+        (u64::from(nanos % 1000), "ns"),
+    ];
+    parts
+        .iter()
+        .filter(|(value, _)| *value != 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect()
+}
+
+/// Writes `--name ` if flag is set
+fn switch(f: &mut fmt::Formatter, name: &str, set: bool) -> fmt::Result {
+    if set {
+        write!(f, "--{} ", name)
+    } else {
+        Ok(())
+    }
+}
+
+/// `user[:group]` of `--chown`
+fn chown(user: &User) -> String {
+    match &user.group {
+        Some(group) => format!("{}:{}", user.user, group),
+        None => user.user.clone(),
+    }
+}
+
+/// `--chmod` flag of [`Add`] and [`Copy`]
+///
+/// Strings of octal digits are converted into `Octal`, other strings are symbolic modes.
+/// # Example
+/// ```rust
+/// use dockerfile_rs::Chmod;
+///
+/// assert_eq!(Chmod::from(0o755).to_string(), "0755");
+/// assert_eq!(Chmod::from("644"), Chmod::Octal(0o644));
+/// assert_eq!(Chmod::from("u=rwx,go=rx").to_string(), "u=rwx,go=rx");
+/// ```
+///
+/// [`Add`]: struct.Add.html
+/// [`Copy`]: struct.Copy.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum Chmod {
+    Octal(u32),
+    Symbolic(String),
+}
+
+impl StdFrom<u32> for Chmod {
+    fn from(mode: u32) -> Self {
+        Chmod::Octal(mode)
+    }
+}
+
+impl<'a> StdFrom<&'a str> for Chmod {
+    fn from(mode: &'a str) -> Self {
+        match u32::from_str_radix(mode, 8) {
+            Ok(octal) if !mode.starts_with('+') => Chmod::Octal(octal),
+            _ => Chmod::Symbolic(mode.to_string()),
+        }
+    }
+}
+
+impl StdFrom<String> for Chmod {
+    fn from(mode: String) -> Self {
+        Chmod::from(mode.as_str())
+    }
+}
+
+impl Display for Chmod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chmod::Octal(mode) => write!(f, "0{:o}", mode),
+            Chmod::Symbolic(mode) => write!(f, "{}", mode),
+        }
+    }
+}
+
+/// Sources can be globs, flags are written in order: `--chown`, `--chmod`, `--link`,
+/// `--checksum`, `--keep-git-dir`, `--exclude`
+/// # Example
+/// ```rust
+/// use dockerfile_rs::Add;
+///
+/// let add = Add::from((vec!["a.tar.gz", "b.tar.gz"], "/opt/"));
+/// assert_eq!(add.to_string(), r#"ADD "a.tar.gz" "b.tar.gz" "/opt/""#);
+///
+/// let add = Add::from(("My Documents", "/docs"));
+/// assert_eq!(add.to_string(), r#"ADD ["My Documents", "/docs"]"#);
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Add {
-    pub src: String,
+    pub src: Vec<Source>,
     pub dst: String,
     pub chown: Option<User>,
+    pub chmod: Option<Chmod>,
+    pub link: bool,
+    /// Digest of remote source like `sha256:...`
+    pub checksum: Option<String>,
+    /// Keeps `.git` directory of Git repository source
+    pub keep_git_dir: bool,
+    pub exclude: Vec<String>,
 }
 
 impl<K, V> StdFrom<(K, V)> for Add
 where
-    K: Into<String>,
+    K: Into<Source>,
     V: Into<String>,
 {
     fn from((src, dst): (K, V)) -> Self {
+        Add::from((vec![src], dst))
+    }
+}
+
+impl<K, V> StdFrom<(Vec<K>, V)> for Add
+where
+    K: Into<Source>,
+    V: Into<String>,
+{
+    fn from((src, dst): (Vec<K>, V)) -> Self {
         Add {
-            src: src.into(),
+            src: src.into_iter().map(Into::into).collect(),
             dst: dst.into(),
             chown: None,
+            chmod: None,
+            link: false,
+            checksum: None,
+            keep_git_dir: false,
+            exclude: Vec::new(),
         }
     }
 }
 
+impl Add {
+    /// Checks heredoc delimiters, that there is a source
+    /// and destination ends with `/` if there are several of them
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        check_paths("ADD", &self.src, &self.dst)
+    }
+}
+
 impl Display for Add {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self.chown {
-            Some(chown) => write!(
-                f,
-                r#"ADD --chown={}{} "{}" "{}""#,
-                chown.user,
-                chown
-                    .group
-                    .clone()
-                    .map(|s| format!(":{}", s))
-                    .unwrap_or_default(),
-                self.src,
-                self.dst
-            ),
-            None => write!(f, r#"ADD "{}" "{}""#, self.src, self.dst),
+        write!(f, "ADD ")?;
+        flag(f, "chown", self.chown.as_ref().map(chown))?;
+        flag(f, "chmod", self.chmod.as_ref())?;
+        switch(f, "link", self.link)?;
+        flag(f, "checksum", self.checksum.as_ref())?;
+        switch(f, "keep-git-dir", self.keep_git_dir)?;
+        for pattern in &self.exclude {
+            flag(f, "exclude", Some(pattern))?;
         }
+        paths(f, &self.src, &self.dst)
     }
 }
 
 impl Instruction for Add {}
-impl StorageInstruction for Add {}
 
+/// Source of [`Add`] and [`Copy`], path, URL or inline file
+///
+/// [`Add`]: struct.Add.html
+/// [`Copy`]: struct.Copy.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum Source {
+    Path(String),
+    Heredoc(Heredoc),
+}
+
+impl<'a> StdFrom<&'a str> for Source {
+    fn from(path: &'a str) -> Self {
+        Source::Path(path.to_string())
+    }
+}
+
+impl StdFrom<String> for Source {
+    fn from(path: String) -> Self {
+        Source::Path(path)
+    }
+}
+
+impl StdFrom<Heredoc> for Source {
+    fn from(heredoc: Heredoc) -> Self {
+        Source::Heredoc(heredoc)
+    }
+}
+
+/// Checks heredoc delimiters, that there is a source
+/// and destination ends with `/` if there are several of them
+fn check_paths(name: &str, src: &[Source], dst: &str) -> Result<(), ValidationError> {
+    for src in src {
+        if let Source::Heredoc(heredoc) = src {
+            heredoc.validate()?;
+        }
+    }
+    match src.len() {
+        0 => Err(ValidationError::MissingSource(name.to_string())),
+        1 => Ok(()),
+        _ if dst.ends_with('/') => Ok(()),
+        _ => Err(ValidationError::InvalidDestination(dst.to_string())),
+    }
+}
+
+/// Writes sources and destination of `ADD` and `COPY` followed by heredocs,
+/// JSON array is used if some path has whitespaces or quotes and there are no heredocs
+fn paths(f: &mut fmt::Formatter, src: &[Source], dst: &str) -> fmt::Result {
+    let mut paths = Vec::new();
+    for src in src {
+        if let Source::Path(path) = src {
+            paths.push(path.as_str());
+        }
+    }
+    paths.push(dst);
+    let special = |path: &&str| path.contains(char::is_whitespace) || path.contains('"');
+    if paths.len() == src.len() + 1 && paths.iter().any(special) {
+        return write!(f, "{}", quote::exec_form(&paths));
+    }
+
+    let escape = quote::escape();
+    let mut heredocs = Vec::new();
+    for src in src {
+        match src {
+            Source::Path(path) => write!(f, "{} ", quote::path(path, escape))?,
+            Source::Heredoc(heredoc) => {
+                let delimiter = heredoc.unique_delimiter();
+                write!(f, "<<{} ", delimiter)?;
+                heredocs.push((heredoc, delimiter));
+            }
+        }
+    }
+    write!(f, "{}", quote::path(dst, escape))?;
+    for (heredoc, delimiter) in heredocs {
+        heredoc.write_body(f, &delimiter)?;
+    }
+    Ok(())
+}
+
+/// Sources can be globs, flags are written in order:
+/// `--from`, `--chown`, `--chmod`, `--link`, `--parents`, `--exclude`
+/// # Example
+/// ```rust
+/// use dockerfile_rs::{Copy, Heredoc};
+///
+/// let copy = Copy::from(("target/release/app", "/usr/bin/app"));
+/// assert_eq!(copy.to_string(), r#"COPY "target/release/app" "/usr/bin/app""#);
+///
+/// let copy = Copy::from((vec!["Cargo.toml", "Cargo.lock"], "./"));
+/// assert_eq!(copy.to_string(), r#"COPY "Cargo.toml" "Cargo.lock" "./""#);
+///
+/// let copy = Copy::from((Heredoc::from("listen 80;"), "/etc/nginx/conf.d/app.conf"));
+/// assert_eq!(
+///     copy.to_string(),
+///     "COPY <<EOF \"/etc/nginx/conf.d/app.conf\"\nlisten 80;\nEOF"
+/// );
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Copy {
-    pub src: String,
+    pub src: Vec<Source>,
     pub dst: String,
     pub from: Option<String>,
     pub chown: Option<User>,
+    pub chmod: Option<Chmod>,
+    pub link: bool,
+    pub parents: bool,
+    pub exclude: Vec<String>,
 }
 
 impl<K, V> StdFrom<(K, V)> for Copy
 where
-    K: Into<String>,
+    K: Into<Source>,
     V: Into<String>,
 {
     fn from((src, dst): (K, V)) -> Self {
+        Copy::from((vec![src], dst))
+    }
+}
+
+impl<K, V> StdFrom<(Vec<K>, V)> for Copy
+where
+    K: Into<Source>,
+    V: Into<String>,
+{
+    fn from((src, dst): (Vec<K>, V)) -> Self {
         Copy {
-            src: src.into(),
+            src: src.into_iter().map(Into::into).collect(),
             dst: dst.into(),
             from: None,
             chown: None,
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
         }
     }
 }
 
+impl Copy {
+    /// Checks heredoc delimiters, that there is a source
+    /// and destination ends with `/` if there are several of them
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        check_paths("COPY", &self.src, &self.dst)
+    }
+}
+
 impl Display for Copy {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match (&self.from, &self.chown) {
-            (Some(from), Some(chown)) => write!(
-                f,
-                r#"COPY --from={} --chown={}{} "{}" "{}""#,
-                from,
-                chown.user,
-                chown
-                    .group
-                    .clone()
-                    .map(|s| format!(":{}", s))
-                    .unwrap_or_default(),
-                self.src,
-                self.dst
-            ),
-            (Some(from), None) => {
-                write!(f, r#"COPY --from={} "{}" "{}""#, from, self.src, self.dst)
-            }
-            (None, Some(chown)) => write!(
-                f,
-                r#"COPY --chown={}{} "{}" "{}""#,
-                chown.user,
-                chown
-                    .group
-                    .clone()
-                    .map(|group| format!(":{}", group))
-                    .unwrap_or_default(),
-                self.src,
-                self.dst
-            ),
-            (None, None) => write!(f, r#"COPY "{}" "{}""#, self.src, self.dst),
+        write!(f, "COPY ")?;
+        flag(f, "from", self.from.as_ref())?;
+        flag(f, "chown", self.chown.as_ref().map(chown))?;
+        flag(f, "chmod", self.chmod.as_ref())?;
+        switch(f, "link", self.link)?;
+        switch(f, "parents", self.parents)?;
+        for pattern in &self.exclude {
+            flag(f, "exclude", Some(pattern))?;
         }
+        paths(f, &self.src, &self.dst)
     }
 }
 
 impl Instruction for Copy {}
-impl StorageInstruction for Copy {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EntryPoint {
-    params: Vec<String>,
+    pub command: CommandForm,
+}
+
+impl EntryPoint {
+    /// Checks that command is not a heredoc
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.command.validate("ENTRYPOINT")
+    }
 }
 
-impl<I, S> StdFrom<I> for EntryPoint
+impl<T> StdFrom<T> for EntryPoint
 where
-    I: IntoIterator<Item = S>,
-    S: Into<String>,
+    T: Into<CommandForm>,
 {
-    fn from(iter: I) -> Self {
-        let params = iter.into_iter().map(Into::into).collect();
-        EntryPoint { params }
+    fn from(command: T) -> Self {
+        EntryPoint {
+            command: command.into(),
+        }
     }
 }
 
 impl Display for EntryPoint {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "ENTRYPOINT [{}]",
-            self.params
-                .iter()
-                .map(|i| format!(r#""{}""#, i))
-                .collect::<Vec<String>>()
-                .join(", ")
-        )
+        write!(f, "ENTRYPOINT {}", self.command)
     }
 }
 
 impl Instruction for EntryPoint {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Volume {
     pub paths: Vec<String>,
 }
@@ -421,22 +1952,14 @@ where
 
 impl Display for Volume {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "VOLUME [{}]",
-            self.paths
-                .iter()
-                .map(|i| format!(r#""{}""#, i))
-                .collect::<Vec<String>>()
-                .join(", ")
-        )
+        write!(f, "VOLUME {}", quote::exec_form(&self.paths))
     }
 }
 
 impl Instruction for Volume {}
-impl StorageInstruction for Volume {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct User {
     pub user: String,
     pub group: Option<String>,
@@ -452,9 +1975,9 @@ impl Display for User {
 }
 
 impl Instruction for User {}
-impl StorageInstruction for User {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct WorkDir {
     pub path: String,
 }
@@ -475,18 +1998,18 @@ impl Display for WorkDir {
 }
 
 impl Instruction for WorkDir {}
-impl StorageInstruction for WorkDir {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Arg {
     pub name: String,
-    pub value: Option<String>,
+    pub value: Option<Value>,
 }
 
 impl<K, V> StdFrom<(K, V)> for Arg
 where
     K: Into<String>,
-    V: Into<String>,
+    V: Into<Value>,
 {
     fn from((name, value): (K, V)) -> Self {
         Arg {
@@ -496,19 +2019,31 @@ where
     }
 }
 
+impl Arg {
+    /// Checks that name is not empty and has no whitespaces, quotes, `=`, `$`, `\` and escape
+    /// character
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if quote::is_valid_name(&self.name, quote::escape()) {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidKey(self.name.clone()))
+        }
+    }
+}
+
 impl Display for Arg {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.value {
-            Some(value) => write!(f, r#"ARG {}="{}""#, self.name, value),
+            Some(value) => write!(f, "ARG {}={}", self.name, value),
             None => write!(f, "ARG {}", self.name),
         }
     }
 }
 
 impl Instruction for Arg {}
-impl StorageInstruction for Arg {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StopSignal {
     pub signal: String,
 }
@@ -531,20 +2066,67 @@ impl Display for StopSignal {
 }
 
 impl Instruction for StopSignal {}
-impl StorageInstruction for StopSignal {}
 
+/// `HEALTHCHECK` instruction, durations are written with units like `30s` or `1m30s`
+///
+/// Docker doesn't expand variables in flags of `HEALTHCHECK`, so durations and retries are
+/// always literal and [`DockerFile::parse`] rejects `--interval=$INTERVAL` with
+/// [`ParseErrorKind::UnsupportedVariable`].
+///
+/// [`DockerFile::parse`]: struct.DockerFile.html#method.parse
+/// [`ParseErrorKind::UnsupportedVariable`]: enum.ParseErrorKind.html#variant.UnsupportedVariable
+/// # Example
+/// ```rust
+/// use std::time::Duration;
+/// use dockerfile_rs::{Cmd, HealthCheck};
+///
+/// let health_check = HealthCheck::Check {
+///     cmd: Cmd::from("curl -f http://localhost/"),
+///     interval: Some(Duration::from_secs(90)),
+///     timeout: Some(Duration::from_secs(5)),
+///     start_period: None,
+///     start_interval: Some(Duration::from_millis(500)),
+///     retries: Some(3),
+/// };
+/// assert_eq!(
+///     health_check.to_string(),
+///     "HEALTHCHECK --interval=1m30s --timeout=5s --start-interval=500ms --retries=3 \
+///      CMD curl -f http://localhost/"
+/// );
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum HealthCheck {
     Check {
         cmd: Cmd,
-        interval: Option<i32>,
-        timeout: Option<i32>,
-        start_period: Option<i32>,
+        interval: Option<Duration>,
+        timeout: Option<Duration>,
+        start_period: Option<Duration>,
+        /// Interval of checks during `start_period`, requires Docker 25.0 or newer
+        start_interval: Option<Duration>,
         retries: Option<i32>,
     },
     None,
 }
 
+impl HealthCheck {
+    /// Checks that command is not a heredoc and retries are not negative
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match self {
+            HealthCheck::Check {
+                retries: Some(retries),
+                ..
+            } if *retries < 0 => Err(ValidationError::NegativeRetries(*retries)),
+            HealthCheck::Check { cmd, .. } => cmd.command.validate("HEALTHCHECK"),
+            HealthCheck::None => Ok(()),
+        }
+    }
+}
+
 impl Display for HealthCheck {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -553,21 +2135,15 @@ impl Display for HealthCheck {
                 interval,
                 timeout,
                 start_period,
+                start_interval,
                 retries,
             } => {
                 write!(f, "HEALTHCHECK ")?;
-                if let Some(interval) = interval {
-                    write!(f, "--interval={} ", interval)?;
-                }
-                if let Some(timeout) = timeout {
-                    write!(f, "--timeout={} ", timeout)?;
-                }
-                if let Some(period) = start_period {
-                    write!(f, "--start-period={} ", period)?;
-                }
-                if let Some(retries) = retries {
-                    write!(f, "--retries={} ", retries)?;
-                }
+                flag(f, "interval", interval.map(duration))?;
+                flag(f, "timeout", timeout.map(duration))?;
+                flag(f, "start-period", start_period.map(duration))?;
+                flag(f, "start-interval", start_interval.map(duration))?;
+                flag(f, "retries", *retries)?;
                 write!(f, "{}", cmd)
             }
             HealthCheck::None => write!(f, "HEALTHCHECK NONE"),
@@ -576,9 +2152,9 @@ impl Display for HealthCheck {
 }
 
 impl Instruction for HealthCheck {}
-impl StorageInstruction for HealthCheck {}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Shell {
     pub params: Vec<String>,
 }
@@ -596,31 +2172,32 @@ where
 
 impl Display for Shell {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "SHELL [{}]",
-            self.params
-                .iter()
-                .map(|i| format!(r#""{}""#, i))
-                .collect::<Vec<String>>()
-                .join(", ")
-        )
+        write!(f, "SHELL {}", quote::exec_form(&self.params))
     }
 }
 
 impl Instruction for Shell {}
-impl StorageInstruction for Shell {}
 
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OnBuild {
-    inner: Box<Instruction>,
+    #[cfg_attr(feature = "serde", serde(rename = "trigger"))]
+    inner: Box<InstructionKind>,
+}
+
+impl OnBuild {
+    /// Trigger instruction
+    pub fn instruction(&self) -> &InstructionKind {
+        &self.inner
+    }
 }
 
 impl<I> StdFrom<I> for OnBuild
 where
-    I: Instruction + 'static,
+    I: Instruction + Into<InstructionKind>,
 {
     fn from(i: I) -> Self {
-        let inner = Box::new(i);
+        let inner = Box::new(i.into());
         OnBuild { inner }
     }
 }
@@ -631,6 +2208,104 @@ impl Display for OnBuild {
     }
 }
 
+/// Parser directive, written at the top of `Dockerfile` before any instruction or comment
+///
+/// # Example
+///
+/// ```
+/// use dockerfile_rs::Directive;
+///
+/// assert_eq!(Directive::syntax("docker/dockerfile:1").to_string(), "# syntax=docker/dockerfile:1");
+/// assert_eq!(Directive::Escape('`').to_string(), "# escape=`");
+/// let check = Directive::Check { skip: vec!["JSONArgsRecommended".into()], error: true };
+/// assert_eq!(check.to_string(), "# check=skip=JSONArgsRecommended;error=true");
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum Directive {
+    /// Image of `Dockerfile` frontend used by BuildKit
+    Syntax(String),
+    /// Character used for escaping and line continuations instead of `\`, only `\` and `` ` ``
+    /// are allowed
+    Escape(char),
+    /// Build checks of BuildKit, `skip` holds rule names or `all`, `error` fails build on warnings
+    Check { skip: Vec<String>, error: bool },
+}
+
+/// Names of directives that are recognized at the top of the file
+pub(crate) const DIRECTIVES: &[&str] = &["syntax", "escape", "check"];
+
+/// Splits text of comment into lowercase name and value if Docker reads it as a known directive
+pub(crate) fn split_directive(text: &str) -> Option<(String, &str)> {
+    let mut parts = text.splitn(2, '=');
+    let name = parts.next()?.trim();
+    let value = parts.next()?.trim();
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric());
+    let name = name.to_ascii_lowercase();
+    if valid && !value.is_empty() && DIRECTIVES.contains(&name.as_str()) {
+        Some((name, value))
+    } else {
+        None
+    }
+}
+
+impl Directive {
+    pub fn syntax<T: Into<String>>(image: T) -> Self {
+        Directive::Syntax(image.into())
+    }
+
+    /// Name of directive as it's written before `=`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Directive::Syntax(_) => "syntax",
+            Directive::Escape(_) => "escape",
+            Directive::Check { .. } => "check",
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let valid = match self {
+            Directive::Syntax(image) => !image.trim().is_empty() && !image.contains('\n'),
+            Directive::Escape(c) => *c == '\\' || *c == '`',
+            Directive::Check { skip, .. } => skip
+                .iter()
+                .all(|rule| !rule.is_empty() && rule.chars().all(|c| c.is_ascii_alphanumeric())),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(ValidationError::InvalidDirective(self.to_string()))
+        }
+    }
+}
+
+impl Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Directive::Syntax(image) => write!(f, "# syntax={}", image),
+            Directive::Escape(c) => write!(f, "# escape={}", c),
+            Directive::Check { skip, error } => {
+                let mut options = Vec::new();
+                if !skip.is_empty() {
+                    options.push(format!("skip={}", skip.join(",")));
+                }
+                if *error || options.is_empty() {
+                    options.push(format!("error={}", error));
+                }
+                write!(f, "# check={}", options.join(";"))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Comment {
     pub comment: String,
 }
@@ -646,6 +2321,18 @@ where
     }
 }
 
+impl Comment {
+    /// Rejects comments that Docker would read as [`Directive`] if they were first in the file
+    ///
+    /// [`Directive`]: enum.Directive.html
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match split_directive(&self.comment) {
+            Some(_) => Err(ValidationError::MisplacedDirective(self.comment.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
 impl Display for Comment {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "# {}", self.comment)
@@ -653,7 +2340,6 @@ impl Display for Comment {
 }
 
 impl Instruction for Comment {}
-impl StorageInstruction for Comment {}
 
 #[cfg(test)]
 mod tests {
@@ -671,6 +2357,7 @@ mod tests {
             image: image.clone(),
             tag_or_digest: tag.clone(),
             name: None,
+            platform: None,
         };
         assert_eq!(from.to_string(), "FROM rust:latest");
 
@@ -679,6 +2366,7 @@ mod tests {
             image: image.clone(),
             tag_or_digest: tag.clone(),
             name: name.clone(),
+            platform: None,
         };
         assert_eq!(from.to_string(), "FROM rust:latest AS crab");
 
@@ -687,6 +2375,7 @@ mod tests {
             image: image.clone(),
             tag_or_digest: digest.clone(),
             name: None,
+            platform: None,
         };
         assert_eq!(from.to_string(), "FROM rust@digest");
 
@@ -695,6 +2384,7 @@ mod tests {
             image: image.clone(),
             tag_or_digest: digest.clone(),
             name: name.clone(),
+            platform: None,
         };
         assert_eq!(from.to_string(), "FROM rust@digest AS crab");
 
@@ -703,6 +2393,7 @@ mod tests {
             image: image.clone(),
             tag_or_digest: None,
             name: None,
+            platform: None,
         };
         assert_eq!(from.to_string(), "FROM rust");
 
@@ -711,26 +2402,371 @@ mod tests {
             image: image.clone(),
             tag_or_digest: None,
             name: name.clone(),
+            platform: None,
         };
         assert_eq!(from.to_string(), "FROM rust AS crab");
+
+        // platform, tag and name
+        let from = From {
+            image: image.clone(),
+            tag_or_digest: tag.clone(),
+            name: name.clone(),
+            platform: Some(Platform::from("linux/amd64")),
+        };
+        assert_eq!(
+            from.to_string(),
+            "FROM --platform=linux/amd64 rust:latest AS crab"
+        );
+        assert_eq!(from.validate(), Ok(()));
+
+        let invalid = |from: From| from.validate().unwrap_err().to_string();
+        assert_eq!(
+            invalid(From {
+                image: "rust\nRUN evil".to_string(),
+                ..from.clone()
+            }),
+            "invalid image `rust\nRUN evil:latest`"
+        );
+        assert_eq!(
+            invalid(From {
+                image: String::new(),
+                ..from.clone()
+            }),
+            "invalid image `:latest`"
+        );
+        assert_eq!(
+            invalid(From {
+                tag_or_digest: Some(Tag("1 AS x".to_string())),
+                ..from.clone()
+            }),
+            "invalid image `rust:1 AS x`"
+        );
+        assert_eq!(
+            invalid(From {
+                tag_or_digest: Some(Digest(String::new())),
+                ..from.clone()
+            }),
+            "invalid image `rust@`"
+        );
+        assert_eq!(
+            invalid(From {
+                name: Some("crab\nRUN evil".to_string()),
+                ..from.clone()
+            }),
+            "invalid stage name `crab\nRUN evil`"
+        );
+        assert_eq!(
+            invalid(From {
+                name: Some("1crab".to_string()),
+                ..from.clone()
+            }),
+            "invalid stage name `1crab`"
+        );
+        assert_eq!(
+            invalid(From {
+                platform: Some(Platform::from("linux//v7")),
+                ..from
+            }),
+            "invalid platform `linux//v7`"
+        );
+    }
+
+    #[test]
+    fn platform() {
+        assert_eq!(
+            Platform::from("linux"),
+            Platform::Target {
+                os: "linux".to_string(),
+                architecture: None,
+                variant: None,
+            }
+        );
+        assert_eq!(Platform::from("linux/arm/v7").to_string(), "linux/arm/v7");
+        assert_eq!(
+            Platform::from("$TARGETPLATFORM").to_string(),
+            "$TARGETPLATFORM"
+        );
+        assert_eq!(
+            Platform::from("${TARGETPLATFORM:-linux/amd64}").to_string(),
+            "${TARGETPLATFORM:-linux/amd64}"
+        );
+        assert_eq!(Platform::from("linux/arm64/v8").validate(), Ok(()));
+        assert_eq!(Platform::from("$BUILDPLATFORM").validate(), Ok(()));
+        assert_eq!(
+            Platform::from("linux/arm/v7/extra").validate(),
+            Err(ValidationError::InvalidPlatform(
+                "linux/arm/v7/extra".to_string()
+            ))
+        );
+        assert_eq!(
+            Platform::from("linux//v7").validate(),
+            Err(ValidationError::InvalidPlatform("linux//v7".to_string()))
+        );
     }
 
     #[test]
     fn run() {
         let curl = vec!["curl", "-v", "https://rust-lang.org"];
         let run = Run::from(curl);
-        assert_eq!(run.params, ["curl", "-v", "https://rust-lang.org"]);
+        assert_eq!(
+            run.command,
+            CommandForm::Exec(vec![
+                "curl".to_string(),
+                "-v".to_string(),
+                "https://rust-lang.org".to_string()
+            ])
+        );
         assert_eq!(
             run.to_string(),
             r#"RUN ["curl", "-v", "https://rust-lang.org"]"#
         )
     }
 
+    #[test]
+    fn shell_form() {
+        let run = Run::from("apt-get update && \\\n    apt-get install -y curl");
+        assert_eq!(
+            run.command,
+            CommandForm::Shell("apt-get update && \\\n    apt-get install -y curl".to_string())
+        );
+        assert_eq!(
+            run.to_string(),
+            "RUN apt-get update && \\\n    apt-get install -y curl"
+        );
+
+        let run = Run::from("echo 1\necho 2".to_string());
+        assert_eq!(run.to_string(), "RUN echo 1\\\necho 2");
+
+        let cmd = Cmd::from("nginx -g 'daemon off;'");
+        assert_eq!(cmd.to_string(), "CMD nginx -g 'daemon off;'");
+
+        let point = EntryPoint::from("exec app");
+        assert_eq!(point.to_string(), "ENTRYPOINT exec app");
+
+        let check = HealthCheck::Check {
+            cmd: Cmd::from("curl -f http://localhost/ || exit 1"),
+            interval: None,
+            timeout: None,
+            start_period: None,
+            start_interval: None,
+            retries: None,
+        };
+        assert_eq!(
+            check.to_string(),
+            "HEALTHCHECK CMD curl -f http://localhost/ || exit 1"
+        );
+    }
+
+    #[test]
+    fn mount() {
+        let mount = Mount::Cache {
+            id: Some("cargo".to_string()),
+            target: "/usr/local/cargo/registry".to_string(),
+            source: None,
+            from: None,
+            sharing: Some(Sharing::Locked),
+            mode: Some(0o755),
+            uid: Some(1000),
+            gid: Some(1000),
+            readonly: true,
+        };
+        assert_eq!(
+            mount.to_string(),
+            "type=cache,id=cargo,target=/usr/local/cargo/registry,sharing=locked,\
+             mode=0755,uid=1000,gid=1000,readonly"
+        );
+
+        let mount = Mount::Bind {
+            target: "/src".to_string(),
+            source: Some("target,debug".to_string()),
+            from: Some("builder".to_string()),
+            readwrite: true,
+        };
+        assert_eq!(
+            mount.to_string(),
+            r#"type=bind,target=/src,"source=target,debug",from=builder,rw"#
+        );
+
+        let mount = Mount::Tmpfs {
+            target: "/tmp".to_string(),
+            size: Some(1024),
+        };
+        assert_eq!(mount.to_string(), "type=tmpfs,target=/tmp,size=1024");
+        assert_eq!(Mount::ssh().to_string(), "type=ssh");
+
+        let mut run = Run::from("cargo build");
+        run.mounts = vec![Mount::secret("token"), Mount::bind(".")];
+        assert_eq!(
+            run.to_string(),
+            "RUN --mount=type=secret,id=token --mount=type=bind,target=. cargo build"
+        );
+        assert_eq!(run.validate(), Ok(()));
+
+        run.mounts.push(Mount::Secret {
+            id: None,
+            target: None,
+            required: true,
+            mode: None,
+            uid: None,
+            gid: None,
+        });
+        let err = ValidationError::InvalidMount("type=secret,required".to_string());
+        assert_eq!(run.validate(), Err(err));
+    }
+
+    #[test]
+    fn run_flags() {
+        let mut run = Run::from("make");
+        run.mounts.push(Mount::tmpfs("/tmp"));
+        run.security = Some(Security::Insecure);
+        run.network = Some(Network::Host);
+        assert_eq!(
+            run.to_string(),
+            "RUN --mount=type=tmpfs,target=/tmp --network=host --security=insecure make"
+        );
+    }
+
+    #[test]
+    fn copy_flags() {
+        let copy = Copy {
+            from: Some("builder".to_string()),
+            chown: Some(User {
+                user: "app".to_string(),
+                group: None,
+            }),
+            chmod: Some(Chmod::from("u=rwx,go=rx")),
+            link: true,
+            parents: true,
+            exclude: vec!["**/*.rs".to_string()],
+            ..Copy::from(("/src/./app/", "/opt/"))
+        };
+        assert_eq!(
+            copy.to_string(),
+            "COPY --from=builder --chown=app --chmod=u=rwx,go=rx --link --parents \
+             --exclude=**/*.rs \"/src/./app/\" \"/opt/\""
+        );
+    }
+
+    #[test]
+    fn sources() {
+        let copy = Copy::from((vec!["Cargo.toml", "Cargo.lock"], "./"));
+        assert_eq!(copy.to_string(), r#"COPY "Cargo.toml" "Cargo.lock" "./""#);
+        assert_eq!(copy.validate(), Ok(()));
+
+        let copy = Copy::from((vec!["src/*.rs", "My Documents"], "/app"));
+        assert_eq!(
+            copy.to_string(),
+            r#"COPY ["src/*.rs", "My Documents", "/app"]"#
+        );
+        let err = ValidationError::InvalidDestination("/app".to_string());
+        assert_eq!(copy.validate(), Err(err));
+
+        let add = Add::from((
+            vec![Source::from("a b"), Heredoc::from("x").into()],
+            "/c d/",
+        ));
+        assert_eq!(add.to_string(), "ADD \"a b\" <<EOF \"/c d/\"\nx\nEOF");
+        assert_eq!(add.validate(), Ok(()));
+
+        let copy = Copy::from((
+            vec![
+                Source::from(r#"a"b"#),
+                Heredoc::from("x\n").into(),
+                r"c\".into(),
+            ],
+            "/$DIR/",
+        ));
+        assert_eq!(
+            copy.to_string(),
+            "COPY \"a\\\"b\" <<EOF \"c\\\\\" \"/$DIR/\"\nx\nEOF"
+        );
+        let docker_file = DockerFile::parse(&format!("FROM rust\n{}", copy)).unwrap();
+        assert_eq!(
+            docker_file.stages()[0].instructions(),
+            &[InstructionKind::from(copy)]
+        );
+
+        let add = Add::from((Vec::<String>::new(), "/"));
+        let err = ValidationError::MissingSource("ADD".to_string());
+        assert_eq!(add.validate(), Err(err));
+    }
+
+    #[test]
+    fn heredoc() {
+        let run = Run::from(Heredoc::from("set -e\necho done\n"));
+        assert_eq!(run.to_string(), "RUN <<EOF\nset -e\necho done\nEOF");
+
+        let run = Run::from(CommandForm::Heredoc {
+            command: Some("python3".to_string()),
+            heredoc: Heredoc::from("print(1)"),
+            suffix: None,
+        });
+        assert_eq!(run.to_string(), "RUN python3 <<EOF\nprint(1)\nEOF");
+
+        let run = Run::from(CommandForm::Heredoc {
+            command: Some("cat".to_string()),
+            heredoc: Heredoc::from("port = 80\n"),
+            suffix: Some("> /etc/app.conf".to_string()),
+        });
+        assert_eq!(
+            run.to_string(),
+            "RUN cat <<EOF > /etc/app.conf\nport = 80\nEOF"
+        );
+
+        let heredoc = Heredoc {
+            content: "cat <<EOF\nEOF\nEOF1\n".to_string(),
+            delimiter: "EOF".to_string(),
+            interpreter: Some("/bin/sh".to_string()),
+        };
+        assert_eq!(heredoc.unique_delimiter(), "EOF2");
+        assert_eq!(
+            Run::from(heredoc).to_string(),
+            "RUN <<EOF2\n#!/bin/sh\ncat <<EOF\nEOF\nEOF1\nEOF2"
+        );
+
+        let copy = Copy {
+            src: vec![Heredoc::from("").into()],
+            dst: "/app/.keep".to_string(),
+            from: None,
+            chown: Some(User {
+                user: "app".to_string(),
+                group: None,
+            }),
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
+        };
+        assert_eq!(
+            copy.to_string(),
+            "COPY --chown=app <<EOF \"/app/.keep\"\nEOF"
+        );
+
+        let heredoc = Heredoc {
+            delimiter: "END OF FILE".to_string(),
+            ..Heredoc::from("")
+        };
+        let err = ValidationError::InvalidDelimiter("END OF FILE".to_string());
+        assert_eq!(Run::from(heredoc.clone()).validate(), Err(err.clone()));
+        assert_eq!(Copy::from((heredoc, "/")).validate(), Err(err));
+
+        let cmd = Cmd::from(Heredoc::from("echo"));
+        let err = ValidationError::UnexpectedHeredoc("CMD".to_string());
+        assert_eq!(cmd.validate(), Err(err));
+    }
+
     #[test]
     fn cmd() {
         let curl = vec!["curl", "-v", "https://rust-lang.org"];
         let cmd = Cmd::from(curl);
-        assert_eq!(cmd.params, ["curl", "-v", "https://rust-lang.org"]);
+        assert_eq!(
+            cmd.command,
+            CommandForm::Exec(vec![
+                "curl".to_string(),
+                "-v".to_string(),
+                "https://rust-lang.org".to_string()
+            ])
+        );
         assert_eq!(
             cmd.to_string(),
             r#"CMD ["curl", "-v", "https://rust-lang.org"]"#
@@ -758,14 +2794,62 @@ mod tests {
         map.insert("key", "value");
         map.insert("hello", "world");
         let label = Label::from(map);
-        let label = label.to_string();
-        assert!(
-            label
-                == r#"LABEL hello="world" \
+        assert_eq!(
+            label.to_string(),
+            r#"LABEL hello="world" \
       key="value""#
-                || label
-                    == r#"LABEL key="value" \
-      hello="world""#
+        );
+
+        let mut label = Label::from(vec![("key", "value"), ("hello", "world")]);
+        label.insert("key", "new");
+        label.insert("a", "b");
+        assert_eq!(
+            label.to_string(),
+            r#"LABEL key="new" \
+      hello="world" \
+      a="b""#
+        );
+        assert_eq!(label.get("key"), Some(&Value::literal("new")));
+        assert_eq!(
+            label.sorted().iter().map(|(k, _)| k).collect::<Vec<_>>(),
+            ["a", "hello", "key"]
+        );
+    }
+
+    #[test]
+    fn values() {
+        // literal
+        let label = Label::from(("key", "\"quoted\" $HOME \\ 1\n2"));
+        assert_eq!(
+            label.to_string(),
+            "LABEL key=\"\\\"quoted\\\" \\$HOME \\\\ 1\\\n2\""
+        );
+
+        // expanding
+        let env = Env::from(("PATH", Value::expand(r#"$PATH:${HOME}/bin:\$5 "x""#)));
+        assert_eq!(env.to_string(), r#"ENV PATH="$PATH:${HOME}/bin:\$5 \"x\"""#);
+
+        let arg = Arg::from(("name", Value::expand("${HOME:-/root}")));
+        assert_eq!(arg.to_string(), r#"ARG name="${HOME:-/root}""#);
+
+        // keys
+        let label = Label::from(("with space", "value"));
+        assert_eq!(label.to_string(), r#"LABEL "with space"="value""#);
+        assert_eq!(label.validate(), Ok(()));
+        let label = Label::from(("a=b", "value"));
+        assert_eq!(
+            label.validate(),
+            Err(ValidationError::InvalidKey("a=b".to_string()))
+        );
+        let env = Env::from(("with space", "value"));
+        assert_eq!(
+            env.validate(),
+            Err(ValidationError::InvalidKey("with space".to_string()))
+        );
+        let arg = Arg::from(("", "value"));
+        assert_eq!(
+            arg.validate(),
+            Err(ValidationError::InvalidKey(String::new()))
         );
     }
 
@@ -779,16 +2863,43 @@ mod tests {
 
     #[test]
     fn expose() {
-        let port = 80;
-        let proto = Some(String::from("tcp"));
-
         // without proto
-        let expose = Expose { port, proto: None };
+        let expose = Expose::from(80);
         assert_eq!(expose.to_string(), "EXPOSE 80");
 
         // with proto
-        let expose = Expose { port, proto };
-        assert_eq!(expose.to_string(), "EXPOSE 80/tcp")
+        let expose = Expose::from((80, Protocol::Tcp));
+        assert_eq!(expose.to_string(), "EXPOSE 80/tcp");
+
+        // several ports and ranges
+        let expose = Expose::from(vec![
+            Port::from((8000..=8010, Protocol::Udp)),
+            Port::from((9090, Protocol::Sctp)),
+            Port::from(7000..=7000),
+        ]);
+        assert_eq!(
+            expose.to_string(),
+            "EXPOSE 8000-8010/udp 9090/sctp 7000-7000"
+        );
+        assert_eq!(expose.validate(), Ok(()));
+
+        assert_eq!(
+            Expose::from(0).validate(),
+            Err(ValidationError::InvalidPort("0".to_string()))
+        );
+        assert_eq!(
+            Expose::from(Port {
+                port: 8010,
+                end: Some(8000),
+                protocol: Some(Protocol::Tcp),
+            })
+            .validate(),
+            Err(ValidationError::InvalidPort("8010-8000/tcp".to_string()))
+        );
+        assert_eq!(
+            Expose::from(Vec::<Port>::new()).validate(),
+            Err(ValidationError::MissingPort)
+        );
     }
 
     #[test]
@@ -797,6 +2908,17 @@ mod tests {
         map.insert("key", "value");
         let label = Env::from(map.clone());
         assert_eq!(label.to_string(), r#"ENV key="value""#);
+
+        map.insert("A", "1");
+        map.insert("b", "2");
+        assert_eq!(Env::from(map).to_string(), r#"ENV A="1" b="2" key="value""#);
+
+        let mut map = BTreeMap::new();
+        map.insert("z", "1");
+        map.insert("y", "2");
+        let env = Env::from(map);
+        assert_eq!(env.to_string(), r#"ENV y="2" z="1""#);
+        assert_eq!(env.get("z"), Some(&Value::literal("1")));
     }
 
     #[test]
@@ -810,9 +2932,14 @@ mod tests {
 
         // with chown
         let add = Add {
-            src: src.clone(),
+            src: vec![src.clone().into()],
             dst: dst.clone(),
             chown: Some(chown),
+            chmod: None,
+            link: false,
+            checksum: None,
+            keep_git_dir: false,
+            exclude: Vec::new(),
         };
         assert_eq!(
             add.to_string(),
@@ -822,6 +2949,37 @@ mod tests {
         // without chown
         let add = Add::from((src.clone(), dst.clone()));
         assert_eq!(add.to_string(), r#"ADD "/home/container001" "/""#);
+
+        // with chmod, link and exclude
+        let add = Add {
+            chmod: Some(Chmod::from(0o640)),
+            link: true,
+            exclude: vec!["*.tmp".to_string(), "cache/".to_string()],
+            ..add
+        };
+        assert_eq!(
+            add.to_string(),
+            r#"ADD --chmod=0640 --link --exclude=*.tmp --exclude=cache/ "/home/container001" "/""#
+        );
+
+        // with checksum and keep-git-dir
+        let add = Add {
+            checksum: Some("sha256:24454f830c".to_string()),
+            keep_git_dir: true,
+            ..Add::from(("https://github.com/moby/buildkit.git", "/src"))
+        };
+        assert_eq!(
+            add.to_string(),
+            r#"ADD --checksum=sha256:24454f830c --keep-git-dir "https://github.com/moby/buildkit.git" "/src""#
+        );
+    }
+
+    #[test]
+    fn chmod() {
+        assert_eq!(Chmod::from("0755"), Chmod::Octal(0o755));
+        assert_eq!(Chmod::from("+x"), Chmod::Symbolic("+x".to_string()));
+        assert_eq!(Chmod::from("u+rw".to_string()).to_string(), "u+rw");
+        assert_eq!(Chmod::Octal(0o4755).to_string(), "04755");
     }
 
     #[test]
@@ -836,10 +2994,14 @@ mod tests {
 
         // with from and with chown
         let copy = Copy {
-            src: src.clone(),
+            src: vec![src.clone().into()],
             dst: dst.clone(),
             from: from.clone(),
             chown: chown.clone(),
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
         };
         assert_eq!(
             copy.to_string(),
@@ -848,10 +3010,14 @@ mod tests {
 
         // with from
         let copy = Copy {
-            src: src.clone(),
+            src: vec![src.clone().into()],
             dst: dst.clone(),
             from: from.clone(),
             chown: None,
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
         };
         assert_eq!(
             copy.to_string(),
@@ -860,10 +3026,14 @@ mod tests {
 
         // with chown
         let copy = Copy {
-            src: src.clone(),
+            src: vec![src.clone().into()],
             dst: dst.clone(),
             from: None,
             chown: chown.clone(),
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
         };
         assert_eq!(
             copy.to_string(),
@@ -875,11 +3045,39 @@ mod tests {
         assert_eq!(copy.to_string(), r#"COPY "/home/container001" "/""#);
     }
 
+    #[test]
+    fn exec_form_escaping() {
+        let run = Run::from(vec!["sh", "-c", r#"echo "hi""#]);
+        assert_eq!(run.to_string(), r#"RUN ["sh", "-c", "echo \"hi\""]"#);
+
+        let cmd = Cmd::from(vec![r"C:\app\run.exe", "line\nbreak"]);
+        assert_eq!(
+            cmd.to_string(),
+            r#"CMD ["C:\\app\\run.exe", "line\nbreak"]"#
+        );
+
+        let point = EntryPoint::from(vec!["printf", "\u{1b}[1m"]);
+        assert_eq!(point.to_string(), r#"ENTRYPOINT ["printf", "\u001b[1m"]"#);
+
+        let volume = Volume::from(vec![r"C:\data"]);
+        assert_eq!(volume.to_string(), r#"VOLUME ["C:\\data"]"#);
+
+        let shell = Shell::from(vec!["powershell", "-command"]);
+        assert_eq!(shell.to_string(), r#"SHELL ["powershell", "-command"]"#);
+    }
+
     #[test]
     fn entrypoint() {
         let curl = vec!["curl", "-v", "https://rust-lang.org"];
         let point = EntryPoint::from(curl);
-        assert_eq!(point.params, ["curl", "-v", "https://rust-lang.org"]);
+        assert_eq!(
+            point.command,
+            CommandForm::Exec(vec![
+                "curl".to_string(),
+                "-v".to_string(),
+                "https://rust-lang.org".to_string()
+            ])
+        );
         assert_eq!(
             point.to_string(),
             r#"ENTRYPOINT ["curl", "-v", "https://rust-lang.org"]"#
@@ -920,7 +3118,7 @@ mod tests {
     #[test]
     fn arg() {
         let name = "name".to_string();
-        let value = Some("value".to_string());
+        let value = Some(Value::from("value"));
 
         // with value
         let arg = Arg {
@@ -928,6 +3126,7 @@ mod tests {
             value,
         };
         assert_eq!(arg.to_string(), r#"ARG name="value""#);
+        assert_eq!(arg.validate(), Ok(()));
 
         // without value
         let arg = Arg { name, value: None };
@@ -948,12 +3147,30 @@ mod tests {
         let cmd = Cmd::from(curl);
         let check = HealthCheck::Check {
             cmd,
-            interval: Some(0),
-            timeout: Some(3600),
-            start_period: Some(123),
+            interval: Some(Duration::from_secs(0)),
+            timeout: Some(Duration::from_secs(3600)),
+            start_period: Some(Duration::from_secs(123)),
+            start_interval: Some(Duration::new(5, 250_000_001)),
             retries: Some(2),
         };
-        assert_eq!(check.to_string(), r#"HEALTHCHECK --interval=0 --timeout=3600 --start-period=123 --retries=2 CMD ["curl", "-v", "https://rust-lang.org"]"#);
+        assert_eq!(
+            check.to_string(),
+            r#"HEALTHCHECK --interval=0s --timeout=1h --start-period=2m3s --start-interval=5s250ms1ns --retries=2 CMD ["curl", "-v", "https://rust-lang.org"]"#
+        );
+        assert_eq!(check.validate(), Ok(()));
+
+        let check = match check {
+            HealthCheck::Check { cmd, .. } => HealthCheck::Check {
+                cmd,
+                interval: None,
+                timeout: None,
+                start_period: None,
+                start_interval: None,
+                retries: Some(-1),
+            },
+            HealthCheck::None => unreachable!(),
+        };
+        assert_eq!(check.validate(), Err(ValidationError::NegativeRetries(-1)));
 
         // without params
         let check = HealthCheck::None;
@@ -984,5 +3201,37 @@ mod tests {
         let comment = "This is an example comment";
         let comment = Comment::from(comment);
         assert_eq!(comment.to_string(), "# This is an example comment");
+        assert_eq!(comment.validate(), Ok(()));
+        assert_eq!(Comment::from("a = b").validate(), Ok(()));
+        assert_eq!(
+            Comment::from(" Syntax = docker/dockerfile:1").validate(),
+            Err(ValidationError::MisplacedDirective(
+                " Syntax = docker/dockerfile:1".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn directive() {
+        let check = Directive::Check {
+            skip: vec![],
+            error: false,
+        };
+        assert_eq!(check.to_string(), "# check=error=false");
+        let check = Directive::Check {
+            skip: vec!["all".into()],
+            error: false,
+        };
+        assert_eq!(check.to_string(), "# check=skip=all");
+        assert_eq!(check.validate(), Ok(()));
+
+        assert_eq!(
+            Directive::Escape('/').validate(),
+            Err(ValidationError::InvalidDirective("# escape=/".to_string()))
+        );
+        assert_eq!(
+            Directive::syntax("").validate(),
+            Err(ValidationError::InvalidDirective("# syntax=".to_string()))
+        );
     }
 }
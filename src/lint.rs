@@ -0,0 +1,697 @@
+//! Checks of [`DockerFile`] for mistakes that `docker build` doesn't report or reports late
+//!
+//! Every [`Rule`] has an id used in [`LintConfig`] and in `# lint-ignore: rule-a, rule-b`
+//! comments, the comment disables listed rules for the next instruction.
+//! # Example
+//! ```rust
+//! use dockerfile_rs::{lint::LintConfig, DockerFile, FROM};
+//!
+//! let docker_file = DockerFile::from(FROM!(rust:latest))
+//!     .maintainer("Rustacean")
+//!     .comment("lint-ignore: relative-workdir")
+//!     .work_dir("app");
+//!
+//! let rules: Vec<_> = docker_file
+//!     .lint(&LintConfig::default())
+//!     .into_iter()
+//!     .map(|diagnostic| diagnostic.rule)
+//!     .collect();
+//! assert_eq!(rules, ["latest-tag", "deprecated-maintainer"]);
+//!
+//! let config = LintConfig::default().disable("latest-tag");
+//! assert_eq!(docker_file.lint(&config).len(), 1);
+//! ```
+//!
+//! [`DockerFile`]: ../struct.DockerFile.html
+//! [`Rule`]: trait.Rule.html
+//! [`LintConfig`]: struct.LintConfig.html
+
+use crate::{DockerFile, InstructionKind, Tag};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+};
+
+/// Prefix of comments that disable rules for the next instruction
+const IGNORE: &str = "lint-ignore:";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Position of instruction in [`DockerFile`]
+///
+/// [`DockerFile`]: ../struct.DockerFile.html
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Location {
+    /// Index of stage, `None` for instructions before the first `FROM`
+    pub stage: Option<usize>,
+    /// Index in instructions of stage or in global instructions, `None` for `FROM` of stage
+    pub index: Option<usize>,
+}
+
+impl Location {
+    pub fn global(index: usize) -> Self {
+        Location {
+            stage: None,
+            index: Some(index),
+        }
+    }
+
+    /// `FROM` of stage
+    pub fn base(stage: usize) -> Self {
+        Location {
+            stage: Some(stage),
+            index: None,
+        }
+    }
+
+    pub fn instruction(stage: usize, index: usize) -> Self {
+        Location {
+            stage: Some(stage),
+            index: Some(index),
+        }
+    }
+
+    /// Instruction at this location, `FROM` is returned as `InstructionKind::From`
+    pub fn find(&self, docker_file: &DockerFile) -> Option<InstructionKind> {
+        match (self.stage, self.index) {
+            (None, Some(index)) => docker_file.global().get(index).cloned(),
+            (Some(stage), None) => docker_file
+                .stages()
+                .get(stage)
+                .map(|stage| InstructionKind::From(stage.base().clone())),
+            (Some(stage), Some(index)) => docker_file
+                .stages()
+                .get(stage)
+                .and_then(|stage| stage.instructions().get(index).cloned()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Problem found by [`Rule`]
+///
+/// [`Rule`]: trait.Rule.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Violation {
+    pub location: Location,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new<T: Into<String>>(location: Location, message: T) -> Self {
+        Violation {
+            location,
+            message: message.into(),
+        }
+    }
+}
+
+/// Reported [`Violation`] with id and severity of its rule
+///
+/// [`Violation`]: struct.Violation.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub location: Location,
+    pub instruction: InstructionKind,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+    }
+}
+
+/// Check over the whole [`DockerFile`]
+///
+/// [`DockerFile`]: ../struct.DockerFile.html
+pub trait Rule {
+    /// Unique id in kebab case, used in [`LintConfig`] and `# lint-ignore:` comments
+    ///
+    /// [`LintConfig`]: struct.LintConfig.html
+    fn id(&self) -> &'static str;
+
+    /// One sentence about what the rule checks
+    fn description(&self) -> &'static str;
+
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation>;
+}
+
+/// Built-in rules in the order they are run
+pub fn rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(DuplicateCmd),
+        Box::new(DuplicateEntryPoint),
+        Box::new(DuplicateHealthCheck),
+        Box::new(UntaggedImage),
+        Box::new(LatestTag),
+        Box::new(DeprecatedMaintainer),
+        Box::new(RelativeWorkDir),
+    ]
+}
+
+/// Rules to run and their severities, built-in rules are enabled by default
+/// # Example
+/// ```rust
+/// use dockerfile_rs::lint::{LintConfig, Severity};
+///
+/// let config = LintConfig::default()
+///     .disable("deprecated-maintainer")
+///     .severity("latest-tag", Severity::Error);
+/// assert!(!config.is_enabled("deprecated-maintainer"));
+/// ```
+pub struct LintConfig {
+    rules: Vec<Box<dyn Rule>>,
+    disabled: HashSet<String>,
+    severities: HashMap<String, Severity>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            rules: rules(),
+            disabled: HashSet::new(),
+            severities: HashMap::new(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Config without any rules, use [`rule`] to add them
+    ///
+    /// [`rule`]: struct.LintConfig.html#method.rule
+    pub fn empty() -> Self {
+        LintConfig {
+            rules: Vec::new(),
+            ..LintConfig::default()
+        }
+    }
+
+    /// Adds custom rule, it's run after the ones added before
+    pub fn rule<T: Rule + 'static>(mut self, rule: T) -> Self {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn disable<T: Into<String>>(mut self, id: T) -> Self {
+        self.disabled.insert(id.into());
+        self
+    }
+
+    pub fn enable(mut self, id: &str) -> Self {
+        self.disabled.remove(id);
+        self
+    }
+
+    /// Overrides severity of rule
+    pub fn severity<T: Into<String>>(mut self, id: T, severity: Severity) -> Self {
+        self.severities.insert(id.into(), severity);
+        self
+    }
+
+    pub fn rules(&self) -> &[Box<dyn Rule>] {
+        &self.rules
+    }
+
+    pub fn is_enabled(&self, id: &str) -> bool {
+        !self.disabled.contains(id) && self.rules.iter().any(|rule| rule.id() == id)
+    }
+}
+
+/// Runs enabled rules, diagnostics are sorted by location
+pub(crate) fn lint(docker_file: &DockerFile, config: &LintConfig) -> Vec<Diagnostic> {
+    let ignored = ignored(docker_file);
+    let mut diagnostics = Vec::new();
+
+    for rule in config.rules.iter() {
+        if config.disabled.contains(rule.id()) {
+            continue;
+        }
+        let severity = config
+            .severities
+            .get(rule.id())
+            .copied()
+            .unwrap_or_else(|| rule.severity());
+        for violation in rule.check(docker_file) {
+            let is_ignored = ignored
+                .get(&violation.location)
+                .is_some_and(|ids| ids.contains(rule.id()));
+            if is_ignored {
+                continue;
+            }
+            if let Some(instruction) = violation.location.find(docker_file) {
+                diagnostics.push(Diagnostic {
+                    rule: rule.id(),
+                    severity,
+                    message: violation.message,
+                    location: violation.location,
+                    instruction,
+                });
+            }
+        }
+    }
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.location);
+    diagnostics
+}
+
+/// Rules disabled by `# lint-ignore:` comments for the next instruction
+fn ignored(docker_file: &DockerFile) -> HashMap<Location, HashSet<String>> {
+    let global = docker_file
+        .global()
+        .iter()
+        .enumerate()
+        .map(|(i, instruction)| (Location::global(i), Some(instruction)));
+    let stages = docker_file
+        .stages()
+        .iter()
+        .enumerate()
+        .flat_map(|(s, stage)| {
+            let instructions = stage
+                .instructions()
+                .iter()
+                .enumerate()
+                .map(move |(i, instruction)| (Location::instruction(s, i), Some(instruction)));
+            Some((Location::base(s), None))
+                .into_iter()
+                .chain(instructions)
+        });
+
+    let mut ignored = HashMap::new();
+    let mut pending = HashSet::new();
+    for (location, instruction) in global.chain(stages) {
+        match instruction {
+            Some(InstructionKind::Comment(comment)) => {
+                if let Some(ids) = comment.comment.trim().strip_prefix(IGNORE) {
+                    let ids = ids.split(',').map(str::trim).filter(|id| !id.is_empty());
+                    pending.extend(ids.map(String::from));
+                }
+            }
+            _ if !pending.is_empty() => {
+                ignored.insert(location, std::mem::take(&mut pending));
+            }
+            _ => {}
+        }
+    }
+    ignored
+}
+
+/// Reports every instruction of stage matched by `is_match` except the last one
+fn overridden<F>(docker_file: &DockerFile, is_match: F, message: &str) -> Vec<Violation>
+where
+    F: Fn(&InstructionKind) -> bool,
+{
+    let mut violations = Vec::new();
+    for (s, stage) in docker_file.stages().iter().enumerate() {
+        let mut matched: Vec<usize> = stage
+            .instructions()
+            .iter()
+            .enumerate()
+            .filter(|(_, instruction)| is_match(instruction))
+            .map(|(i, _)| i)
+            .collect();
+        matched.pop();
+        violations.extend(
+            matched
+                .into_iter()
+                .map(|i| Violation::new(Location::instruction(s, i), message)),
+        );
+    }
+    violations
+}
+
+/// Base images that are not pulled from registry, stages referenced by name and variables
+fn is_local_image(docker_file: &DockerFile, stage: usize, image: &str) -> bool {
+    image == "scratch"
+        || image.contains('$')
+        || docker_file.stages()[..stage]
+            .iter()
+            .filter_map(|stage| stage.name())
+            .any(|name| name.eq_ignore_ascii_case(image))
+}
+
+/// Only the last `CMD` of stage takes effect
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateCmd;
+
+impl Rule for DuplicateCmd {
+    fn id(&self) -> &'static str {
+        "duplicate-cmd"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stage has several `CMD` instructions, only the last one takes effect"
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+        let is_cmd = |i: &InstructionKind| matches!(i, InstructionKind::Cmd(_));
+        overridden(docker_file, is_cmd, "`CMD` is overridden by a later `CMD`")
+    }
+}
+
+/// Only the last `ENTRYPOINT` of stage takes effect
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateEntryPoint;
+
+impl Rule for DuplicateEntryPoint {
+    fn id(&self) -> &'static str {
+        "duplicate-entrypoint"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stage has several `ENTRYPOINT` instructions, only the last one takes effect"
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+        let is_entry_point = |i: &InstructionKind| matches!(i, InstructionKind::EntryPoint(_));
+        let message = "`ENTRYPOINT` is overridden by a later `ENTRYPOINT`";
+        overridden(docker_file, is_entry_point, message)
+    }
+}
+
+/// Only the last `HEALTHCHECK` of stage takes effect
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateHealthCheck;
+
+impl Rule for DuplicateHealthCheck {
+    fn id(&self) -> &'static str {
+        "duplicate-healthcheck"
+    }
+
+    fn description(&self) -> &'static str {
+        "Stage has several `HEALTHCHECK` instructions, only the last one takes effect"
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+        let is_health_check = |i: &InstructionKind| matches!(i, InstructionKind::HealthCheck(_));
+        let message = "`HEALTHCHECK` is overridden by a later `HEALTHCHECK`";
+        overridden(docker_file, is_health_check, message)
+    }
+}
+
+/// Base image without tag or digest is resolved to `latest`
+#[derive(Debug, Clone, Copy)]
+pub struct UntaggedImage;
+
+impl Rule for UntaggedImage {
+    fn id(&self) -> &'static str {
+        "untagged-image"
+    }
+
+    fn description(&self) -> &'static str {
+        "Base image has no tag or digest, so build uses whatever `latest` points to"
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+        docker_file
+            .stages()
+            .iter()
+            .enumerate()
+            .filter(|(s, stage)| {
+                let from = stage.base();
+                from.tag_or_digest.is_none() && !is_local_image(docker_file, *s, &from.image)
+            })
+            .map(|(s, stage)| {
+                let message = format!("base image `{}` has no tag", stage.base().image);
+                Violation::new(Location::base(s), message)
+            })
+            .collect()
+    }
+}
+
+/// `latest` tag changes with every release of base image
+#[derive(Debug, Clone, Copy)]
+pub struct LatestTag;
+
+impl Rule for LatestTag {
+    fn id(&self) -> &'static str {
+        "latest-tag"
+    }
+
+    fn description(&self) -> &'static str {
+        "Base image uses `latest` tag, so builds are not reproducible"
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+        docker_file
+            .stages()
+            .iter()
+            .enumerate()
+            .filter(|(_, stage)| match &stage.base().tag_or_digest {
+                Some(Tag(tag)) => tag == "latest",
+                _ => false,
+            })
+            .map(|(s, stage)| {
+                let message = format!("base image `{}` uses `latest` tag", stage.base().image);
+                Violation::new(Location::base(s), message)
+            })
+            .collect()
+    }
+}
+
+/// `MAINTAINER` is deprecated in favor of `LABEL maintainer=...`
+#[derive(Debug, Clone, Copy)]
+pub struct DeprecatedMaintainer;
+
+impl Rule for DeprecatedMaintainer {
+    fn id(&self) -> &'static str {
+        "deprecated-maintainer"
+    }
+
+    fn description(&self) -> &'static str {
+        "`MAINTAINER` is deprecated, `LABEL maintainer=...` should be used instead"
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for (s, stage) in docker_file.stages().iter().enumerate() {
+            for (i, instruction) in stage.instructions().iter().enumerate() {
+                if let InstructionKind::Maintainer(maintainer) = instruction {
+                    let message = format!(
+                        "`MAINTAINER` is deprecated, use `LABEL maintainer=\"{}\"`",
+                        maintainer.name
+                    );
+                    violations.push(Violation::new(Location::instruction(s, i), message));
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// Relative `WORKDIR` depends on the working directory of base image
+#[derive(Debug, Clone, Copy)]
+pub struct RelativeWorkDir;
+
+impl Rule for RelativeWorkDir {
+    fn id(&self) -> &'static str {
+        "relative-workdir"
+    }
+
+    fn description(&self) -> &'static str {
+        "`WORKDIR` is relative, so it depends on the working directory of base image"
+    }
+
+    fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+        let is_absolute = |path: &str| {
+            let mut chars = path.chars();
+            path.starts_with('/')
+                || path.starts_with('$')
+                || (chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                    && chars.next() == Some(':'))
+        };
+
+        let mut violations = Vec::new();
+        for (s, stage) in docker_file.stages().iter().enumerate() {
+            for (i, instruction) in stage.instructions().iter().enumerate() {
+                match instruction {
+                    InstructionKind::WorkDir(work_dir) if !is_absolute(&work_dir.path) => {
+                        let message = format!("`WORKDIR {}` is relative", work_dir.path);
+                        violations.push(Violation::new(Location::instruction(s, i), message));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        violations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cmd, From, FROM};
+
+    fn rules(docker_file: &DockerFile, config: &LintConfig) -> Vec<(&'static str, Location)> {
+        docker_file
+            .lint(config)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.rule, diagnostic.location))
+            .collect()
+    }
+
+    #[test]
+    fn builtin() {
+        let content = r#"FROM rust AS builder
+WORKDIR src
+RUN cargo build
+CMD ["a"]
+HEALTHCHECK NONE
+CMD ["b"]
+ENTRYPOINT ["c"]
+HEALTHCHECK CMD true
+ENTRYPOINT ["d"]
+
+FROM builder
+MAINTAINER Rustacean
+WORKDIR /app
+WORKDIR C:\app
+WORKDIR $HOME
+
+FROM debian:latest
+CMD ["e"]
+"#;
+        let docker_file = DockerFile::parse(content).unwrap();
+        assert_eq!(
+            rules(&docker_file, &LintConfig::default()),
+            [
+                ("untagged-image", Location::base(0)),
+                ("relative-workdir", Location::instruction(0, 0)),
+                ("duplicate-cmd", Location::instruction(0, 2)),
+                ("duplicate-healthcheck", Location::instruction(0, 3)),
+                ("duplicate-entrypoint", Location::instruction(0, 5)),
+                ("deprecated-maintainer", Location::instruction(1, 0)),
+                ("latest-tag", Location::base(2)),
+            ]
+        );
+
+        let diagnostics = docker_file.lint(&LintConfig::default());
+        assert_eq!(
+            diagnostics[2].instruction,
+            InstructionKind::Cmd(Cmd::from(vec!["a"]))
+        );
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "warning[untagged-image]: base image `rust` has no tag"
+        );
+        assert_eq!(
+            diagnostics[6].instruction,
+            InstructionKind::From(docker_file.stages()[2].base().clone())
+        );
+    }
+
+    #[test]
+    fn config() {
+        let docker_file = DockerFile::from(FROM!(rust)).maintainer("Rustacean");
+        let config = LintConfig::default()
+            .disable("untagged-image")
+            .severity("deprecated-maintainer", Severity::Error);
+        let diagnostics = docker_file.lint(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert!(!config.is_enabled("untagged-image"));
+        let config = config.enable("untagged-image");
+        assert!(config.is_enabled("untagged-image"));
+
+        struct NoRun;
+
+        impl Rule for NoRun {
+            fn id(&self) -> &'static str {
+                "no-run"
+            }
+
+            fn description(&self) -> &'static str {
+                "Stage has `RUN`"
+            }
+
+            fn severity(&self) -> Severity {
+                Severity::Info
+            }
+
+            fn check(&self, docker_file: &DockerFile) -> Vec<Violation> {
+                docker_file.stages()[0]
+                    .instructions()
+                    .iter()
+                    .position(|i| matches!(i, InstructionKind::Run(_)))
+                    .map(|i| Violation::new(Location::instruction(0, i), "`RUN` is not allowed"))
+                    .into_iter()
+                    .collect()
+            }
+        }
+
+        let docker_file = docker_file.run("make");
+        let config = LintConfig::empty().rule(NoRun);
+        let diagnostics = docker_file.lint(&config);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "info[no-run]: `RUN` is not allowed"
+        );
+    }
+
+    #[test]
+    fn ignore() {
+        let content = r#"# lint-ignore: untagged-image
+ARG VERSION
+
+# lint-ignore: untagged-image
+FROM rust
+# lint-ignore: deprecated-maintainer, relative-workdir
+# another comment
+MAINTAINER Rustacean
+WORKDIR app
+# lint-ignore: latest-tag
+FROM debian:latest
+"#;
+        let docker_file = DockerFile::parse(content).unwrap();
+        assert_eq!(
+            rules(&docker_file, &LintConfig::default()),
+            [("relative-workdir", Location::instruction(0, 3))]
+        );
+
+        let docker_file = DockerFile::from(From {
+            image: "rust".to_string(),
+            tag_or_digest: None,
+            name: None,
+            platform: None,
+        });
+        assert_eq!(
+            rules(&docker_file, &LintConfig::default()),
+            [("untagged-image", Location::base(0))]
+        );
+
+        // `Display` moves `CMD` to the end of stage, comment must stay above it
+        let docker_file =
+            DockerFile::parse("FROM rust:1.75\n# lint-ignore: duplicate-cmd\nCMD a\nRUN x\nCMD b")
+                .unwrap();
+        assert_eq!(rules(&docker_file, &LintConfig::default()), []);
+        let rendered = docker_file.to_string();
+        assert_eq!(
+            rendered,
+            "FROM rust:1.75\n\nRUN x\n\n# lint-ignore: duplicate-cmd\nCMD a\nCMD b\n"
+        );
+        let docker_file = DockerFile::parse(&rendered).unwrap();
+        assert_eq!(rules(&docker_file, &LintConfig::default()), []);
+    }
+}
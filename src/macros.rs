@@ -4,15 +4,24 @@
 /// # use dockerfile_rs::FROM;
 /// let from = FROM!(rust:latest);
 /// assert_eq!(from.to_string(), "FROM rust:latest");
+///
+/// let from = FROM!(--platform="$BUILDPLATFORM" rust AS builder);
+/// assert_eq!(from.to_string(), "FROM --platform=$BUILDPLATFORM rust AS builder");
 /// ```
 #[macro_export]
 macro_rules! FROM {
+    (--platform=$platform:literal $($rest:tt)+) => {{
+        let mut from = $crate::FROM!($($rest)+);
+        from.platform = Some($crate::Platform::from($platform));
+        from
+    }};
     ($image:ident) => {{
         use $crate::From;
         From {
             image: stringify!($image).to_string(),
             tag_or_digest: None,
             name: None,
+            platform: None,
         }
     }};
     ($image:ident AS $name:ident) => {{
@@ -21,6 +30,7 @@ macro_rules! FROM {
             image: stringify!($image).to_string(),
             tag_or_digest: None,
             name: Some(stringify!($name).to_string()),
+            platform: None,
         }
     }};
     ($image:ident:$tag:ident) => {{
@@ -29,6 +39,7 @@ macro_rules! FROM {
             image: stringify!($image).to_string(),
             tag_or_digest: Some(Tag(stringify!($tag).to_string())),
             name: None,
+            platform: None,
         }
     }};
     ($image:ident:$tag:ident AS $name:ident) => {{
@@ -37,6 +48,7 @@ macro_rules! FROM {
             image: stringify!($image).to_string(),
             tag_or_digest: Some(Tag(stringify!($tag).to_string())),
             name: Some(stringify!($name).to_string()),
+            platform: None,
         }
     }};
     ($image:ident@$digest:ident) => {{
@@ -45,6 +57,7 @@ macro_rules! FROM {
             image: stringify!($image).to_string(),
             tag_or_digest: Some(Digest(stringify!($digest).to_string())),
             name: None,
+            platform: None,
         }
     }};
     ($image:ident@$digest:ident AS $name:ident) => {{
@@ -53,6 +66,7 @@ macro_rules! FROM {
             image: stringify!($image).to_string(),
             tag_or_digest: Some(Digest(stringify!($digest).to_string())),
             name: Some(stringify!($name).to_string()),
+            platform: None,
         }
     }};
 }
@@ -61,9 +75,59 @@ macro_rules! FROM {
 /// # use dockerfile_rs::RUN;
 /// let run = RUN!["echo", "Hello, world!"];
 /// assert_eq!(run.to_string(), r#"RUN ["echo", "Hello, world!"]"#);
+///
+/// let run = RUN!(SHELL "apt-get update && apt-get install -y curl");
+/// assert_eq!(run.to_string(), "RUN apt-get update && apt-get install -y curl");
+///
+/// # use dockerfile_rs::Mount;
+/// let run = RUN!(--mount=Mount::cache("/root/.cargo/registry"), SHELL "cargo build");
+/// assert_eq!(
+///     run.to_string(),
+///     "RUN --mount=type=cache,target=/root/.cargo/registry cargo build"
+/// );
+///
+/// let run = RUN!(--network=none, --security=sandbox, "cargo", "test", "--offline");
+/// assert_eq!(
+///     run.to_string(),
+///     r#"RUN --network=none --security=sandbox ["cargo", "test", "--offline"]"#
+/// );
 /// ```
 #[macro_export]
 macro_rules! RUN {
+    (--mount=$mount:expr, $($rest:tt)+) => {{
+        let mut run = $crate::RUN!($($rest)+);
+        run.mounts.insert(0, $mount);
+        run
+    }};
+    (--network=default, $($rest:tt)+) => {{
+        let mut run = $crate::RUN!($($rest)+);
+        run.network = Some($crate::Network::Default);
+        run
+    }};
+    (--network=none, $($rest:tt)+) => {{
+        let mut run = $crate::RUN!($($rest)+);
+        run.network = Some($crate::Network::None);
+        run
+    }};
+    (--network=host, $($rest:tt)+) => {{
+        let mut run = $crate::RUN!($($rest)+);
+        run.network = Some($crate::Network::Host);
+        run
+    }};
+    (--security=sandbox, $($rest:tt)+) => {{
+        let mut run = $crate::RUN!($($rest)+);
+        run.security = Some($crate::Security::Sandbox);
+        run
+    }};
+    (--security=insecure, $($rest:tt)+) => {{
+        let mut run = $crate::RUN!($($rest)+);
+        run.security = Some($crate::Security::Insecure);
+        run
+    }};
+    (SHELL $cmd:expr) => {{
+        use $crate::{CommandForm, Run};
+        Run::from(CommandForm::Shell($cmd.into()))
+    }};
     ($($x:expr), +) => {{
         use $crate::Run;
         Run::from(vec![$($x), +])
@@ -74,9 +138,16 @@ macro_rules! RUN {
 /// # use dockerfile_rs::CMD;
 /// let cmd = CMD!["echo", "Hello, world!"];
 /// assert_eq!(cmd.to_string(), r#"CMD ["echo", "Hello, world!"]"#);
+///
+/// let cmd = CMD!(SHELL "echo $HOME");
+/// assert_eq!(cmd.to_string(), "CMD echo $HOME");
 /// ```
 #[macro_export]
 macro_rules! CMD {
+    (SHELL $cmd:expr) => {{
+        use $crate::{CommandForm, Cmd};
+        Cmd::from(CommandForm::Shell($cmd.into()))
+    }};
     ($($x:expr), +) => {{
         use $crate::Cmd;
         Cmd::from(vec![$($x), +])
@@ -87,17 +158,19 @@ macro_rules! CMD {
 /// # use dockerfile_rs::LABEL;
 /// let label = LABEL!["key" => "value"];
 /// assert_eq!(label.to_string(), r#"LABEL key="value""#);
+///
+/// let label = LABEL!["version" => "1.0", "description" => "app"];
+/// assert_eq!(label.to_string(), "LABEL version=\"1.0\" \\\n      description=\"app\"");
 /// ```
 #[macro_export]
 macro_rules! LABEL {
     ($($x:expr => $y:expr), +) => {{
-        use $crate::Label;
-        use std::collections::HashMap;
-        let mut map = HashMap::new();
+        use $crate::{Label, Value};
+        let mut label = Label::from(Vec::<(String, Value)>::new());
         $(
-            map.insert($x, $y);
+            label.insert($x, $y);
         )+
-        Label::from(map)
+        label
     }};
 }
 
@@ -122,15 +195,46 @@ macro_rules! MAINTAINER {
 /// # use dockerfile_rs::EXPOSE;
 /// let expose = EXPOSE!(5757/udp);
 /// assert_eq!(expose.to_string(), "EXPOSE 5757/udp");
+///
+/// let expose = EXPOSE!(8000-8010/udp 9090/tcp 80);
+/// assert_eq!(expose.to_string(), "EXPOSE 8000-8010/udp 9090/tcp 80");
 /// ```
 #[macro_export]
 macro_rules! EXPOSE {
-    ($port:tt/$proto:ident) => {{
+    (@protocol tcp) => {
+        $crate::Protocol::Tcp
+    };
+    (@protocol udp) => {
+        $crate::Protocol::Udp
+    };
+    (@protocol sctp) => {
+        $crate::Protocol::Sctp
+    };
+    (@ports $expose:ident) => {};
+    (@ports $expose:ident $port:literal - $end:literal / $proto:ident $($rest:tt)*) => {
+        $expose.ports.push($crate::Port::from(($port..=$end, $crate::EXPOSE!(@protocol $proto))).into());
+        $crate::EXPOSE!(@ports $expose $($rest)*);
+    };
+    (@ports $expose:ident $port:literal - $end:literal $($rest:tt)*) => {
+        $expose.ports.push($crate::Port::from($port..=$end).into());
+        $crate::EXPOSE!(@ports $expose $($rest)*);
+    };
+    (@ports $expose:ident $port:literal / $proto:ident $($rest:tt)*) => {
+        $expose.ports.push($crate::Port::from(($port, $crate::EXPOSE!(@protocol $proto))).into());
+        $crate::EXPOSE!(@ports $expose $($rest)*);
+    };
+    (@ports $expose:ident $port:literal $($rest:tt)*) => {
+        $expose.ports.push($crate::Port::from($port).into());
+        $crate::EXPOSE!(@ports $expose $($rest)*);
+    };
+    ($port:ident/$proto:ident) => {{
         use $crate::Expose;
-        Expose {
-            port: $port,
-            proto: Some(stringify!($proto).to_string()),
-        }
+        Expose::from(($port, $crate::EXPOSE!(@protocol $proto)))
+    }};
+    ($port:literal $($rest:tt)*) => {{
+        let mut expose = $crate::Expose::from(Vec::<$crate::Port>::new());
+        $crate::EXPOSE!(@ports expose $port $($rest)*);
+        expose
     }};
     ($port:expr) => {{
         use $crate::Expose;
@@ -146,118 +250,139 @@ macro_rules! EXPOSE {
 #[macro_export]
 macro_rules! ENV {
     ($($x:expr => $y:expr), +) => {{
-        use $crate::Env;
-        use std::collections::HashMap;
-        let mut map = HashMap::new();
+        use $crate::{Env, Value};
+        let mut env = Env::from(Vec::<(String, Value)>::new());
         $(
-            map.insert($x, $y);
+            env.insert($x, $y);
         )+
-        Env::from(map)
+        env
     }};
 }
 
+/// Flags can be given in any order, `--chmod` accepts octal number or string,
+/// `--exclude` can be repeated
 /// ```rust,no_run
 /// # use dockerfile_rs::ADD;
 /// let add = ADD!("/var/run" "/home");
 /// assert_eq!(add.to_string(), r#"ADD "/var/run" "/home""#);
+///
+/// let add = ADD!(--link --chmod=644 "app.tar.gz" "/opt/");
+/// assert_eq!(add.to_string(), r#"ADD --chmod=0644 --link "app.tar.gz" "/opt/""#);
+///
+/// let add = ADD!("a.tar.gz" "b.tar.gz" "/opt/");
+/// assert_eq!(add.to_string(), r#"ADD "a.tar.gz" "b.tar.gz" "/opt/""#);
 /// ```
 #[macro_export]
 macro_rules! ADD {
-    (--chown=$user:ident:$group:ident $src:tt $dst:tt) => {{
-        use $crate::{Add, User};
-        Add {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            chown: Some(User {
-                user: stringify!($user).to_string(),
-                group: Some(stringify!($group).to_string()),
-            }),
-        }
+    (@flags $add:ident --chown=$user:tt:$group:tt $($rest:tt)+) => {{
+        $add.chown = Some($crate::User {
+            user: stringify!($user).trim_matches('"').to_string(),
+            group: Some(stringify!($group).trim_matches('"').to_string()),
+        });
+        $crate::ADD!(@flags $add $($rest)+)
+    }};
+    (@flags $add:ident --chown=$user:tt $($rest:tt)+) => {{
+        $add.chown = Some($crate::User {
+            user: stringify!($user).trim_matches('"').to_string(),
+            group: None,
+        });
+        $crate::ADD!(@flags $add $($rest)+)
     }};
-    (--chown=$user:ident $src:tt $dst:tt) => {{
-        use $crate::{Add, User};
-        Add {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            chown: Some(User {
-                user: stringify!($user).to_string(),
-                group: None,
-            }),
-        }
+    (@flags $add:ident --chmod=$mode:tt $($rest:tt)+) => {{
+        $add.chmod = Some($crate::Chmod::from(stringify!($mode).trim_matches('"')));
+        $crate::ADD!(@flags $add $($rest)+)
     }};
-    ($src:tt $dst:tt) => {{
-        use $crate::Add;
-        Add {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            chown: None,
-        }
+    (@flags $add:ident --link $($rest:tt)+) => {{
+        $add.link = true;
+        $crate::ADD!(@flags $add $($rest)+)
+    }};
+    (@flags $add:ident --exclude=$pattern:literal $($rest:tt)+) => {{
+        $add.exclude.push($pattern.to_string());
+        $crate::ADD!(@flags $add $($rest)+)
+    }};
+    (@flags $add:ident $dst:tt) => {{
+        $add.dst = $dst.to_string();
+    }};
+    (@flags $add:ident $src:tt $($rest:tt)+) => {{
+        $add.src.push($crate::Source::Path($src.to_string()));
+        $crate::ADD!(@flags $add $($rest)+)
+    }};
+    ($($tokens:tt)+) => {{
+        let mut add = $crate::Add::from((Vec::<$crate::Source>::new(), ""));
+        $crate::ADD!(@flags add $($tokens)+);
+        add
     }};
 }
 
+/// Flags can be given in any order, `--chmod` accepts octal number or string,
+/// `--exclude` can be repeated
 /// ```rust,no_run
 /// # use dockerfile_rs::COPY;
 /// let copy = COPY!("." ".");
 /// assert_eq!(copy.to_string(), r#"COPY "." ".""#);
+///
+/// let copy = COPY!("Cargo.toml" "Cargo.lock" "./");
+/// assert_eq!(copy.to_string(), r#"COPY "Cargo.toml" "Cargo.lock" "./""#);
+///
+/// let copy = COPY!(--from=builder --chown=app:app --chmod="u+x" "/app" "/usr/bin/app");
+/// assert_eq!(
+///     copy.to_string(),
+///     r#"COPY --from=builder --chown=app:app --chmod=u+x "/app" "/usr/bin/app""#
+/// );
+///
+/// let copy = COPY!(--parents --exclude="*.md" --exclude="*.txt" "docs" "/srv/");
+/// assert_eq!(
+///     copy.to_string(),
+///     r#"COPY --parents --exclude=*.md --exclude=*.txt "docs" "/srv/""#
+/// );
 /// ```
 #[macro_export]
 macro_rules! COPY {
-    (--from=$name:ident --chown=$user:ident:$group:ident $src:tt $dst:tt) => {{
-        use $crate::{Copy, User};
-        Copy {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            from: Some(stringify!($from).to_string()),
-            chown: Some(User {
-                user: stringify!($user).to_string(),
-                group: Some(stringify!($group).to_string()),
-            }),
-        }
+    (@flags $copy:ident --from=$name:tt $($rest:tt)+) => {{
+        $copy.from = Some(stringify!($name).trim_matches('"').to_string());
+        $crate::COPY!(@flags $copy $($rest)+)
+    }};
+    (@flags $copy:ident --chown=$user:tt:$group:tt $($rest:tt)+) => {{
+        $copy.chown = Some($crate::User {
+            user: stringify!($user).trim_matches('"').to_string(),
+            group: Some(stringify!($group).trim_matches('"').to_string()),
+        });
+        $crate::COPY!(@flags $copy $($rest)+)
+    }};
+    (@flags $copy:ident --chown=$user:tt $($rest:tt)+) => {{
+        $copy.chown = Some($crate::User {
+            user: stringify!($user).trim_matches('"').to_string(),
+            group: None,
+        });
+        $crate::COPY!(@flags $copy $($rest)+)
     }};
-    (--from=$name:ident --chown=$user:ident $src:tt $dst:tt) => {{
-        use $crate::{Copy, User};
-        Copy {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            from: Some(stringify!($from).to_string()),
-            chown: Some(User {
-                user: stringify!($user).to_string(),
-                group: None,
-            }),
-        }
+    (@flags $copy:ident --chmod=$mode:tt $($rest:tt)+) => {{
+        $copy.chmod = Some($crate::Chmod::from(stringify!($mode).trim_matches('"')));
+        $crate::COPY!(@flags $copy $($rest)+)
     }};
-    (--chown=$user:ident:$group:ident $src:tt $dst:tt) => {{
-        use $crate::{Copy, User};
-        Copy {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            from: None,
-            chown: Some(User {
-                user: stringify!($user).to_string(),
-                group: Some(stringify!($group).to_string()),
-            }),
-        }
+    (@flags $copy:ident --link $($rest:tt)+) => {{
+        $copy.link = true;
+        $crate::COPY!(@flags $copy $($rest)+)
     }};
-    (--chown=$user:ident $src:tt $dst:tt) => {{
-        use $crate::{Copy, User};
-        Copy {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            from: None,
-            chown: Some(User {
-                user: stringify!($user).to_string(),
-                group: None,
-            }),
-        }
+    (@flags $copy:ident --parents $($rest:tt)+) => {{
+        $copy.parents = true;
+        $crate::COPY!(@flags $copy $($rest)+)
     }};
-    ($src:tt $dst:tt) => {{
-        use $crate::Copy;
-        Copy {
-            src: $src.to_string(),
-            dst: $dst.to_string(),
-            from: None,
-            chown: None,
-        }
+    (@flags $copy:ident --exclude=$pattern:literal $($rest:tt)+) => {{
+        $copy.exclude.push($pattern.to_string());
+        $crate::COPY!(@flags $copy $($rest)+)
+    }};
+    (@flags $copy:ident $dst:tt) => {{
+        $copy.dst = $dst.to_string();
+    }};
+    (@flags $copy:ident $src:tt $($rest:tt)+) => {{
+        $copy.src.push($crate::Source::Path($src.to_string()));
+        $crate::COPY!(@flags $copy $($rest)+)
+    }};
+    ($($tokens:tt)+) => {{
+        let mut copy = $crate::Copy::from((Vec::<$crate::Source>::new(), ""));
+        $crate::COPY!(@flags copy $($tokens)+);
+        copy
     }};
 }
 
@@ -265,9 +390,16 @@ macro_rules! COPY {
 /// # use dockerfile_rs::ENTRYPOINT;
 /// let entry_point = ENTRYPOINT!["/bin/bash/", "-c", "echo"];
 /// assert_eq!(entry_point.to_string(), r#"ENTRYPOINT ["/bin/bash/", "-c", "echo"]"#);
+///
+/// let entry_point = ENTRYPOINT!(SHELL "exec app");
+/// assert_eq!(entry_point.to_string(), "ENTRYPOINT exec app");
 /// ```
 #[macro_export]
 macro_rules! ENTRYPOINT {
+    (SHELL $cmd:expr) => {{
+        use $crate::{CommandForm, EntryPoint};
+        EntryPoint::from(CommandForm::Shell($cmd.into()))
+    }};
     ($($x:expr), +) => {{
         use $crate::EntryPoint;
         EntryPoint::from(vec![$($x), +])
@@ -353,9 +485,58 @@ macro_rules! STOPSIGNAL {
 /// # use dockerfile_rs::HEALTHCHECK;
 /// let health_check = HEALTHCHECK!(NONE);
 /// assert_eq!(health_check.to_string(), "HEALTHCHECK NONE");
+///
+/// let health_check = HEALTHCHECK!(CMD "curl -f http://localhost/ || exit 1");
+/// assert_eq!(health_check.to_string(), "HEALTHCHECK CMD curl -f http://localhost/ || exit 1");
+///
+/// # use std::time::Duration;
+/// let health_check = HEALTHCHECK!(
+///     --interval=Duration::from_secs(30),
+///     --retries=3,
+///     CMD vec!["curl", "-f", "http://localhost/"]
+/// );
+/// assert_eq!(
+///     health_check.to_string(),
+///     r#"HEALTHCHECK --interval=30s --retries=3 CMD ["curl", "-f", "http://localhost/"]"#
+/// );
 /// ```
 #[macro_export]
 macro_rules! HEALTHCHECK {
+    (--interval=$interval:expr, $($rest:tt)+) => {{
+        let mut health_check = $crate::HEALTHCHECK!($($rest)+);
+        if let $crate::HealthCheck::Check { interval, .. } = &mut health_check {
+            *interval = Some($interval);
+        }
+        health_check
+    }};
+    (--timeout=$timeout:expr, $($rest:tt)+) => {{
+        let mut health_check = $crate::HEALTHCHECK!($($rest)+);
+        if let $crate::HealthCheck::Check { timeout, .. } = &mut health_check {
+            *timeout = Some($timeout);
+        }
+        health_check
+    }};
+    (--start-period=$period:expr, $($rest:tt)+) => {{
+        let mut health_check = $crate::HEALTHCHECK!($($rest)+);
+        if let $crate::HealthCheck::Check { start_period, .. } = &mut health_check {
+            *start_period = Some($period);
+        }
+        health_check
+    }};
+    (--start-interval=$interval:expr, $($rest:tt)+) => {{
+        let mut health_check = $crate::HEALTHCHECK!($($rest)+);
+        if let $crate::HealthCheck::Check { start_interval, .. } = &mut health_check {
+            *start_interval = Some($interval);
+        }
+        health_check
+    }};
+    (--retries=$retries:expr, $($rest:tt)+) => {{
+        let mut health_check = $crate::HEALTHCHECK!($($rest)+);
+        if let $crate::HealthCheck::Check { retries, .. } = &mut health_check {
+            *retries = Some($retries);
+        }
+        health_check
+    }};
     (NONE) => {{
         use $crate::HealthCheck;
         HealthCheck::None
@@ -367,6 +548,7 @@ macro_rules! HEALTHCHECK {
             interval: None,
             timeout: None,
             start_period: None,
+            start_interval: None,
             retries: None,
         }
     }};
@@ -421,22 +603,53 @@ mod tests {
         let _ = FROM!(rust:latest AS crab);
         let _ = FROM!(rust@digest);
         let _ = FROM!(rust@digest AS crab);
+
+        let from = FROM!(--platform="linux/arm64/v8" rust:latest AS crab);
+        assert_eq!(
+            from.platform,
+            Some(crate::Platform::Target {
+                os: "linux".to_string(),
+                architecture: Some("arm64".to_string()),
+                variant: Some("v8".to_string()),
+            })
+        );
+        assert_eq!(from.name, Some("crab".to_string()));
     }
 
     #[test]
     fn run() {
         let _ = RUN!["/bin/bash", "-c", "echo"];
+        let _ = RUN!(SHELL "echo");
+        let _ = RUN!(SHELL String::from("echo"));
+        let run = RUN!(
+            --mount = crate::Mount::cache("/cache"),
+            --mount = crate::Mount::ssh(),
+            "git",
+            "clone",
+            "git@github.com:ark0f/dockerfile.rs.git"
+        );
+        assert_eq!(
+            run.mounts,
+            vec![crate::Mount::cache("/cache"), crate::Mount::ssh()]
+        );
+        let run = RUN!(--security=insecure, --network=host, SHELL "make");
+        assert_eq!(run.network, Some(crate::Network::Host));
+        assert_eq!(run.security, Some(crate::Security::Insecure));
     }
 
     #[test]
     fn cmd() {
         let _ = CMD!["echo", "Hello, world!"];
+        let _ = CMD!(SHELL "echo Hello, world!");
     }
 
     #[test]
     fn label() {
         let _ = LABEL!["key" => "value"];
         let _ = LABEL!["key" => "value", "hello" => "world"];
+
+        let label = LABEL!["z" => "1", "a" => String::from("2"), "z" => "3"];
+        assert_eq!(label.to_string(), "LABEL z=\"3\" \\\n      a=\"2\"");
     }
 
     #[test]
@@ -448,12 +661,27 @@ mod tests {
     fn expose() {
         let _ = EXPOSE!(80 / tcp);
         let _ = EXPOSE!(443);
+
+        let port = 8080;
+        assert_eq!(EXPOSE!(port / sctp).to_string(), "EXPOSE 8080/sctp");
+        assert_eq!(EXPOSE!(port + 1).to_string(), "EXPOSE 8081");
+        assert_eq!(
+            EXPOSE!(8000-8010 9000-9001/udp 53/udp).ports,
+            vec![
+                crate::Port::from(8000..=8010).into(),
+                crate::Port::from((9000..=9001, crate::Protocol::Udp)).into(),
+                crate::Port::from((53, crate::Protocol::Udp)).into(),
+            ]
+        );
     }
 
     #[test]
     fn env() {
         let _ = ENV!["key" => "value"];
         let _ = ENV!["key" => "value", "hello" => "world"];
+
+        let env = ENV!["PATH" => crate::Value::expand("$PATH:/bin"), "HOME" => "/root"];
+        assert_eq!(env.to_string(), r#"ENV PATH="$PATH:/bin" HOME="/root""#);
     }
 
     #[test]
@@ -461,6 +689,11 @@ mod tests {
         let _ = ADD!(--chown=rustacean:root "/var/run" "/home");
         let _ = ADD!(--chown=rustacean "/var/run" "/home");
         let _ = ADD!("/var/run" "/home");
+        let add = ADD!(--exclude="*.log" --chown=1000:1000 --link --chmod=755 "src" "dst");
+        assert_eq!(add.chown.unwrap().group.unwrap(), "1000");
+        assert_eq!(add.chmod, Some(crate::Chmod::Octal(0o755)));
+        assert!(add.link);
+        assert_eq!(add.exclude, vec!["*.log"]);
     }
 
     #[test]
@@ -470,11 +703,20 @@ mod tests {
         let _ = COPY!(--chown=rustacean:root "/var/run" "/home");
         let _ = COPY!(--chown=rustacean "/var/run" "/home");
         let _ = COPY!("/var/run" "/home");
+        let copy = COPY!(--from=crab "/var/run" "/home");
+        assert_eq!(copy.from.as_deref(), Some("crab"));
+        let copy = COPY!(--from="build-env" --link --parents "a" "b");
+        assert_eq!(copy.from.as_deref(), Some("build-env"));
+        assert!(copy.link && copy.parents);
+        let copy = COPY!(--link "Cargo.toml" "Cargo.lock" "src" "./");
+        assert_eq!(copy.src.len(), 3);
+        assert_eq!(copy.dst, "./");
     }
 
     #[test]
     fn entry_point() {
         let _ = ENTRYPOINT!["echo", "Hello, world!"];
+        let _ = ENTRYPOINT!(SHELL "echo Hello, world!");
     }
 
     #[test]
@@ -507,6 +749,22 @@ mod tests {
     fn health_check() {
         let _ = HEALTHCHECK!(NONE);
         let _ = HEALTHCHECK!(CMD vec!["curl", "-v", "https://rust-lang.org"]);
+        let _ = HEALTHCHECK!(CMD "curl -v https://rust-lang.org");
+
+        let second = std::time::Duration::from_secs(1);
+        let health_check = HEALTHCHECK!(
+            --interval=30 * second,
+            --timeout=second,
+            --start-period=5 * second,
+            --start-interval=second / 2,
+            --retries=3,
+            CMD "curl -v https://rust-lang.org"
+        );
+        assert_eq!(
+            health_check.to_string(),
+            "HEALTHCHECK --interval=30s --timeout=1s --start-period=5s --start-interval=500ms \
+             --retries=3 CMD curl -v https://rust-lang.org"
+        );
     }
 
     #[test]
@@ -0,0 +1,2150 @@
+use crate::{
+    quote, split_directive, Add, Arg, Chmod, Cmd, CommandForm, Comment, Copy, Digest, Directive,
+    DockerFile, EntryPoint, Env, Expose, ExposedPort, From, HealthCheck, Heredoc, InstructionKind,
+    Label, Maintainer, Mount, Network, OnBuild, Platform, Port, Protocol, Run, Security, Sharing,
+    Shell, Source, StopSignal, Tag, TagOrDigest, User, Value, Volume, WorkDir,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    error::Error,
+    fmt::{self, Display},
+    mem,
+    time::Duration,
+};
+
+/// Flags that can be given more than once
+const REPEATABLE_FLAGS: &[&str] = &["mount", "exclude"];
+
+/// Error returned by [`DockerFile::parse`]
+///
+/// `line` and `column` are 1-based and point to the place in the source text
+/// where the problem was found.
+///
+/// [`DockerFile::parse`]: struct.DockerFile.html#method.parse
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// Keyword is not a known instruction
+    UnknownInstruction(String),
+    /// Instruction has no arguments or not enough of them
+    MissingArguments(String),
+    /// Instruction has more arguments than it accepts
+    TooManyArguments(String),
+    /// Flag is not supported by instruction
+    UnknownFlag(String),
+    /// Flag is given more than once
+    DuplicateFlag(String),
+    /// Flag or argument has a value that can't be represented
+    InvalidValue(String),
+    /// Flag references a variable, but instruction doesn't expand variables in it
+    UnsupportedVariable(String),
+    /// Instruction accepts only exec form, but argument is not a JSON array of strings
+    InvalidJson,
+    /// Quote is not closed until the end of instruction
+    UnterminatedQuote,
+    /// Heredoc has no closing delimiter until the end of input
+    UnterminatedHeredoc(String),
+    /// Instruction is not allowed as a trigger of `ONBUILD`
+    InvalidOnBuild(String),
+    /// Instruction other than `ARG` or comment comes before `FROM`
+    InstructionBeforeFrom(String),
+    /// Text contains no `FROM` instruction
+    MissingFrom,
+    /// Parser directive is given more than once
+    DuplicateDirective(String),
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnknownInstruction(name) => write!(f, "unknown instruction `{}`", name),
+            ParseErrorKind::MissingArguments(name) => {
+                write!(f, "`{}` requires more arguments", name)
+            }
+            ParseErrorKind::TooManyArguments(name) => {
+                write!(f, "`{}` has too many arguments", name)
+            }
+            ParseErrorKind::UnknownFlag(flag) => write!(f, "unknown flag `--{}`", flag),
+            ParseErrorKind::DuplicateFlag(flag) => write!(f, "flag `--{}` is given twice", flag),
+            ParseErrorKind::InvalidValue(value) => write!(f, "invalid value `{}`", value),
+            ParseErrorKind::UnsupportedVariable(value) => {
+                write!(f, "variables are not expanded in `{}`", value)
+            }
+            ParseErrorKind::InvalidJson => write!(f, "expected JSON array of strings"),
+            ParseErrorKind::UnterminatedQuote => write!(f, "unterminated quote"),
+            ParseErrorKind::UnterminatedHeredoc(delimiter) => {
+                write!(f, "heredoc `{}` is not terminated", delimiter)
+            }
+            ParseErrorKind::InvalidOnBuild(name) => {
+                write!(f, "`{}` is not allowed in `ONBUILD`", name)
+            }
+            ParseErrorKind::InstructionBeforeFrom(name) => {
+                write!(f, "`{}` is not allowed before `FROM`", name)
+            }
+            ParseErrorKind::MissingFrom => write!(f, "no `FROM` instruction"),
+            ParseErrorKind::DuplicateDirective(name) => {
+                write!(f, "directive `{}` is given twice", name)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    fn error(self, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+}
+
+/// Logical line, continuations are already joined
+struct Text {
+    chars: Vec<(char, Position)>,
+    end: Position,
+    /// Bodies of heredocs in order of their markers
+    heredocs: Vec<String>,
+    /// Escape character set by `# escape=` directive
+    escape: char,
+}
+
+impl Text {
+    fn span(&self) -> Span<'_> {
+        Span {
+            chars: &self.chars,
+            end: self.end,
+            escape: self.escape,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Span<'a> {
+    chars: &'a [(char, Position)],
+    end: Position,
+    escape: char,
+}
+
+impl<'a> Span<'a> {
+    fn position(&self) -> Position {
+        self.chars.first().map(|(_, pos)| *pos).unwrap_or(self.end)
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        self.position().error(kind)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    fn slice(&self, start: usize, end: usize) -> Span<'a> {
+        Span {
+            chars: &self.chars[start..end],
+            end: self.chars.get(end).map(|(_, pos)| *pos).unwrap_or(self.end),
+            escape: self.escape,
+        }
+    }
+
+    fn trim(&self) -> Span<'a> {
+        let start = self
+            .chars
+            .iter()
+            .position(|(c, _)| !c.is_whitespace())
+            .unwrap_or(self.chars.len());
+        let end = self
+            .chars
+            .iter()
+            .rposition(|(c, _)| !c.is_whitespace())
+            .map_or(start, |i| i + 1);
+        self.slice(start, end)
+    }
+
+    /// Splits off the first whitespace delimited word, rest is trimmed
+    fn split_word(&self) -> (Span<'a>, Span<'a>) {
+        let span = self.trim();
+        let end = span
+            .chars
+            .iter()
+            .position(|(c, _)| c.is_whitespace())
+            .unwrap_or(span.chars.len());
+        (span.slice(0, end), span.slice(end, span.chars.len()).trim())
+    }
+
+    fn find(&self, c: char) -> Option<usize> {
+        self.chars.iter().position(|(ch, _)| *ch == c)
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        let mut chars = self.chars.iter();
+        prefix
+            .chars()
+            .all(|p| chars.next().is_some_and(|(c, _)| *c == p))
+    }
+}
+
+impl<'a> Display for Span<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (c, _) in self.chars {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+enum Item {
+    Comment(Text),
+    Instruction(Text),
+}
+
+/// Splits input after the first `skip` lines, which are already read, into comments and logical
+/// lines with their heredoc bodies
+fn items(input: &str, skip: usize, escape: char) -> Result<Vec<Item>, ParseError> {
+    let mut items = Vec::new();
+    let mut current: Option<Vec<(char, Position)>> = None;
+    let mut end = Position { line: 1, column: 1 };
+    let mut pending = VecDeque::new();
+    let mut body = String::new();
+
+    for (idx, line) in input.lines().enumerate().skip(skip) {
+        if let Some((delimiter, strip_tabs, _)) = pending.front() {
+            let line = if *strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                line
+            };
+            if line == delimiter {
+                pending.pop_front();
+                if let Some(Item::Instruction(text)) = items.last_mut() {
+                    text.heredocs.push(mem::take(&mut body));
+                }
+            } else {
+                body.push_str(line);
+                body.push('\n');
+            }
+            continue;
+        }
+
+        let chars: Vec<(char, Position)> = line
+            .chars()
+            .enumerate()
+            .map(|(column, c)| {
+                let pos = Position {
+                    line: idx + 1,
+                    column: column + 1,
+                };
+                (c, pos)
+            })
+            .collect();
+        end = Position {
+            line: idx + 1,
+            column: chars.len() + 1,
+        };
+
+        let first = match chars.iter().position(|(c, _)| !c.is_whitespace()) {
+            Some(first) => first,
+            None => continue,
+        };
+
+        // comments inside of continuation are dropped as Docker does
+        if chars[first].0 == '#' {
+            if current.is_none() {
+                let chars = chars[first + 1..].to_vec();
+                let heredocs = Vec::new();
+                items.push(Item::Comment(Text {
+                    chars,
+                    end,
+                    heredocs,
+                    escape,
+                }));
+            }
+            continue;
+        }
+
+        let last = chars
+            .iter()
+            .rposition(|(c, _)| !c.is_whitespace())
+            .unwrap_or(first);
+        let continued = chars[last].0 == escape;
+        let mut buffer = current.take().unwrap_or_default();
+        buffer.extend_from_slice(&chars[..if continued { last } else { chars.len() }]);
+        if continued {
+            current = Some(buffer);
+        } else {
+            pending.extend(heredoc_markers(&buffer));
+            let heredocs = Vec::new();
+            items.push(Item::Instruction(Text {
+                chars: buffer,
+                end,
+                heredocs,
+                escape,
+            }));
+        }
+    }
+
+    if let Some(chars) = current {
+        let heredocs = Vec::new();
+        items.push(Item::Instruction(Text {
+            chars,
+            end,
+            heredocs,
+            escape,
+        }));
+    } else if let Some((delimiter, _, pos)) = pending.pop_front() {
+        return Err(pos.error(ParseErrorKind::UnterminatedHeredoc(delimiter)));
+    }
+
+    Ok(items)
+}
+
+/// Finds heredoc markers of `RUN`, `ADD` and `COPY`,
+/// returns delimiters, whether tabs are stripped and positions of markers
+fn heredoc_markers(chars: &[(char, Position)]) -> Vec<(String, bool, Position)> {
+    let mut words = chars
+        .split(|(c, _)| c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| (word.iter().map(|(c, _)| c).collect::<String>(), word[0].1))
+        .peekable();
+    if words
+        .peek()
+        .is_some_and(|(word, _)| word.eq_ignore_ascii_case("ONBUILD"))
+    {
+        words.next();
+    }
+    match words.next() {
+        Some((keyword, _)) if keyword.eq_ignore_ascii_case("RUN") => {}
+        Some((keyword, _)) if keyword.eq_ignore_ascii_case("ADD") => {}
+        Some((keyword, _)) if keyword.eq_ignore_ascii_case("COPY") => {}
+        _ => return Vec::new(),
+    }
+    words
+        .filter_map(|(word, pos)| {
+            let (delimiter, strip_tabs) = heredoc_marker(&word)?;
+            Some((delimiter, strip_tabs, pos))
+        })
+        .collect()
+}
+
+/// Parses `<<EOF`, `<<-EOF`, `<<"EOF"` or `<<'EOF'`
+fn heredoc_marker(word: &str) -> Option<(String, bool)> {
+    let rest = word.strip_prefix("<<")?;
+    let (rest, strip_tabs) = match rest.strip_prefix('-') {
+        Some(rest) => (rest, true),
+        None => (rest, false),
+    };
+    let delimiter = ['"', '\'']
+        .iter()
+        .find_map(|&q| rest.strip_prefix(q)?.strip_suffix(q))
+        .unwrap_or(rest);
+    let valid = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.';
+    if delimiter.is_empty() || !delimiter.chars().all(valid) {
+        return None;
+    }
+    Some((delimiter.to_string(), strip_tabs))
+}
+
+pub(crate) fn parse(input: &str) -> Result<DockerFile, ParseError> {
+    let directives = directives(input)?;
+    let escape = directives
+        .iter()
+        .find_map(|directive| match directive {
+            Directive::Escape(c) => Some(*c),
+            _ => None,
+        })
+        .unwrap_or('\\');
+    let docker_file = stages(input, directives.len(), escape)?;
+    Ok(directives
+        .into_iter()
+        .fold(docker_file, DockerFile::directive))
+}
+
+/// Reads parser directives, they are recognized only in comments at the very top of the file
+fn directives(input: &str) -> Result<Vec<Directive>, ParseError> {
+    let mut directives: Vec<Directive> = Vec::new();
+
+    for (idx, line) in input.lines().enumerate() {
+        let text = match line.trim_start().strip_prefix('#') {
+            Some(text) => text,
+            None => break,
+        };
+        let (name, value) = match split_directive(text) {
+            Some(directive) => directive,
+            None => break,
+        };
+        let pos = Position {
+            line: idx + 1,
+            column: line.chars().take_while(|c| c.is_whitespace()).count() + 1,
+        };
+        if directives.iter().any(|directive| directive.name() == name) {
+            return Err(pos.error(ParseErrorKind::DuplicateDirective(name)));
+        }
+        let invalid = || pos.error(ParseErrorKind::InvalidValue(value.to_string()));
+        let directive = match name.as_str() {
+            "syntax" => Directive::syntax(value),
+            "escape" => match value {
+                "\\" => Directive::Escape('\\'),
+                "`" => Directive::Escape('`'),
+                _ => return Err(invalid()),
+            },
+            _ => {
+                let mut skip = Vec::new();
+                let mut error = false;
+                for option in value.split(';') {
+                    let mut parts = option.splitn(2, '=');
+                    match (parts.next().map(str::trim), parts.next().map(str::trim)) {
+                        (Some("skip"), Some(rules)) => skip.extend(
+                            rules
+                                .split(',')
+                                .map(str::trim)
+                                .filter(|rule| !rule.is_empty())
+                                .map(String::from),
+                        ),
+                        (Some("error"), Some("true")) => error = true,
+                        (Some("error"), Some("false")) => error = false,
+                        _ => return Err(invalid()),
+                    }
+                }
+                Directive::Check { skip, error }
+            }
+        };
+        directives.push(directive);
+    }
+
+    Ok(directives)
+}
+
+/// Parses instructions and comments after directives
+fn stages(input: &str, skip: usize, escape: char) -> Result<DockerFile, ParseError> {
+    let mut docker_file: Option<DockerFile> = None;
+    let mut global = Vec::new();
+    let mut end = Position { line: 1, column: 1 };
+
+    for item in items(input, skip, escape)? {
+        match item {
+            Item::Comment(text) => {
+                end = text.end;
+                let comment = Comment::from(text.span().trim().to_string());
+                match docker_file.take() {
+                    Some(file) => docker_file = Some(file.comment(comment)),
+                    None => global.push(InstructionKind::Comment(comment)),
+                }
+            }
+            Item::Instruction(text) => {
+                end = text.end;
+                let span = text.span().trim();
+                for instruction in instruction(span, &text.heredocs)? {
+                    docker_file = Some(match (instruction, docker_file.take()) {
+                        (InstructionKind::From(from), None) => {
+                            global
+                                .drain(..)
+                                .fold(DockerFile::from(from), |file, i| match i {
+                                    InstructionKind::Arg(arg) => file.global_arg(arg),
+                                    InstructionKind::Comment(comment) => {
+                                        file.global_comment(comment)
+                                    }
+                                    _ => unreachable!("only `ARG` and comments are global"),
+                                })
+                        }
+                        (InstructionKind::From(from), Some(file)) => {
+                            file.stage(from, |stage| stage)
+                        }
+                        (InstructionKind::Arg(arg), None) => {
+                            global.push(InstructionKind::Arg(arg));
+                            continue;
+                        }
+                        (_, None) => {
+                            let (keyword, _) = span.split_word();
+                            let name = keyword.to_string().to_uppercase();
+                            return Err(span.error(ParseErrorKind::InstructionBeforeFrom(name)));
+                        }
+                        // repeated `CMD` and `ENTRYPOINT` are kept as written, so lint can report them
+                        (instruction, Some(file)) => file.push(instruction),
+                    });
+                }
+            }
+        }
+    }
+
+    docker_file.ok_or_else(|| end.error(ParseErrorKind::MissingFrom))
+}
+
+/// Parses logical line, some instructions like `ARG a b` are split into several
+fn instruction(span: Span, heredocs: &[String]) -> Result<Vec<InstructionKind>, ParseError> {
+    let (keyword, args) = span.split_word();
+    let name = keyword.to_string().to_uppercase();
+    if args.is_empty() {
+        return Err(args.error(ParseErrorKind::MissingArguments(name)));
+    }
+
+    let instruction: InstructionKind = match name.as_str() {
+        "FROM" => from(args)?.into(),
+        "MAINTAINER" => Maintainer::from(args.to_string()).into(),
+        "RUN" => run(args, heredocs)?.into(),
+        "CMD" => Cmd::from(command(args)).into(),
+        "ENTRYPOINT" => EntryPoint::from(command(args)).into(),
+        "LABEL" => Label::from(pairs(&name, args)?).into(),
+        "ENV" => Env::from(pairs(&name, args)?).into(),
+        "EXPOSE" => expose(args)?.into(),
+        "ADD" => add(args, heredocs)?.into(),
+        "COPY" => copy(args, heredocs)?.into(),
+        "VOLUME" => Volume::from(list(args)?).into(),
+        "USER" => user(&unquote(args)?).into(),
+        "WORKDIR" => WorkDir::from(unquote(args)?).into(),
+        "ARG" => return Ok(arg(args)?.into_iter().map(Into::into).collect()),
+        "STOPSIGNAL" => StopSignal::from(args.to_string()).into(),
+        "HEALTHCHECK" => health_check(args)?.into(),
+        "SHELL" => json_array(args)
+            .map(Shell::from)
+            .ok_or_else(|| args.error(ParseErrorKind::InvalidJson))?
+            .into(),
+        "ONBUILD" => return on_build(args, heredocs),
+        _ => return Err(keyword.error(ParseErrorKind::UnknownInstruction(name))),
+    };
+    Ok(vec![instruction])
+}
+
+fn from(args: Span) -> Result<From, ParseError> {
+    let (flags, args) = flags(args, &["platform"])?;
+    let platform = flag_value(&flags, "platform")?.map(Platform::from);
+    let words = words(args)?;
+    let name = match words.as_slice() {
+        [] => return Err(args.error(ParseErrorKind::MissingArguments("FROM".to_string()))),
+        [_] => None,
+        [_, keyword, name] if keyword.to_string().eq_ignore_ascii_case("AS") => {
+            Some(name.to_string())
+        }
+        [_, _] | [_, _, _] => {
+            return Err(words[1].error(ParseErrorKind::InvalidValue(words[1].to_string())))
+        }
+        _ => return Err(words[3].error(ParseErrorKind::TooManyArguments("FROM".to_string()))),
+    };
+
+    let (image, tag_or_digest) = reference(&words[0].to_string());
+
+    Ok(From {
+        image,
+        tag_or_digest,
+        name,
+        platform,
+    })
+}
+
+/// Splits image reference into image and tag or digest, `:` and `@` inside of `${...}` are
+/// left to variable
+pub(crate) fn reference(reference: &str) -> (String, Option<TagOrDigest>) {
+    let mut separators = Vec::new();
+    let mut depth = 0;
+    let mut chars = reference.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '$' if chars.peek().is_some_and(|(_, next)| *next == '{') => {
+                chars.next();
+                depth += 1;
+            }
+            '}' if depth > 0 => depth -= 1,
+            '/' | ':' | '@' if depth == 0 => separators.push((i, c)),
+            _ => {}
+        }
+    }
+
+    if let Some(&(at, _)) = separators.iter().find(|(_, c)| *c == '@') {
+        let digest = Digest(reference[at + 1..].to_string());
+        return (reference[..at].to_string(), Some(digest));
+    }
+    // colon before the last slash belongs to registry port
+    let slash = separators
+        .iter()
+        .rev()
+        .find(|(_, c)| *c == '/')
+        .map_or(0, |(i, _)| i + 1);
+    match separators.iter().find(|(i, c)| *c == ':' && *i >= slash) {
+        Some(&(colon, _)) => {
+            let tag = Tag(reference[colon + 1..].to_string());
+            (reference[..colon].to_string(), Some(tag))
+        }
+        None => (reference.to_string(), None),
+    }
+}
+
+/// Parses either exec or shell form
+fn command(args: Span) -> CommandForm {
+    match json_array(args) {
+        Some(params) => CommandForm::Exec(params),
+        None => CommandForm::Shell(args.to_string()),
+    }
+}
+
+/// Parses command with heredoc as `[command] <<EOF [suffix]`, other forms are parsed by `command`
+fn run(args: Span, heredocs: &[String]) -> Result<Run, ParseError> {
+    let (flags, args) = flags(args, &["mount", "network", "security"])?;
+    let mounts = flags
+        .iter()
+        .filter(|(name, _, _)| name == "mount")
+        .map(|(_, value, span)| match value {
+            // value is taken as is, quotes of CSV must be kept
+            Some(_) => mount(&span.to_string()["--mount=".len()..], *span),
+            None => Err(span.error(ParseErrorKind::InvalidValue(span.to_string()))),
+        })
+        .collect::<Result<_, _>>()?;
+    let invalid = |name| {
+        let (_, _, span) = flags.iter().find(|(flag, _, _)| flag == name).unwrap();
+        span.error(ParseErrorKind::InvalidValue(span.to_string()))
+    };
+    let network = match flag_value(&flags, "network")?.as_deref() {
+        Some("default") => Some(Network::Default),
+        Some("none") => Some(Network::None),
+        Some("host") => Some(Network::Host),
+        Some(_) => return Err(invalid("network")),
+        None => None,
+    };
+    let security = match flag_value(&flags, "security")?.as_deref() {
+        Some("sandbox") => Some(Security::Sandbox),
+        Some("insecure") => Some(Security::Insecure),
+        Some(_) => return Err(invalid("security")),
+        None => None,
+    };
+    if args.is_empty() {
+        return Err(args.error(ParseErrorKind::MissingArguments("RUN".to_string())));
+    }
+    let body = match heredocs {
+        [] => {
+            let command = command(args);
+            return Ok(Run {
+                mounts,
+                network,
+                security,
+                command,
+            });
+        }
+        [body] => body,
+        _ => return Err(args.error(ParseErrorKind::TooManyArguments("RUN".to_string()))),
+    };
+    let words = words(args)?;
+    let (marker, delimiter) = match words
+        .iter()
+        .find_map(|word| Some((word, heredoc_marker(&word.to_string())?.0)))
+    {
+        Some(marker) => marker,
+        None => {
+            let word = words.last().expect("arguments are not empty");
+            return Err(word.error(ParseErrorKind::InvalidValue(word.to_string())));
+        }
+    };
+    let start = args
+        .chars
+        .iter()
+        .position(|(_, pos)| *pos == marker.position())
+        .expect("marker is a word of arguments");
+    let text = |span: Span| {
+        let span = span.trim();
+        if span.is_empty() {
+            None
+        } else {
+            Some(span.to_string())
+        }
+    };
+    let command = CommandForm::Heredoc {
+        command: text(args.slice(0, start)),
+        heredoc: heredoc(delimiter, body),
+        suffix: text(args.slice(start + marker.chars.len(), args.chars.len())),
+    };
+    Ok(Run {
+        mounts,
+        network,
+        security,
+        command,
+    })
+}
+
+/// Parses value of `--mount`, options are comma separated and can be quoted as in CSV
+fn mount(value: &str, span: Span) -> Result<Mount, ParseError> {
+    let invalid = |value: &str| span.error(ParseErrorKind::InvalidValue(value.to_string()));
+    let mut options = Vec::new();
+    let mut option = String::new();
+    let mut quoted = false;
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                option.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => options.push(mem::take(&mut option)),
+            c => option.push(c),
+        }
+    }
+    options.push(option);
+
+    let mut kind = "bind".to_string();
+    let mut values: HashMap<&str, String> = HashMap::new();
+    for option in &options {
+        let (name, value) = match option.find('=') {
+            Some(eq) => (&option[..eq], Some(option[eq + 1..].to_string())),
+            None => (option.as_str(), None),
+        };
+        let name = match name.to_lowercase().as_str() {
+            "type" => {
+                kind = value.ok_or_else(|| invalid(option))?;
+                continue;
+            }
+            "id" => "id",
+            "target" | "dst" | "destination" => "target",
+            "source" | "src" => "source",
+            "from" => "from",
+            "sharing" => "sharing",
+            "mode" => "mode",
+            "uid" => "uid",
+            "gid" => "gid",
+            "size" => "size",
+            "required" => "required",
+            "ro" | "readonly" => "readonly",
+            "rw" | "readwrite" => "rw",
+            _ => return Err(invalid(option)),
+        };
+        values.insert(name, value.unwrap_or_else(|| "true".to_string()));
+    }
+
+    let string = |name| values.get(name).cloned();
+    let boolean = |name| match values.get(name).map(String::as_str) {
+        Some("true") => Ok(true),
+        Some("false") | None => Ok(false),
+        Some(value) => Err(invalid(value)),
+    };
+    let number = |name| -> Result<Option<u32>, ParseError> {
+        match values.get(name) {
+            Some(value) if name == "mode" => u32::from_str_radix(value, 8).map(Some),
+            Some(value) => value.parse().map(Some),
+            None => Ok(None),
+        }
+        .map_err(|_| invalid(&values[name]))
+    };
+    let target = || string("target").ok_or_else(|| invalid(value));
+
+    Ok(match kind.as_str() {
+        "bind" => Mount::Bind {
+            target: target()?,
+            source: string("source"),
+            from: string("from"),
+            readwrite: boolean("rw")?,
+        },
+        "cache" => Mount::Cache {
+            id: string("id"),
+            target: target()?,
+            source: string("source"),
+            from: string("from"),
+            sharing: match string("sharing").as_deref() {
+                Some("shared") => Some(Sharing::Shared),
+                Some("private") => Some(Sharing::Private),
+                Some("locked") => Some(Sharing::Locked),
+                Some(sharing) => return Err(invalid(sharing)),
+                None => None,
+            },
+            mode: number("mode")?,
+            uid: number("uid")?,
+            gid: number("gid")?,
+            readonly: boolean("readonly")?,
+        },
+        "tmpfs" => Mount::Tmpfs {
+            target: target()?,
+            size: match values.get("size") {
+                Some(size) => Some(size.parse().map_err(|_| invalid(size))?),
+                None => None,
+            },
+        },
+        "secret" => Mount::Secret {
+            id: string("id"),
+            target: string("target"),
+            required: boolean("required")?,
+            mode: number("mode")?,
+            uid: number("uid")?,
+            gid: number("gid")?,
+        },
+        "ssh" => Mount::Ssh {
+            id: string("id"),
+            target: string("target"),
+            required: boolean("required")?,
+            mode: number("mode")?,
+            uid: number("uid")?,
+            gid: number("gid")?,
+        },
+        _ => return Err(invalid(&kind)),
+    })
+}
+
+/// Splits shebang line off the heredoc body
+fn heredoc(delimiter: String, body: &str) -> Heredoc {
+    match body.strip_prefix("#!") {
+        Some(script) => {
+            let newline = script.find('\n').unwrap_or(script.len());
+            Heredoc {
+                content: script[(newline + 1).min(script.len())..].to_string(),
+                delimiter,
+                interpreter: Some(script[..newline].to_string()),
+            }
+        }
+        None => Heredoc {
+            content: body.to_string(),
+            delimiter,
+            interpreter: None,
+        },
+    }
+}
+
+/// Parses JSON array or whitespace delimited words
+fn list(args: Span) -> Result<Vec<String>, ParseError> {
+    match json_array(args) {
+        Some(list) => Ok(list),
+        None => words(args)?.into_iter().map(unquote).collect(),
+    }
+}
+
+fn pairs(name: &str, args: Span) -> Result<Vec<(String, Value)>, ParseError> {
+    let words = words(args)?;
+    let mut pairs = Vec::new();
+
+    // legacy `KEY value with spaces` form
+    if words[0].find('=').is_none() {
+        let (key, value) = args.split_word();
+        if value.is_empty() {
+            return Err(value.error(ParseErrorKind::MissingArguments(name.to_string())));
+        }
+        pairs.push((unquote(key)?, self::value(value)?));
+        return Ok(pairs);
+    }
+
+    for word in words {
+        match word.find('=') {
+            Some(eq) => {
+                let key = word.slice(0, eq);
+                if key.is_empty() {
+                    return Err(word.error(ParseErrorKind::InvalidValue(word.to_string())));
+                }
+                let value = word.slice(eq + 1, word.chars.len());
+                pairs.push((unquote(key)?, self::value(value)?));
+            }
+            None => return Err(word.error(ParseErrorKind::InvalidValue(word.to_string()))),
+        }
+    }
+
+    Ok(pairs)
+}
+
+fn expose(args: Span) -> Result<Expose, ParseError> {
+    let ports = words(args)?
+        .into_iter()
+        .map(|span| {
+            let word = word(span)?;
+            if quote::has_expansion(&word) {
+                return Ok(ExposedPort::Variable(word));
+            }
+            let text = quote::unescape(&word);
+            port(&text)
+                .map(ExposedPort::Port)
+                .ok_or_else(|| span.error(ParseErrorKind::InvalidValue(text)))
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Expose { ports })
+}
+
+/// Parses `port`, `port/protocol` or `start-end/protocol`, protocol is case-insensitive
+pub(crate) fn port(text: &str) -> Option<Port> {
+    let (range, protocol) = match text.find('/') {
+        Some(slash) => (&text[..slash], Some(&text[slash + 1..])),
+        None => (text, None),
+    };
+    let protocol = match protocol.map(str::to_lowercase).as_deref() {
+        Some("tcp") => Some(Protocol::Tcp),
+        Some("udp") => Some(Protocol::Udp),
+        Some("sctp") => Some(Protocol::Sctp),
+        Some(_) => return None,
+        None => None,
+    };
+    let (port, end) = match range.find('-') {
+        Some(dash) => (&range[..dash], Some(&range[dash + 1..])),
+        None => (range, None),
+    };
+    let port = Port {
+        port: port.parse().ok()?,
+        end: match end {
+            Some(end) => Some(end.parse().ok()?),
+            None => None,
+        },
+        protocol,
+    };
+    port.validate().ok()?;
+    Some(port)
+}
+
+/// Splits sources and destination of `ADD` and `COPY`, heredoc markers are replaced by bodies
+fn paths(name: &str, args: Span, heredocs: &[String]) -> Result<(Vec<Source>, String), ParseError> {
+    let mut paths = list(args)?;
+    let dst = match paths.pop() {
+        Some(dst) if !paths.is_empty() => dst,
+        _ => return Err(args.error(ParseErrorKind::MissingArguments(name.to_string()))),
+    };
+    let mut heredocs = heredocs.iter();
+    let src = paths
+        .into_iter()
+        .map(|src| match heredoc_marker(&src) {
+            Some((delimiter, _)) => match heredocs.next() {
+                Some(body) => Source::Heredoc(heredoc(delimiter, body)),
+                None => Source::Path(src),
+            },
+            None => Source::Path(src),
+        })
+        .collect();
+    Ok((src, dst))
+}
+
+fn add(args: Span, heredocs: &[String]) -> Result<Add, ParseError> {
+    let allowed = [
+        "chown",
+        "chmod",
+        "link",
+        "checksum",
+        "keep-git-dir",
+        "exclude",
+    ];
+    let (flags, args) = flags(args, &allowed)?;
+    let (src, dst) = paths("ADD", args, heredocs)?;
+    Ok(Add {
+        src,
+        dst,
+        chown: flag_value(&flags, "chown")?.map(|s| user(&s)),
+        chmod: flag_value(&flags, "chmod")?.map(Chmod::from),
+        link: switch(&flags, "link")?,
+        checksum: flag_value(&flags, "checksum")?,
+        keep_git_dir: switch(&flags, "keep-git-dir")?,
+        exclude: flag_values(&flags, "exclude")?,
+    })
+}
+
+fn copy(args: Span, heredocs: &[String]) -> Result<Copy, ParseError> {
+    let allowed = ["from", "chown", "chmod", "link", "parents", "exclude"];
+    let (flags, args) = flags(args, &allowed)?;
+    let (src, dst) = paths("COPY", args, heredocs)?;
+    Ok(Copy {
+        src,
+        dst,
+        from: flag_value(&flags, "from")?,
+        chown: flag_value(&flags, "chown")?.map(|s| user(&s)),
+        chmod: flag_value(&flags, "chmod")?.map(Chmod::from),
+        link: switch(&flags, "link")?,
+        parents: switch(&flags, "parents")?,
+        exclude: flag_values(&flags, "exclude")?,
+    })
+}
+
+pub(crate) fn user(s: &str) -> User {
+    match s.find(':') {
+        Some(colon) => User {
+            user: s[..colon].to_string(),
+            group: Some(s[colon + 1..].to_string()),
+        },
+        None => User {
+            user: s.to_string(),
+            group: None,
+        },
+    }
+}
+
+fn arg(args: Span) -> Result<Vec<Arg>, ParseError> {
+    words(args)?
+        .into_iter()
+        .map(|word| match word.find('=') {
+            Some(eq) => Ok(Arg {
+                name: unquote(word.slice(0, eq))?,
+                value: Some(value(word.slice(eq + 1, word.chars.len()))?),
+            }),
+            None => Ok(Arg {
+                name: unquote(word)?,
+                value: None,
+            }),
+        })
+        .collect()
+}
+
+fn health_check(args: Span) -> Result<HealthCheck, ParseError> {
+    let (flags, args) = flags(
+        args,
+        &[
+            "interval",
+            "timeout",
+            "start-period",
+            "start-interval",
+            "retries",
+        ],
+    )?;
+    let (keyword, rest) = args.split_word();
+    let keyword_name = keyword.to_string().to_uppercase();
+
+    if keyword_name == "NONE" {
+        if let Some((name, _, span)) = flags.first() {
+            return Err(span.error(ParseErrorKind::UnknownFlag(name.clone())));
+        }
+        if !rest.is_empty() {
+            let kind = ParseErrorKind::TooManyArguments("HEALTHCHECK".to_string());
+            return Err(rest.error(kind));
+        }
+        return Ok(HealthCheck::None);
+    }
+
+    if keyword_name != "CMD" {
+        return Err(keyword.error(ParseErrorKind::InvalidValue(keyword.to_string())));
+    }
+    if rest.is_empty() {
+        let kind = ParseErrorKind::MissingArguments("HEALTHCHECK".to_string());
+        return Err(rest.error(kind));
+    }
+
+    // Docker doesn't expand variables in flags of `HEALTHCHECK`
+    if let Some((_, _, span)) = flags
+        .iter()
+        .find(|(_, value, _)| value.as_deref().is_some_and(quote::has_expansion))
+    {
+        return Err(span.error(ParseErrorKind::UnsupportedVariable(span.to_string())));
+    }
+    let period = |name| -> Result<Option<Duration>, ParseError> {
+        let span = match flags.iter().find(|(flag, _, _)| flag == name) {
+            Some((_, _, span)) => *span,
+            None => return Ok(None),
+        };
+        flag_value(&flags, name)?
+            .and_then(|value| duration(&value))
+            .map(Some)
+            .ok_or_else(|| span.error(ParseErrorKind::InvalidValue(span.to_string())))
+    };
+    let retries = match flags.iter().find(|(flag, _, _)| flag == "retries") {
+        Some((_, value, span)) => value
+            .as_ref()
+            .and_then(|value| value.parse().ok())
+            .filter(|retries: &i32| *retries >= 0)
+            .map(Some)
+            .ok_or_else(|| span.error(ParseErrorKind::InvalidValue(span.to_string())))?,
+        None => None,
+    };
+
+    Ok(HealthCheck::Check {
+        cmd: Cmd::from(command(rest)),
+        interval: period("interval")?,
+        timeout: period("timeout")?,
+        start_period: period("start-period")?,
+        start_interval: period("start-interval")?,
+        retries,
+    })
+}
+
+fn on_build(args: Span, heredocs: &[String]) -> Result<Vec<InstructionKind>, ParseError> {
+    let (keyword, _) = args.split_word();
+    let name = keyword.to_string().to_uppercase();
+    instruction(args, heredocs)?
+        .into_iter()
+        .map(|instruction| match instruction {
+            InstructionKind::From(_)
+            | InstructionKind::Maintainer(_)
+            | InstructionKind::OnBuild(_) => {
+                Err(keyword.error(ParseErrorKind::InvalidOnBuild(name.clone())))
+            }
+            instruction => {
+                let inner = Box::new(instruction);
+                Ok(OnBuild { inner }.into())
+            }
+        })
+        .collect()
+}
+
+type Flag<'a> = (String, Option<String>, Span<'a>);
+
+/// Splits leading `--name=value` flags off the arguments
+fn flags<'a>(args: Span<'a>, allowed: &[&str]) -> Result<(Vec<Flag<'a>>, Span<'a>), ParseError> {
+    let mut flags: Vec<Flag> = Vec::new();
+    let mut args = args.trim();
+
+    while args.starts_with("--") {
+        let (word, rest) = args.split_word();
+        let flag = word.slice(2, word.chars.len());
+        let (name, value) = match flag.find('=') {
+            Some(eq) => (
+                flag.slice(0, eq).to_string(),
+                Some(unquote(flag.slice(eq + 1, flag.chars.len()))?),
+            ),
+            None => (flag.to_string(), None),
+        };
+        if !allowed.contains(&name.as_str()) {
+            return Err(word.error(ParseErrorKind::UnknownFlag(name)));
+        }
+        let repeatable = REPEATABLE_FLAGS.contains(&name.as_str());
+        if !repeatable && flags.iter().any(|(flag, _, _)| *flag == name) {
+            return Err(word.error(ParseErrorKind::DuplicateFlag(name)));
+        }
+        flags.push((name, value, word));
+        args = rest;
+    }
+
+    Ok((flags, args))
+}
+
+/// Returns value of flag, flag must have a value if present
+fn flag_value(flags: &[Flag], name: &str) -> Result<Option<String>, ParseError> {
+    match flags.iter().find(|(flag, _, _)| flag == name) {
+        Some((_, Some(value), _)) => Ok(Some(value.clone())),
+        Some((_, None, span)) => Err(span.error(ParseErrorKind::InvalidValue(span.to_string()))),
+        None => Ok(None),
+    }
+}
+
+/// Values of repeatable flag, every one must have a value
+fn flag_values(flags: &[Flag], name: &str) -> Result<Vec<String>, ParseError> {
+    flags
+        .iter()
+        .filter(|(flag, _, _)| flag == name)
+        .map(|(_, value, span)| {
+            value
+                .clone()
+                .ok_or_else(|| span.error(ParseErrorKind::InvalidValue(span.to_string())))
+        })
+        .collect()
+}
+
+/// Boolean flag, `--name` is the same as `--name=true`
+fn switch(flags: &[Flag], name: &str) -> Result<bool, ParseError> {
+    match flags.iter().find(|(flag, _, _)| flag == name) {
+        Some((_, None, _)) => Ok(true),
+        Some((_, Some(value), _)) if value == "true" => Ok(true),
+        Some((_, Some(value), _)) if value == "false" => Ok(false),
+        Some((_, _, span)) => Err(span.error(ParseErrorKind::InvalidValue(span.to_string()))),
+        None => Ok(false),
+    }
+}
+
+/// Splits arguments into whitespace delimited words keeping quotes
+fn words(args: Span<'_>) -> Result<Vec<Span<'_>>, ParseError> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote: Option<(char, Position)> = None;
+    let mut escaped = false;
+    let escape = args.escape;
+
+    for (i, &(c, pos)) in args.chars.iter().enumerate() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match quote {
+            Some((q, _)) if c == q => quote = None,
+            Some(('"', _)) if c == escape => escaped = true,
+            Some(_) => {}
+            None if c.is_whitespace() => {
+                if let Some(start) = start.take() {
+                    words.push(args.slice(start, i));
+                }
+            }
+            None => {
+                start.get_or_insert(i);
+                match c {
+                    '"' | '\'' => quote = Some((c, pos)),
+                    c if c == escape => escaped = true,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    if let Some((_, pos)) = quote {
+        return Err(pos.error(ParseErrorKind::UnterminatedQuote));
+    }
+    if let Some(start) = start {
+        words.push(args.slice(start, args.chars.len()));
+    }
+
+    Ok(words)
+}
+
+/// Removes quotes and escapes the same way Docker processes words
+fn unquote(span: Span) -> Result<String, ParseError> {
+    word(span).map(|word| quote::unescape(&word))
+}
+
+/// Parses value of `ENV`, `LABEL` or `ARG`, unescaped `$` makes it expanding
+fn value(span: Span) -> Result<Value, ParseError> {
+    let word = word(span)?;
+    if quote::has_expansion(&word) {
+        Ok(Value::expand(word))
+    } else {
+        Ok(Value::literal(quote::unescape(&word)))
+    }
+}
+
+/// Removes quotes, result has the same escaping as expanding [`Value`]
+///
+/// [`Value`]: ../struct.Value.html
+fn word(span: Span) -> Result<String, ParseError> {
+    fn literal(result: &mut String, c: char) {
+        if c == '$' || c == '\\' {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+
+    let mut result = String::new();
+    let mut chars = span.chars.iter().peekable();
+    let escape = span.escape;
+
+    while let Some(&(c, pos)) = chars.next() {
+        match c {
+            c if c == escape => {
+                literal(&mut result, chars.next().map_or(escape, |(next, _)| *next))
+            }
+            '\'' => loop {
+                match chars.next() {
+                    Some(('\'', _)) => break,
+                    Some(&(next, _)) => literal(&mut result, next),
+                    None => return Err(pos.error(ParseErrorKind::UnterminatedQuote)),
+                }
+            },
+            '"' => loop {
+                match chars.next() {
+                    Some(('"', _)) => break,
+                    Some(&(c, _)) if c == escape => match chars.peek() {
+                        Some(&&(next, _)) if next == '"' || next == '$' || next == escape => {
+                            literal(&mut result, next);
+                            chars.next();
+                        }
+                        _ => literal(&mut result, escape),
+                    },
+                    Some(('\\', _)) => literal(&mut result, '\\'),
+                    Some(&(next, _)) => result.push(next),
+                    None => return Err(pos.error(ParseErrorKind::UnterminatedQuote)),
+                }
+            },
+            // `\` is an ordinary character when escape directive changes it
+            '\\' => literal(&mut result, c),
+            _ => result.push(c),
+        }
+    }
+
+    Ok(result)
+}
+
+/// Parses JSON array of strings, `None` means that text is not exec form
+fn json_array(span: Span) -> Option<Vec<String>> {
+    let text = span.trim().to_string();
+    let mut chars = text.chars().peekable();
+    let mut array = Vec::new();
+
+    fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    if chars.next()? != '[' {
+        return None;
+    }
+    skip_whitespace(&mut chars);
+    if chars.peek() == Some(&']') {
+        chars.next();
+    } else {
+        loop {
+            skip_whitespace(&mut chars);
+            if chars.next()? != '"' {
+                return None;
+            }
+            array.push(json_string(&mut chars)?);
+            skip_whitespace(&mut chars);
+            match chars.next()? {
+                ',' => continue,
+                ']' => break,
+                _ => return None,
+            }
+        }
+    }
+
+    skip_whitespace(&mut chars);
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(array)
+}
+
+/// Parses JSON string body, opening quote is already consumed
+fn json_string(chars: &mut impl Iterator<Item = char>) -> Option<String> {
+    let mut result = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(result),
+            '\\' => match chars.next()? {
+                '"' => result.push('"'),
+                '\\' => result.push('\\'),
+                '/' => result.push('/'),
+                'b' => result.push('\u{8}'),
+                'f' => result.push('\u{c}'),
+                'n' => result.push('\n'),
+                'r' => result.push('\r'),
+                't' => result.push('\t'),
+                'u' => {
+                    let high = json_hex(chars)?;
+                    let code = if (0xD800..0xDC00).contains(&high) {
+                        if chars.next()? != '\\' || chars.next()? != 'u' {
+                            return None;
+                        }
+                        let low = json_hex(chars)?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return None;
+                        }
+                        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                    } else {
+                        high
+                    };
+                    result.push(std::char::from_u32(code)?);
+                }
+                _ => return None,
+            },
+            c if (c as u32) < 0x20 => return None,
+            c => result.push(c),
+        }
+    }
+}
+
+fn json_hex(chars: &mut impl Iterator<Item = char>) -> Option<u32> {
+    let digits: String = chars.take(4).collect();
+    if digits.len() != 4 {
+        return None;
+    }
+    u32::from_str_radix(&digits, 16).ok()
+}
+
+/// Parses Go duration like `1m30s` or `500ms`
+pub(crate) fn duration(s: &str) -> Option<Duration> {
+    if s == "0" {
+        return Some(Duration::from_secs(0));
+    }
+
+    // nanoseconds are summed as integers, so overflow is detected and precision is kept
+    let mut total: u128 = 0;
+    let mut rest = s;
+    if rest.is_empty() {
+        return None;
+    }
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let number = &rest[..number_end];
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let unit: u128 = match &rest[..unit_end] {
+            "ns" => 1,
+            "us" | "µs" => 1_000,
+            "ms" => 1_000_000,
+            "s" => 1_000_000_000,
+            "m" => 60_000_000_000,
+            "h" => 3_600_000_000_000,
+            _ => return None,
+        };
+        rest = &rest[unit_end..];
+        total = total.checked_add(nanos(number, unit)?)?;
+    }
+
+    let secs = total / 1_000_000_000;
+    if secs > u128::from(u64::MAX) {
+        return None;
+    }
+    Some(Duration::new(secs as u64, (total % 1_000_000_000) as u32))
+}
+
+/// Converts decimal number like `1.5` of `unit` nanoseconds, digits below nanosecond are dropped
+fn nanos(number: &str, unit: u128) -> Option<u128> {
+    let (int, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if (int.is_empty() && fraction.is_empty()) || fraction.contains('.') {
+        return None;
+    }
+    let int: u128 = if int.is_empty() { 0 } else { int.parse().ok()? };
+    let mut nanos = int.checked_mul(unit)?;
+    // unit is at most 3.6e12 ns, so 18 digits of fraction keep precision without overflow
+    let fraction = &fraction[..fraction.len().min(18)];
+    if !fraction.is_empty() {
+        let digits: u128 = fraction.parse().ok()?;
+        nanos += digits * unit / 10u128.pow(fraction.len() as u32);
+    }
+    Some(nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let content = r#"# syntax comment
+ARG VERSION=latest
+from rust:latest AS crab
+MAINTAINER lead rustacean
+# Hello, world!
+run apt-get update && \
+    # comment inside of continuation
+    apt-get install -y curl
+RUN ["/bin/bash", "-c", "echo"]
+LABEL key="value"
+EXPOSE 80 443/udp
+ENV RUST 1.0.0
+ADD --chown=rustacean /var/run /home
+COPY --from=builder --chown=rustacean:root ["/var/run", "/home"]
+VOLUME /var/run /var/www
+USER rustacean
+WORKDIR "/home/rustacean"
+ARG build=yes
+STOPSIGNAL SIGKILL
+HEALTHCHECK --interval=1m30s --retries=3 CMD curl -f http://localhost/
+SHELL ["/bin/bash", "-c"]
+ONBUILD CMD ["echo", "This is the ONBUILD command"]
+ENTRYPOINT ["cargo", "check"]
+CMD echo Hi!
+"#;
+        let docker_file = DockerFile::parse(content).unwrap();
+        assert_eq!(
+            docker_file.to_string(),
+            r#"# syntax comment
+ARG VERSION="latest"
+
+FROM rust:latest AS crab
+
+MAINTAINER lead rustacean
+
+# Hello, world!
+RUN apt-get update &&     apt-get install -y curl
+RUN ["/bin/bash", "-c", "echo"]
+LABEL key="value"
+EXPOSE 80 443/udp
+ENV RUST="1.0.0"
+ADD --chown=rustacean "/var/run" "/home"
+COPY --from=builder --chown=rustacean:root "/var/run" "/home"
+VOLUME ["/var/run", "/var/www"]
+USER rustacean
+WORKDIR "/home/rustacean"
+ARG build="yes"
+STOPSIGNAL SIGKILL
+HEALTHCHECK --interval=1m30s --retries=3 CMD curl -f http://localhost/
+SHELL ["/bin/bash", "-c"]
+
+ONBUILD CMD ["echo", "This is the ONBUILD command"]
+
+ENTRYPOINT ["cargo", "check"]
+CMD echo Hi!
+"#
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let docker_file = DockerFile::from(From {
+            image: "localhost:5000/rust".to_string(),
+            tag_or_digest: Some(Tag("1.33".to_string())),
+            name: None,
+            platform: None,
+        })
+        .comment("Hello, world!")
+        .run(vec!["/bin/bash", "-c", "echo"])
+        .env(("RUST", "1.0.0"))
+        .copy(Copy {
+            src: vec!["/var/run".into()],
+            dst: "/home".to_string(),
+            from: Some("crab".to_string()),
+            chown: None,
+            chmod: None,
+            link: false,
+            parents: false,
+            exclude: Vec::new(),
+        })
+        .work_dir("/home/rustacean")
+        .health_check(HealthCheck::Check {
+            cmd: Cmd::from("curl -f http://localhost/"),
+            interval: Some(Duration::from_secs(3661)),
+            timeout: Some(Duration::from_millis(1500)),
+            start_period: Some(Duration::from_secs(0)),
+            start_interval: Some(Duration::from_nanos(1_001)),
+            retries: Some(0),
+        })
+        .cmd(vec!["echo", "Hi!"]);
+        let content = docker_file.to_string();
+        assert_eq!(DockerFile::parse(&content).unwrap(), docker_file);
+    }
+
+    #[test]
+    fn heredocs() {
+        let content = "FROM debian\n\
+                       RUN <<EOF\napt-get update\n\napt-get install -y curl\nEOF\n\
+                       run python3 <<-'PY'\n\timport sys\n\tprint(1)\n\tPY\n\
+                       RUN <<EOF\n#!/bin/bash\necho $0\nEOF\n\
+                       COPY --chown=app <<\"CONF\" /etc/app.conf\nport = 80\nCONF\n\
+                       CMD [\"app\"]\n";
+        let docker_file = DockerFile::parse(content).unwrap();
+        let instructions = docker_file.stages()[0].instructions();
+        assert_eq!(
+            instructions[0],
+            Run::from(Heredoc::from("apt-get update\n\napt-get install -y curl\n")).into()
+        );
+        assert_eq!(
+            instructions[1],
+            Run::from(CommandForm::Heredoc {
+                command: Some("python3".to_string()),
+                heredoc: Heredoc {
+                    content: "import sys\nprint(1)\n".to_string(),
+                    delimiter: "PY".to_string(),
+                    interpreter: None,
+                },
+                suffix: None,
+            })
+            .into()
+        );
+        assert_eq!(
+            instructions[2],
+            Run::from(Heredoc {
+                content: "echo $0\n".to_string(),
+                delimiter: "EOF".to_string(),
+                interpreter: Some("/bin/bash".to_string()),
+            })
+            .into()
+        );
+        assert_eq!(
+            instructions[3],
+            Copy {
+                src: vec![Heredoc {
+                    content: "port = 80\n".to_string(),
+                    delimiter: "CONF".to_string(),
+                    interpreter: None,
+                }
+                .into()],
+                dst: "/etc/app.conf".to_string(),
+                from: None,
+                chown: Some(User {
+                    user: "app".to_string(),
+                    group: None,
+                }),
+                chmod: None,
+                link: false,
+                parents: false,
+                exclude: Vec::new(),
+            }
+            .into()
+        );
+
+        let content = docker_file.to_string();
+        assert_eq!(DockerFile::parse(&content).unwrap(), docker_file);
+
+        // marker can be followed by arguments and redirections
+        let content =
+            "FROM debian\n\nRUN <<EOT bash\nset -e\nEOT\nRUN cat <<EOF > /file\nline\nEOF\n";
+        let docker_file = DockerFile::parse(content).unwrap();
+        let instructions = docker_file.stages()[0].instructions();
+        assert_eq!(
+            instructions[0],
+            Run::from(CommandForm::Heredoc {
+                command: None,
+                heredoc: Heredoc {
+                    delimiter: "EOT".to_string(),
+                    ..Heredoc::from("set -e\n")
+                },
+                suffix: Some("bash".to_string()),
+            })
+            .into()
+        );
+        assert_eq!(
+            instructions[1],
+            Run::from(CommandForm::Heredoc {
+                command: Some("cat".to_string()),
+                heredoc: Heredoc::from("line\n"),
+                suffix: Some("> /file".to_string()),
+            })
+            .into()
+        );
+        assert_eq!(docker_file.to_string(), content);
+    }
+
+    #[test]
+    fn mounts() {
+        let content = "FROM rust\n\
+                       RUN --mount=type=cache,target=/usr/local/cargo/registry,sharing=locked \\\n\
+                           --mount=type=secret,id=token,mode=0400,required \\\n\
+                           --mount=target=/src,\"source=a,b\",rw \\\n\
+                           --network=none cargo build\n";
+        let docker_file = DockerFile::parse(content).unwrap();
+        let expected = Run {
+            mounts: vec![
+                Mount::Cache {
+                    id: None,
+                    target: "/usr/local/cargo/registry".to_string(),
+                    source: None,
+                    from: None,
+                    sharing: Some(Sharing::Locked),
+                    mode: None,
+                    uid: None,
+                    gid: None,
+                    readonly: false,
+                },
+                Mount::Secret {
+                    id: Some("token".to_string()),
+                    target: None,
+                    required: true,
+                    mode: Some(0o400),
+                    uid: None,
+                    gid: None,
+                },
+                Mount::Bind {
+                    target: "/src".to_string(),
+                    source: Some("a,b".to_string()),
+                    from: None,
+                    readwrite: true,
+                },
+            ],
+            network: Some(Network::None),
+            security: None,
+            command: CommandForm::Shell("cargo build".to_string()),
+        };
+        assert_eq!(
+            docker_file.stages()[0].instructions(),
+            &[expected.into()][..]
+        );
+        let content = docker_file.to_string();
+        assert_eq!(DockerFile::parse(&content).unwrap(), docker_file);
+
+        let err = |content: &str| DockerFile::parse(content).unwrap_err().kind;
+        assert_eq!(
+            err("FROM rust\nRUN --mount=type=cache cargo build"),
+            ParseErrorKind::InvalidValue("type=cache".to_string())
+        );
+        assert_eq!(
+            err("FROM rust\nRUN --mount=type=secret,id=a,mode=9 cat"),
+            ParseErrorKind::InvalidValue("9".to_string())
+        );
+        assert_eq!(
+            err("FROM rust\nRUN --network=bridge make"),
+            ParseErrorKind::InvalidValue("--network=bridge".to_string())
+        );
+        assert_eq!(
+            err("FROM rust\nRUN --mount=type=bind,target=/a"),
+            ParseErrorKind::MissingArguments("RUN".to_string())
+        );
+    }
+
+    #[test]
+    fn copy_flags() {
+        let content = "FROM rust\n\
+                       COPY --link --chmod=755 --exclude=*.md --exclude=target --parents . /src/\n\
+                       ADD --chmod=u+x --link=false app.tar.gz /opt/\n\
+                       ADD --checksum=sha256:24454f830c https://example.com/app.tar.gz /\n\
+                       ADD --keep-git-dir=true https://github.com/moby/buildkit.git /src\n";
+        let docker_file = DockerFile::parse(content).unwrap();
+        let instructions = docker_file.stages()[0].instructions();
+        let copy = Copy {
+            chmod: Some(Chmod::Octal(0o755)),
+            link: true,
+            parents: true,
+            exclude: vec!["*.md".to_string(), "target".to_string()],
+            ..Copy::from((".", "/src/"))
+        };
+        assert_eq!(instructions[0], copy.into());
+        let add = Add {
+            chmod: Some(Chmod::Symbolic("u+x".to_string())),
+            ..Add::from(("app.tar.gz", "/opt/"))
+        };
+        assert_eq!(instructions[1], add.into());
+        let add = Add {
+            checksum: Some("sha256:24454f830c".to_string()),
+            ..Add::from(("https://example.com/app.tar.gz", "/"))
+        };
+        assert_eq!(instructions[2], add.into());
+        let add = Add {
+            keep_git_dir: true,
+            ..Add::from(("https://github.com/moby/buildkit.git", "/src"))
+        };
+        assert_eq!(instructions[3], add.into());
+        let content = docker_file.to_string();
+        assert_eq!(DockerFile::parse(&content).unwrap(), docker_file);
+
+        let err = DockerFile::parse("FROM rust\nADD --parents a b").unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::UnknownFlag("parents".to_string()));
+        let err = DockerFile::parse("FROM rust\nCOPY --link=yes a b").unwrap_err();
+        let kind = ParseErrorKind::InvalidValue("--link=yes".to_string());
+        assert_eq!(err.kind, kind);
+    }
+
+    #[test]
+    fn sources() {
+        let content = "FROM rust\n\
+                       COPY Cargo.toml Cargo.lock ./\n\
+                       COPY [\"My Documents\", \"/docs\"]\n\
+                       ADD <<one <<two /etc/\n1\none\n2\ntwo\n";
+        let docker_file = DockerFile::parse(content).unwrap();
+        let instructions = docker_file.stages()[0].instructions();
+        let copy = Copy::from((vec!["Cargo.toml", "Cargo.lock"], "./"));
+        assert_eq!(instructions[0], copy.into());
+        assert_eq!(
+            instructions[1],
+            Copy::from(("My Documents", "/docs")).into()
+        );
+        let heredocs = vec![
+            Heredoc {
+                delimiter: "one".to_string(),
+                ..Heredoc::from("1\n")
+            },
+            Heredoc {
+                delimiter: "two".to_string(),
+                ..Heredoc::from("2\n")
+            },
+        ];
+        assert_eq!(instructions[2], Add::from((heredocs, "/etc/")).into());
+        assert_eq!(
+            docker_file.to_string(),
+            r#"FROM rust
+
+COPY "Cargo.toml" "Cargo.lock" "./"
+COPY ["My Documents", "/docs"]
+ADD <<one <<two "/etc/"
+1
+one
+2
+two
+"#
+        );
+        let content = docker_file.to_string();
+        assert_eq!(DockerFile::parse(&content).unwrap(), docker_file);
+
+        let err = DockerFile::parse("FROM rust\nCOPY --link Cargo.toml").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MissingArguments("COPY".to_string())
+        );
+    }
+
+    #[test]
+    fn stages() {
+        let content = "FROM rust AS builder\nRUN [\"cargo\", \"build\"]\nCMD [\"cargo\"]\n\
+                       FROM debian\nCOPY --from=builder /app /app\n";
+        let docker_file = DockerFile::parse(content).unwrap();
+        assert_eq!(docker_file.stages().len(), 2);
+        assert_eq!(docker_file.stages()[0].name(), Some("builder"));
+        assert_eq!(docker_file.stages()[1].base().image, "debian");
+        assert_eq!(
+            docker_file.to_string(),
+            r#"FROM rust AS builder
+
+RUN ["cargo", "build"]
+
+CMD ["cargo"]
+
+FROM debian
+
+COPY --from=builder "/app" "/app"
+"#
+        );
+    }
+
+    #[test]
+    fn from() {
+        let from = |s: &str| super::from(Text::from(s).span()).unwrap();
+        assert_eq!(from("rust").to_string(), "FROM rust");
+        assert_eq!(from("rust as crab").to_string(), "FROM rust AS crab");
+        assert_eq!(from("rust:latest").to_string(), "FROM rust:latest");
+        assert_eq!(from("rust@sha256:abc").to_string(), "FROM rust@sha256:abc");
+        assert_eq!(
+            from("--platform=$BUILDPLATFORM rust AS builder").platform,
+            Some(Platform::Variable("BUILDPLATFORM".to_string()))
+        );
+        assert_eq!(
+            from("--platform=linux/arm/v7 rust").to_string(),
+            "FROM --platform=linux/arm/v7 rust"
+        );
+        let from = from("localhost:5000/rust");
+        assert_eq!(from.image, "localhost:5000/rust");
+        assert_eq!(from.tag_or_digest, None);
+        assert_eq!(from.platform, None);
+
+        assert_eq!(
+            super::reference("${BASE:-rust:1.33}"),
+            ("${BASE:-rust:1.33}".to_string(), None)
+        );
+        assert_eq!(
+            super::reference("${REGISTRY:-localhost:5000}/rust:${TAG:-1}"),
+            (
+                "${REGISTRY:-localhost:5000}/rust".to_string(),
+                Some(Tag("${TAG:-1}".to_string()))
+            )
+        );
+        assert_eq!(
+            super::reference("${BASE:-rust@sha256:abc}"),
+            ("${BASE:-rust@sha256:abc}".to_string(), None)
+        );
+        assert_eq!(
+            super::reference("rust@${DIGEST}"),
+            ("rust".to_string(), Some(Digest("${DIGEST}".to_string())))
+        );
+
+        let err = DockerFile::parse("FROM --platform=linux/amd64").unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::MissingArguments("FROM".to_string())
+        );
+    }
+
+    #[test]
+    fn expose() {
+        let expose = |s: &str| super::expose(Text::from(s).span());
+        assert_eq!(
+            expose("8000-8010/UDP 9090/tcp 80").unwrap(),
+            Expose::from(vec![
+                Port::from((8000..=8010, Protocol::Udp)),
+                Port::from((9090, Protocol::Tcp)),
+                Port::from(80),
+            ])
+        );
+        assert_eq!(
+            expose("80/tpc").unwrap_err().kind,
+            ParseErrorKind::InvalidValue("80/tpc".to_string())
+        );
+        assert_eq!(
+            expose("80 0").unwrap_err(),
+            ParseError {
+                line: 1,
+                column: 4,
+                kind: ParseErrorKind::InvalidValue("0".to_string()),
+            }
+        );
+        assert_eq!(
+            expose("8010-8000").unwrap_err().kind,
+            ParseErrorKind::InvalidValue("8010-8000".to_string())
+        );
+        assert_eq!(
+            expose("$PORT ${P}/udp 80").unwrap(),
+            Expose {
+                ports: vec![
+                    ExposedPort::Variable("$PORT".to_string()),
+                    ExposedPort::Variable("${P}/udp".to_string()),
+                    Port::from(80).into(),
+                ]
+            }
+        );
+
+        let text = "FROM rust
+
+EXPOSE $PORT ${P}/udp 80
+";
+        assert_eq!(DockerFile::parse(text).unwrap().to_string(), text);
+    }
+
+    #[test]
+    fn pairs() {
+        let pairs = |s: &str| {
+            let pairs = super::pairs("LABEL", Text::from(s).span()).unwrap();
+            pairs.into_iter().collect::<HashMap<_, _>>()
+        };
+        let map = pairs(r#"a=1 "b c"="hello \"world\"" d='single \ quote' e=\$HOME"#);
+        assert_eq!(map["a"], Value::literal("1"));
+        assert_eq!(map["b c"], Value::literal(r#"hello "world""#));
+        assert_eq!(map["d"], Value::literal(r#"single \ quote"#));
+        assert_eq!(map["e"], Value::literal("$HOME"));
+
+        let map = pairs(r#"PATH=$PATH:'$5':"\$6\\" HOME="${HOME:-/root}""#);
+        assert_eq!(map["PATH"], Value::expand(r"$PATH:\$5:\$6\\"));
+        assert_eq!(map["HOME"], Value::expand("${HOME:-/root}"));
+
+        let map = pairs("key value with spaces");
+        assert_eq!(map["key"], Value::literal("value with spaces"));
+
+        let label = Label::from(pairs(r#"a="1 \"2\" $3 \\""#));
+        assert_eq!(label.to_string(), r#"LABEL a="1 \"2\" $3 \\""#);
+
+        let env = Env::from(super::pairs("ENV", Text::from("c=1 a=2 b=3 a=4").span()).unwrap());
+        assert_eq!(env.to_string(), r#"ENV c="1" a="4" b="3""#);
+    }
+
+    #[test]
+    fn json() {
+        let json = |s: &str| json_array(Text::from(s).span());
+        assert_eq!(json("[]"), Some(vec![]));
+        assert_eq!(
+            json(r#"[ "a\"b", "c\\d" , "é\n" ]"#),
+            Some(vec![
+                r#"a"b"#.to_string(),
+                r#"c\d"#.to_string(),
+                "é\n".to_string()
+            ])
+        );
+        assert_eq!(json(r#"["🦀"]"#), Some(vec!["🦀".to_string()]));
+        assert_eq!(json(r#"["a", ]"#), None);
+        assert_eq!(json(r#"["a"] b"#), None);
+        assert_eq!(json("echo [a]"), None);
+
+        let params = [
+            r#"sh -c "echo \"hi\"""#,
+            r"C:\Program Files\app.exe",
+            "tab\tnew\nline\u{0}\u{1f}\u{7f}",
+            "🦀",
+        ];
+        let encoded = crate::quote::exec_form(&params);
+        assert_eq!(json(&encoded).unwrap(), params);
+    }
+
+    #[test]
+    fn directives() {
+        let content = "# syntax = docker/dockerfile:1\n#escape=`\n# check=skip=StageNameCasing,JSONArgsRecommended;error=true\nFROM windows\nRUN dir C:\\ `\n  && echo \"`\"done`\"\"\nLABEL path=\"C:\\bin\"\n# escape=\\\n";
+        let docker_file = DockerFile::parse(content).unwrap();
+        assert_eq!(
+            docker_file.directives(),
+            &[
+                Directive::syntax("docker/dockerfile:1"),
+                Directive::Escape('`'),
+                Directive::Check {
+                    skip: vec!["StageNameCasing".into(), "JSONArgsRecommended".into()],
+                    error: true,
+                },
+            ]
+        );
+        let stage = &docker_file.stages()[0];
+        assert_eq!(
+            stage.instructions()[0],
+            Run::from("dir C:\\   && echo \"`\"done`\"\"").into()
+        );
+        assert_eq!(
+            stage.instructions()[1],
+            Label::from(("path", Value::literal("C:\\bin"))).into()
+        );
+        assert_eq!(stage.instructions()[2], Comment::from("escape=\\").into());
+        assert_eq!(
+            docker_file.to_string(),
+            "# syntax=docker/dockerfile:1\n# escape=`\n# check=skip=StageNameCasing,JSONArgsRecommended;error=true\n\nFROM windows\n\nRUN dir C:\\   && echo \"`\"done`\"\"\nLABEL path=\"C:\\bin\"\n# escape=\\\n"
+        );
+        assert_eq!(
+            DockerFile::parse(&docker_file.to_string()).unwrap(),
+            docker_file
+        );
+
+        // directives are read only before the first comment, blank line or instruction
+        let docker_file =
+            DockerFile::parse("# comment\n# syntax=docker/dockerfile:1\nFROM rust").unwrap();
+        assert!(docker_file.directives().is_empty());
+        assert_eq!(docker_file.global().len(), 2);
+        let docker_file = DockerFile::parse("\n# escape=`\nFROM rust\nRUN a \\\n b").unwrap();
+        assert!(docker_file.directives().is_empty());
+        assert_eq!(
+            docker_file.stages()[0].instructions()[0],
+            Run::from("a  b").into()
+        );
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(super::duration("0"), Some(Duration::from_secs(0)));
+        assert_eq!(super::duration("30s"), Some(Duration::from_secs(30)));
+        assert_eq!(super::duration("1h1m1s"), Some(Duration::from_secs(3661)));
+        assert_eq!(super::duration("1.5m"), Some(Duration::from_secs(90)));
+        assert_eq!(super::duration("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(super::duration("30"), None);
+        assert_eq!(super::duration("5d"), None);
+        assert_eq!(super::duration("1..5s"), None);
+        assert_eq!(super::duration(".5s"), Some(Duration::from_millis(500)));
+        assert_eq!(super::duration("1.000000001s"), Some(Duration::new(1, 1)));
+        assert_eq!(super::duration("99999999999999999999999h"), None);
+        assert_eq!(super::duration("5124095576030432h"), None);
+        assert_eq!(
+            super::duration("5124095576030431h"),
+            Some(Duration::from_secs(5124095576030431 * 3600))
+        );
+
+        let err =
+            DockerFile::parse("FROM rust\nHEALTHCHECK --interval=99999999999999999999999h CMD x")
+                .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidValue("--interval=99999999999999999999999h".to_string())
+        );
+
+        let err = DockerFile::parse("FROM rust\nARG I=5s\nHEALTHCHECK --interval=${I} CMD true")
+            .unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::UnsupportedVariable("--interval=${I}".to_string())
+        );
+        assert_eq!(
+            err.to_string(),
+            "3:13: variables are not expanded in `--interval=${I}`"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |s: &str| DockerFile::parse(s).unwrap_err();
+
+        assert_eq!(
+            error("FROM rust\nRUN echo\n  FETCH url"),
+            ParseError {
+                line: 3,
+                column: 3,
+                kind: ParseErrorKind::UnknownInstruction("FETCH".to_string()),
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nCOPY <<EOF <<EOF2 /x/\na\nEOF\n"),
+            ParseError {
+                line: 2,
+                column: 12,
+                kind: ParseErrorKind::UnterminatedHeredoc("EOF2".to_string()),
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nRUN \\\n  <<EOF\necho"),
+            ParseError {
+                line: 3,
+                column: 3,
+                kind: ParseErrorKind::UnterminatedHeredoc("EOF".to_string()),
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nRUN <<A <<B\na\nA\nb\nB"),
+            ParseError {
+                line: 2,
+                column: 5,
+                kind: ParseErrorKind::TooManyArguments("RUN".to_string()),
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nLABEL a=b \\\n  c=\"d"),
+            ParseError {
+                line: 3,
+                column: 5,
+                kind: ParseErrorKind::UnterminatedQuote,
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nCOPY --network=none . ."),
+            ParseError {
+                line: 2,
+                column: 6,
+                kind: ParseErrorKind::UnknownFlag("network".to_string()),
+            }
+        );
+        assert_eq!(
+            error("# comment\nARG a\nRUN echo"),
+            ParseError {
+                line: 3,
+                column: 1,
+                kind: ParseErrorKind::InstructionBeforeFrom("RUN".to_string()),
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nSHELL bash -c"),
+            ParseError {
+                line: 2,
+                column: 7,
+                kind: ParseErrorKind::InvalidJson,
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nONBUILD ONBUILD RUN echo"),
+            ParseError {
+                line: 2,
+                column: 9,
+                kind: ParseErrorKind::InvalidOnBuild("ONBUILD".to_string()),
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nWORKDIR"),
+            ParseError {
+                line: 2,
+                column: 8,
+                kind: ParseErrorKind::MissingArguments("WORKDIR".to_string()),
+            }
+        );
+        assert_eq!(
+            error("# comment\n"),
+            ParseError {
+                line: 1,
+                column: 10,
+                kind: ParseErrorKind::MissingFrom,
+            }
+        );
+        assert_eq!(
+            error("# escape=`\n  # Escape=\\\nFROM rust"),
+            ParseError {
+                line: 2,
+                column: 3,
+                kind: ParseErrorKind::DuplicateDirective("escape".to_string()),
+            }
+        );
+        assert_eq!(
+            error("FROM rust\nHEALTHCHECK --retries=-1 CMD true"),
+            ParseError {
+                line: 2,
+                column: 13,
+                kind: ParseErrorKind::InvalidValue("--retries=-1".to_string()),
+            }
+        );
+        assert_eq!(
+            error("# escape=/\nFROM rust"),
+            ParseError {
+                line: 1,
+                column: 1,
+                kind: ParseErrorKind::InvalidValue("/".to_string()),
+            }
+        );
+        assert_eq!(
+            error("# check=skip=all;warn=true\nFROM rust"),
+            ParseError {
+                line: 1,
+                column: 1,
+                kind: ParseErrorKind::InvalidValue("skip=all;warn=true".to_string()),
+            }
+        );
+    }
+
+    impl<'a> std::convert::From<&'a str> for Text {
+        fn from(s: &'a str) -> Self {
+            let chars: Vec<(char, Position)> = s
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    let pos = Position {
+                        line: 1,
+                        column: i + 1,
+                    };
+                    (c, pos)
+                })
+                .collect();
+            let end = Position {
+                line: 1,
+                column: chars.len() + 1,
+            };
+            let heredocs = Vec::new();
+            Text {
+                chars,
+                end,
+                heredocs,
+                escape: '\\',
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use std::cell::Cell;
+use std::fmt::Write;
+
+thread_local! {
+    static ESCAPE: Cell<char> = const { Cell::new('\\') };
+}
+
+/// Escape character of Dockerfile being rendered, set by `# escape=` directive
+///
+/// Only `Display` and `validate` of instructions read it, because they can't take the escape as
+/// an argument.
+pub(crate) fn escape() -> char {
+    ESCAPE.with(Cell::get)
+}
+
+/// Runs `f` with `escape` used by `Display` and `validate` of instructions, previous escape is restored even if
+/// `f` panics
+pub(crate) fn with_escape<T, F: FnOnce() -> T>(escape: char, f: F) -> T {
+    struct Restore(char);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            ESCAPE.with(|cell| cell.set(self.0));
+        }
+    }
+
+    let _restore = Restore(ESCAPE.with(|cell| cell.replace(escape)));
+    f()
+}
+
+/// Encodes string as JSON string literal including surrounding quotes
+pub(crate) fn json_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str(r#"\""#),
+            '\\' => result.push_str(r"\\"),
+            '\n' => result.push_str(r"\n"),
+            '\r' => result.push_str(r"\r"),
+            '\t' => result.push_str(r"\t"),
+            '\u{8}' => result.push_str(r"\b"),
+            '\u{c}' => result.push_str(r"\f"),
+            c if c.is_control() => {
+                let mut buf = [0; 2];
+                for unit in c.encode_utf16(&mut buf) {
+                    write!(result, r"\u{:04x}", unit).unwrap();
+                }
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Encodes arguments as JSON array used by exec form of instructions
+pub(crate) fn exec_form<S: AsRef<str>>(params: &[S]) -> String {
+    format!(
+        "[{}]",
+        params
+            .iter()
+            .map(|i| json_string(i.as_ref()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// Quotes option of `--mount` if it has commas or quotes, Docker reads options as CSV
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains(',') || s.contains('"') {
+        format!(r#""{}""#, s.replace('"', r#""""#))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Characters that can't be used in names without quoting
+fn is_special(c: char, escape: char) -> bool {
+    c.is_whitespace() || c == '=' || c == '"' || c == '\'' || c == '\\' || c == '$' || c == escape
+}
+
+/// Checks name of `ENV` or `ARG`
+pub(crate) fn is_valid_name(s: &str, escape: char) -> bool {
+    !s.is_empty() && !s.chars().any(|c| is_special(c, escape))
+}
+
+/// Checks key of `LABEL`, other special characters are quoted
+pub(crate) fn is_valid_label_key(s: &str) -> bool {
+    !s.is_empty() && !s.contains('=') && !s.contains('\n')
+}
+
+pub(crate) fn label_key(s: &str, escape: char) -> String {
+    if s.chars().any(|c| is_special(c, escape)) {
+        double_quoted(s, false, escape)
+    } else {
+        s.to_string()
+    }
+}
+
+/// Quotes value of `ENV`, `LABEL` or `ARG`
+///
+/// With `expand` unescaped `$` is left for variable expansion and `\` escapes the next character.
+/// Newlines become line continuations because Dockerfile can't hold them in values. Escapes are
+/// written with `escape` character.
+pub(crate) fn double_quoted(s: &str, expand: bool, escape: char) -> String {
+    let literal = |result: &mut String, c: char| {
+        if c == '\n' || c == '"' || c == '$' || c == escape {
+            result.push(escape);
+        }
+        result.push(c);
+    };
+
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if expand => result.push('$'),
+            '\\' if expand => literal(&mut result, chars.next().unwrap_or('\\')),
+            c => literal(&mut result, c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Quotes path of `ADD` or `COPY` written in shell form
+///
+/// `$` is left unescaped, Docker expands variables in paths of both shell and exec forms.
+pub(crate) fn path(s: &str, escape: char) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        if c == '"' || c == escape {
+            result.push(escape);
+        }
+        result.push(c);
+    }
+    result.push('"');
+    result
+}
+
+/// Checks whether expanding value has unescaped `$`
+pub(crate) fn has_expansion(s: &str) -> bool {
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '$' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Turns expanding value into literal one
+pub(crate) fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.push(chars.next().unwrap_or('\\')),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn json_string() {
+        assert_eq!(super::json_string("echo"), r#""echo""#);
+        assert_eq!(super::json_string(""), r#""""#);
+        assert_eq!(
+            super::json_string(r#"sh -c "echo \"hi\"""#),
+            r#""sh -c \"echo \\\"hi\\\"\"""#
+        );
+        assert_eq!(
+            super::json_string(r"C:\Program Files\app.exe"),
+            r#""C:\\Program Files\\app.exe""#
+        );
+        assert_eq!(
+            super::json_string(r"\\server\share\"),
+            r#""\\\\server\\share\\""#
+        );
+        assert_eq!(
+            super::json_string("line\nbreak\ttab\r\u{8}\u{c}"),
+            r#""line\nbreak\ttab\r\b\f""#
+        );
+        assert_eq!(
+            super::json_string("\u{0}\u{1b}\u{7f}"),
+            r#""\u0000\u001b\u007f""#
+        );
+        assert_eq!(super::json_string("\u{85}"), r#""\u0085""#);
+        assert_eq!(super::json_string("é 🦀 /"), r#""é 🦀 /""#);
+    }
+
+    #[test]
+    fn exec_form() {
+        assert_eq!(super::exec_form::<&str>(&[]), "[]");
+        assert_eq!(
+            super::exec_form(&["cmd", "/S", "/C", r"C:\tools\run.bat"]),
+            r#"["cmd", "/S", "/C", "C:\\tools\\run.bat"]"#
+        );
+    }
+
+    #[test]
+    fn double_quoted() {
+        assert_eq!(super::double_quoted("value", false, '\\'), r#""value""#);
+        assert_eq!(
+            super::double_quoted(r#"say "hi" to $USER \o/"#, false, '\\'),
+            r#""say \"hi\" to \$USER \\o/""#
+        );
+        assert_eq!(super::double_quoted("1\n2", false, '\\'), "\"1\\\n2\"");
+        assert_eq!(
+            super::double_quoted(r#"$PATH:${HOME}/bin \$5 \\ "q""#, true, '\\'),
+            r#""$PATH:${HOME}/bin \$5 \\ \"q\"""#
+        );
+        assert_eq!(
+            super::double_quoted(r"trailing\", true, '\\'),
+            r#""trailing\\""#
+        );
+        assert_eq!(
+            super::double_quoted("C:\\ \"`\"\n", false, '`'),
+            "\"C:\\ `\"```\"`\n\""
+        );
+    }
+
+    #[test]
+    fn with_escape() {
+        assert_eq!(super::with_escape('`', super::escape), '`');
+        assert_eq!(super::escape(), '\\');
+        let panicked = std::panic::catch_unwind(|| super::with_escape('`', || panic!("render")));
+        assert!(panicked.is_err());
+        assert_eq!(super::escape(), '\\');
+    }
+
+    #[test]
+    fn csv_field() {
+        assert_eq!(super::csv_field("target=/cache"), "target=/cache");
+        assert_eq!(super::csv_field("target=/a,b"), r#""target=/a,b""#);
+        assert_eq!(super::csv_field(r#"id=a"b"#), r#""id=a""b""#);
+    }
+
+    #[test]
+    fn keys() {
+        assert!(super::is_valid_name("PATH", '\\'));
+        assert!(super::is_valid_name("my.var-1", '\\'));
+        assert!(!super::is_valid_name("", '\\'));
+        assert!(!super::is_valid_name("A B", '\\'));
+        assert!(!super::is_valid_name("A=B", '\\'));
+        assert!(!super::is_valid_name("$A", '\\'));
+        assert!(super::is_valid_name("a`b", '\\'));
+        assert!(!super::is_valid_name("a`b", '`'));
+
+        assert!(super::is_valid_label_key("org.opencontainers.image.title"));
+        assert!(super::is_valid_label_key("with space"));
+        assert!(!super::is_valid_label_key("a=b"));
+        assert!(!super::is_valid_label_key(""));
+        assert_eq!(super::label_key("com.example", '\\'), "com.example");
+        assert_eq!(super::label_key("with space", '\\'), r#""with space""#);
+        assert_eq!(super::label_key("a`b", '\\'), "a`b");
+        assert_eq!(super::label_key("a`b", '`'), r#""a``b""#);
+    }
+
+    #[test]
+    fn expansion() {
+        assert!(super::has_expansion("$PATH"));
+        assert!(super::has_expansion(r"\$5 $PATH"));
+        assert!(!super::has_expansion(r"\$5"));
+        assert_eq!(super::unescape(r"\$5 \\ a"), r"$5 \ a");
+    }
+}
@@ -0,0 +1,2021 @@
+//! Declarative description of [`DockerFile`] in YAML or TOML, requires `spec` feature
+//!
+//! Spec has `version` of the format, which is `1`, optional `syntax` and `escape` directives,
+//! `variables`, global `args` and `stages`. Every stage has `from`, optional `name`, `platform`
+//! and `network` used by every `RUN` of the stage, and `instructions`. Instruction is a table
+//! with a single key, the lowercase keyword:
+//!
+//! * `run`, `cmd` and `entrypoint` take shell command or exec form list, `run` also takes table
+//!   with `command`, `heredoc`, `mounts`, `network` and `security`
+//! * `env` and `label` take table of values, `$VAR` references in values are expanded by Docker
+//! * `arg` takes name, list of names or table of names and defaults
+//! * `copy` and `add` take table with `src`, `dst` and flags like `chown` and `exclude`, source
+//!   is a path or a table with `heredoc` like in `run`
+//! * `expose` takes port or list of ports like `80`, `53/udp` or `8000-8010/tcp`
+//! * `volume` takes path or list of paths, `shell` takes list
+//! * `healthcheck` takes `none` or table with `cmd`, `interval`, `timeout`, `start_period`,
+//!   `start_interval` and `retries`, durations are written like `1m30s`
+//! * `onbuild` takes another instruction
+//! * `user`, `workdir`, `stopsignal`, `maintainer` and `comment` take string
+//!
+//! `{{ name }}` in any string is replaced with the value from `variables`, so `variables` must
+//! come before values that use them and can't refer to other variables. Text like `{{ .Id }}`
+//! that is not a variable name is kept. Errors point to line and column of the value that can't
+//! be used.
+//!
+//! JSON Schema of the format is returned by [`schema`].
+//! # Example
+//! ```rust
+//! use dockerfile_rs::spec;
+//!
+//! let docker_file = spec::from_yaml(r#"
+//! version: 1
+//! variables:
+//!   rust: "1.75"
+//! stages:
+//!   - from: rust:{{ rust }}
+//!     name: builder
+//!     instructions:
+//!       - workdir: /app
+//!       - copy: { src: [Cargo.toml, src], dst: ./ }
+//!       - run: cargo build --release
+//!   - from: debian:bookworm-slim
+//!     instructions:
+//!       - copy: { from: builder, src: /app/target/release/app, dst: /usr/local/bin/ }
+//!       - expose: 8080
+//!       - cmd: [app]
+//! "#).unwrap();
+//! assert_eq!(
+//!     docker_file.to_string(),
+//!     r#"FROM rust:1.75 AS builder
+//!
+//! WORKDIR "/app"
+//! COPY "Cargo.toml" "src" "./"
+//! RUN cargo build --release
+//!
+//! FROM debian:bookworm-slim
+//!
+//! COPY --from=builder "/app/target/release/app" "/usr/local/bin/"
+//! EXPOSE 8080
+//!
+//! CMD ["app"]
+//! "#
+//! );
+//!
+//! let same = spec::from_toml(r#"
+//! version = 1
+//! variables = { rust = "1.75" }
+//!
+//! [[stages]]
+//! from = "rust:{{ rust }}"
+//! name = "builder"
+//! instructions = [
+//!     { workdir = "/app" },
+//!     { copy = { src = ["Cargo.toml", "src"], dst = "./" } },
+//!     { run = "cargo build --release" },
+//! ]
+//!
+//! [[stages]]
+//! from = "debian:bookworm-slim"
+//! instructions = [
+//!     { copy = { from = "builder", src = "/app/target/release/app", dst = "/usr/local/bin/" } },
+//!     { expose = 8080 },
+//!     { cmd = ["app"] },
+//! ]
+//! "#).unwrap();
+//! assert_eq!(same, docker_file);
+//! ```
+//!
+//! [`DockerFile`]: ../struct.DockerFile.html
+//! [`schema`]: fn.schema.html
+
+use crate::{
+    parser, Add, Arg, Chmod, Cmd, CommandForm, Comment, Copy, Directive, DockerFile, EntryPoint,
+    Env, Expose, ExposedPort, From, HealthCheck, Heredoc, InstructionKind, Label, Maintainer,
+    Mount, Network, OnBuild, Platform, Port, Run, Security, Sharing, Shell, Source,
+    Stage as DockerStage, StopSignal, Value, Volume, WorkDir,
+};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{
+    de::{
+        self,
+        value::{MapAccessDeserializer, SeqAccessDeserializer},
+        DeserializeSeed, Deserializer, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+        VariantAccess, Visitor,
+    },
+    Deserialize,
+};
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    error::Error,
+    fmt::{self, Display},
+    marker::PhantomData,
+    time::Duration,
+};
+
+/// Version of the format this crate reads
+pub const VERSION: u32 = 1;
+
+/// Error of [`from_yaml`] or [`from_toml`], `line` and `column` start from 1 and are 0 if
+/// position is unknown
+///
+/// [`from_yaml`]: fn.from_yaml.html
+/// [`from_toml`]: fn.from_toml.html
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SpecError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for SpecError {}
+
+/// Reads spec written in YAML
+pub fn from_yaml(input: &str) -> Result<DockerFile, SpecError> {
+    fn error(e: serde_yaml_ng::Error) -> SpecError {
+        let (line, column) = e
+            .location()
+            .map_or((0, 0), |location| (location.line(), location.column()));
+        let message = e.to_string();
+        // location is already in `SpecError`
+        let message = match message.rfind(" at line ") {
+            Some(at) => message[..at].to_string(),
+            None => message,
+        };
+        SpecError {
+            line,
+            column,
+            message,
+        }
+    }
+
+    let spec: Spec = serde_yaml_ng::from_str(input).map_err(error)?;
+    Ok(spec.docker_file())
+}
+
+/// Reads spec written in TOML
+pub fn from_toml(input: &str) -> Result<DockerFile, SpecError> {
+    let error = |e: toml::de::Error| {
+        let (line, column) = match e.span() {
+            Some(span) => {
+                let before = &input[..span.start];
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                (
+                    before.matches('\n').count() + 1,
+                    before[line_start..].chars().count() + 1,
+                )
+            }
+            None => (0, 0),
+        };
+        SpecError {
+            line,
+            column,
+            message: e.message().to_string(),
+        }
+    };
+
+    let spec: Spec = toml::from_str(input).map_err(error)?;
+    Ok(spec.docker_file())
+}
+
+/// JSON Schema of spec, editors use it to check and complete spec files
+pub fn schema() -> String {
+    let schema = schemars::schema_for!(Spec);
+    serde_json::to_string_pretty(&schema).expect("schema is serializable")
+}
+
+/// Values of `{{ name }}` in strings
+#[derive(Default)]
+struct Variables(Vec<(String, String)>);
+
+impl Variables {
+    /// Replaces `{{ name }}` with value of variable
+    fn substitute(&self, text: &str) -> Result<String, String> {
+        let is_name = |name: &str| {
+            name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            result.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find("}}") {
+                Some(end) if is_name(after[..end].trim()) => {
+                    let name = after[..end].trim();
+                    match self.0.iter().find(|(k, _)| k == name) {
+                        Some((_, value)) => result.push_str(value),
+                        None => return Err(format!("unknown variable `{}`", name)),
+                    }
+                    rest = &after[end + 2..];
+                }
+                _ => {
+                    result.push_str("{{");
+                    rest = after;
+                }
+            }
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+}
+
+/// Wraps deserializer, visitor, seed or access, so every string read through it has variables
+/// substituted before it reaches the wrapped visitor, errors of substitution get location
+struct Substitute<'a, T> {
+    inner: T,
+    variables: &'a Variables,
+}
+
+impl<'a, T> Substitute<'a, T> {
+    fn new(inner: T, variables: &'a Variables) -> Self {
+        Substitute { inner, variables }
+    }
+
+    fn wrap<U>(&self, inner: U) -> Substitute<'a, U> {
+        Substitute::new(inner, self.variables)
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $type:ty),*)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $($arg: $type,)* visitor: V) -> Result<V::Value, D::Error> {
+                let visitor = self.wrap(visitor);
+                self.inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Substitute<'_, D> {
+    type Error = D::Error;
+
+    forward_deserialize! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($type:ty)),* $(,)?) => {
+        $(
+            fn $method<E: de::Error>(self, v: $type) -> Result<V::Value, E> {
+                self.inner.$method(v)
+            }
+        )*
+    };
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for Substitute<'_, V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    forward_visit! {
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<V::Value, E> {
+        let v = self.variables.substitute(v).map_err(E::custom)?;
+        self.inner.visit_string(v)
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<V::Value, E> {
+        self.visit_str(v)
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<V::Value, E> {
+        self.visit_str(&v)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<V::Value, E> {
+        self.inner.visit_none()
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        let deserializer = self.wrap(deserializer);
+        self.inner.visit_some(deserializer)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<V::Value, E> {
+        self.inner.visit_unit()
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<V::Value, D::Error> {
+        let deserializer = self.wrap(deserializer);
+        self.inner.visit_newtype_struct(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        let seq = self.wrap(seq);
+        self.inner.visit_seq(seq)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        let map = self.wrap(map);
+        self.inner.visit_map(map)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        let data = self.wrap(data);
+        self.inner.visit_enum(data)
+    }
+}
+
+impl<'de, S: DeserializeSeed<'de>> DeserializeSeed<'de> for Substitute<'_, S> {
+    type Value = S::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<S::Value, D::Error> {
+        let deserializer = self.wrap(deserializer);
+        self.inner.deserialize(deserializer)
+    }
+}
+
+impl<'de, A: SeqAccess<'de>> SeqAccess<'de> for Substitute<'_, A> {
+    type Error = A::Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, A::Error> {
+        let seed = self.wrap(seed);
+        self.inner.next_element_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Substitute<'_, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        let seed = self.wrap(seed);
+        self.inner.next_key_seed(seed)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        let seed = self.wrap(seed);
+        self.inner.next_value_seed(seed)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, 'de, A: EnumAccess<'de>> EnumAccess<'de> for Substitute<'a, A> {
+    type Error = A::Error;
+    type Variant = Substitute<'a, A::Variant>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), A::Error> {
+        let seed = self.wrap(seed);
+        let (value, variant) = self.inner.variant_seed(seed)?;
+        Ok((value, Substitute::new(variant, self.variables)))
+    }
+}
+
+impl<'de, A: VariantAccess<'de>> VariantAccess<'de> for Substitute<'_, A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        self.inner.unit_variant()
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, A::Error> {
+        let seed = self.wrap(seed);
+        self.inner.newtype_variant_seed(seed)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, A::Error> {
+        let visitor = self.wrap(visitor);
+        self.inner.tuple_variant(len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        let visitor = self.wrap(visitor);
+        self.inner.struct_variant(fields, visitor)
+    }
+}
+
+/// Numbers and booleans are read as text too, so `expose: 80` and `A: 1` work
+macro_rules! visit_scalars {
+    () => {
+        fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+            self.visit_str(&v.to_string())
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+            self.visit_str(&v.to_string())
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+            self.visit_str(&v.to_string())
+        }
+
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+            self.visit_str(&v.to_string())
+        }
+    };
+}
+
+/// Reads `S` and converts it into `T` inside of visitor, so errors of conversion have location
+struct Convert<S, T>(PhantomData<(S, T)>);
+
+impl<S, T> Convert<S, T> {
+    fn convert<E: de::Error>(source: Result<S, E>) -> Result<T, E>
+    where
+        T: TryFrom<S>,
+        T::Error: Display,
+    {
+        T::try_from(source?).map_err(E::custom)
+    }
+}
+
+impl<'de, S, T> Visitor<'de> for Convert<S, T>
+where
+    S: Deserialize<'de>,
+    T: TryFrom<S>,
+    T::Error: Display,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a value")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<T, E> {
+        Self::convert(S::deserialize(v.into_deserializer()))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        Self::convert(S::deserialize(v.into_deserializer()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        Self::convert(S::deserialize(v.into_deserializer()))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+        Self::convert(S::deserialize(v.into_deserializer()))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        Self::convert(S::deserialize(v.into_deserializer()))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<T, A::Error> {
+        Self::convert(S::deserialize(SeqAccessDeserializer::new(seq)))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<T, A::Error> {
+        Self::convert(S::deserialize(MapAccessDeserializer::new(map)))
+    }
+}
+
+macro_rules! deserialize_via {
+    ($type:ty, $source:ty) => {
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                deserializer.deserialize_any(Convert::<$source, Self>(PhantomData))
+            }
+        }
+    };
+}
+
+fn text_schema() -> Schema {
+    json_schema!({ "type": ["string", "number", "boolean"] })
+}
+
+/// String, number or boolean read as string
+struct Text(String);
+
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TextVisitor;
+
+        impl<'de> Visitor<'de> for TextVisitor {
+            type Value = Text;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a string")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Text, E> {
+                Ok(Text(v.to_string()))
+            }
+
+            visit_scalars!();
+        }
+
+        deserializer.deserialize_any(TextVisitor)
+    }
+}
+
+impl JsonSchema for Text {
+    fn schema_name() -> Cow<'static, str> {
+        "Text".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        text_schema()
+    }
+}
+
+/// Table that keeps order of keys
+struct Pairs<V>(Vec<(String, V)>);
+
+impl<V> Default for Pairs<V> {
+    fn default() -> Self {
+        Pairs(Vec::new())
+    }
+}
+
+impl<'de, V: Deserialize<'de>> Deserialize<'de> for Pairs<V> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PairsVisitor<V>(PhantomData<V>);
+
+        impl<'de, V: Deserialize<'de>> Visitor<'de> for PairsVisitor<V> {
+            type Value = Pairs<V>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a table")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Pairs<V>, A::Error> {
+                let mut pairs = Vec::new();
+                while let Some((Text(key), value)) = map.next_entry()? {
+                    pairs.push((key, value));
+                }
+                Ok(Pairs(pairs))
+            }
+        }
+
+        deserializer.deserialize_map(PairsVisitor(PhantomData))
+    }
+}
+
+impl<V: JsonSchema> JsonSchema for Pairs<V> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        format!("Pairs_of_{}", V::schema_name()).into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "object",
+            "additionalProperties": generator.subschema_for::<V>(),
+        })
+    }
+}
+
+/// Single value or list of values
+struct OneOrMany<T>(Vec<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for OneOrMany<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OneOrManyVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for OneOrManyVisitor<T> {
+            type Value = OneOrMany<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a value or a list")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                T::deserialize(v.into_deserializer()).map(|v| OneOrMany(vec![v]))
+            }
+
+            visit_scalars!();
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+                Vec::deserialize(SeqAccessDeserializer::new(seq)).map(OneOrMany)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                T::deserialize(MapAccessDeserializer::new(map)).map(|v| OneOrMany(vec![v]))
+            }
+        }
+
+        deserializer.deserialize_any(OneOrManyVisitor(PhantomData))
+    }
+}
+
+impl<T: JsonSchema> JsonSchema for OneOrMany<T> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        format!("OneOrMany_of_{}", T::schema_name()).into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        let one = generator.subschema_for::<T>();
+        json_schema!({
+            "anyOf": [one, { "type": "array", "items": one }],
+        })
+    }
+}
+
+/// Shell command or exec form list
+struct CommandSpec(CommandForm);
+
+struct CommandVisitor;
+
+impl<'de> Visitor<'de> for CommandVisitor {
+    type Value = CommandForm;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a shell command or a list of arguments")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<CommandForm, E> {
+        Ok(CommandForm::Shell(v.to_string()))
+    }
+
+    visit_scalars!();
+
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<CommandForm, A::Error> {
+        let params = Vec::<Text>::deserialize(SeqAccessDeserializer::new(seq))?;
+        Ok(CommandForm::Exec(params.into_iter().map(|p| p.0).collect()))
+    }
+}
+
+impl<'de> Deserialize<'de> for CommandSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(CommandVisitor)
+            .map(CommandSpec)
+    }
+}
+
+impl JsonSchema for CommandSpec {
+    fn schema_name() -> Cow<'static, str> {
+        "Command".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [text_schema(), { "type": "array", "items": text_schema() }],
+        })
+    }
+}
+
+/// Command of `RUN` or table with flags
+enum RunSpec {
+    Command(CommandForm),
+    Table(RunTable),
+}
+
+impl<'de> Deserialize<'de> for RunSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RunVisitor;
+
+        impl<'de> Visitor<'de> for RunVisitor {
+            type Value = RunSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a shell command, a list of arguments or a table")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<RunSpec, E> {
+                CommandVisitor.visit_str(v).map(RunSpec::Command)
+            }
+
+            visit_scalars!();
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<RunSpec, A::Error> {
+                CommandVisitor.visit_seq(seq).map(RunSpec::Command)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<RunSpec, A::Error> {
+                RunTable::deserialize(MapAccessDeserializer::new(map)).map(RunSpec::Table)
+            }
+        }
+
+        deserializer.deserialize_any(RunVisitor)
+    }
+}
+
+impl JsonSchema for RunSpec {
+    fn schema_name() -> Cow<'static, str> {
+        "Run".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [
+                generator.subschema_for::<CommandSpec>(),
+                generator.subschema_for::<RunTable>(),
+            ],
+        })
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct RunTable {
+    /// Shell command or exec form list, with `heredoc` it's shell command that reads it
+    command: Option<CommandSpec>,
+    /// Script run by the shell or passed to `command`
+    heredoc: Option<HeredocSpec>,
+    #[serde(default)]
+    mounts: Vec<MountSpec>,
+    network: Option<Network>,
+    security: Option<Security>,
+}
+
+/// Content of heredoc or table with `content`, `delimiter` and `interpreter`
+struct HeredocSpec(Heredoc);
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct HeredocTable {
+    content: Text,
+    /// `EOF` by default
+    delimiter: Option<Text>,
+    /// Written as shebang line, like `/usr/bin/env python3`
+    interpreter: Option<Text>,
+}
+
+impl<'de> Deserialize<'de> for HeredocSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HeredocVisitor;
+
+        impl<'de> Visitor<'de> for HeredocVisitor {
+            type Value = HeredocSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a string or a table")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<HeredocSpec, E> {
+                Ok(HeredocSpec(Heredoc::from(v)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<HeredocSpec, A::Error> {
+                let table = HeredocTable::deserialize(MapAccessDeserializer::new(map))?;
+                let mut heredoc = Heredoc::from(table.content.0);
+                if let Some(delimiter) = table.delimiter {
+                    heredoc.delimiter = delimiter.0;
+                }
+                heredoc.interpreter = table.interpreter.map(|i| i.0);
+                Ok(HeredocSpec(heredoc))
+            }
+        }
+
+        deserializer.deserialize_any(HeredocVisitor)
+    }
+}
+
+impl JsonSchema for HeredocSpec {
+    fn schema_name() -> Cow<'static, str> {
+        "Heredoc".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [{ "type": "string" }, generator.subschema_for::<HeredocTable>()],
+        })
+    }
+}
+
+/// Mode like `0400`
+#[derive(JsonSchema)]
+#[schemars(with = "Text")]
+struct Mode(u32);
+
+deserialize_via!(Mode, Text);
+
+impl TryFrom<Text> for Mode {
+    type Error = String;
+
+    fn try_from(text: Text) -> Result<Self, Self::Error> {
+        u32::from_str_radix(&text.0, 8)
+            .map(Mode)
+            .map_err(|_| format!("invalid octal mode `{}`", text.0))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MountSpec {
+    Bind {
+        target: Text,
+        source: Option<Text>,
+        from: Option<Text>,
+        #[serde(default)]
+        readwrite: bool,
+    },
+    Cache {
+        id: Option<Text>,
+        target: Text,
+        source: Option<Text>,
+        from: Option<Text>,
+        sharing: Option<Sharing>,
+        mode: Option<Mode>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        #[serde(default)]
+        readonly: bool,
+    },
+    Tmpfs {
+        target: Text,
+        /// Size in bytes
+        size: Option<u64>,
+    },
+    Secret {
+        id: Option<Text>,
+        target: Option<Text>,
+        #[serde(default)]
+        required: bool,
+        mode: Option<Mode>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    },
+    Ssh {
+        id: Option<Text>,
+        target: Option<Text>,
+        #[serde(default)]
+        required: bool,
+        mode: Option<Mode>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+    },
+}
+
+fn text(text: Option<Text>) -> Option<String> {
+    text.map(|t| t.0)
+}
+
+impl MountSpec {
+    fn mount(self) -> Mount {
+        match self {
+            MountSpec::Bind {
+                target,
+                source,
+                from,
+                readwrite,
+            } => Mount::Bind {
+                target: target.0,
+                source: text(source),
+                from: text(from),
+                readwrite,
+            },
+            MountSpec::Cache {
+                id,
+                target,
+                source,
+                from,
+                sharing,
+                mode,
+                uid,
+                gid,
+                readonly,
+            } => Mount::Cache {
+                id: text(id),
+                target: target.0,
+                source: text(source),
+                from: text(from),
+                sharing,
+                mode: mode.map(|m| m.0),
+                uid,
+                gid,
+                readonly,
+            },
+            MountSpec::Tmpfs { target, size } => Mount::Tmpfs {
+                target: target.0,
+                size,
+            },
+            MountSpec::Secret {
+                id,
+                target,
+                required,
+                mode,
+                uid,
+                gid,
+            } => Mount::Secret {
+                id: text(id),
+                target: text(target),
+                required,
+                mode: mode.map(|m| m.0),
+                uid,
+                gid,
+            },
+            MountSpec::Ssh {
+                id,
+                target,
+                required,
+                mode,
+                uid,
+                gid,
+            } => Mount::Ssh {
+                id: text(id),
+                target: text(target),
+                required,
+                mode: mode.map(|m| m.0),
+                uid,
+                gid,
+            },
+        }
+    }
+}
+
+/// Port like `80`, `53/udp` or `8000-8010/tcp`
+#[derive(JsonSchema)]
+#[schemars(with = "Text")]
+struct PortSpec(Port);
+
+deserialize_via!(PortSpec, Text);
+
+impl TryFrom<Text> for PortSpec {
+    type Error = String;
+
+    fn try_from(text: Text) -> Result<Self, Self::Error> {
+        parser::port(&text.0)
+            .map(PortSpec)
+            .ok_or_else(|| format!("invalid port `{}`", text.0))
+    }
+}
+
+/// Duration like `30s` or `1m30s`
+#[derive(JsonSchema)]
+#[schemars(with = "Text")]
+struct DurationSpec(Duration);
+
+deserialize_via!(DurationSpec, Text);
+
+impl TryFrom<Text> for DurationSpec {
+    type Error = String;
+
+    fn try_from(text: Text) -> Result<Self, Self::Error> {
+        parser::duration(&text.0)
+            .map(DurationSpec)
+            .ok_or_else(|| format!("invalid duration `{}`", text.0))
+    }
+}
+
+/// Names of `ARG`s without defaults or table of names and defaults
+struct ArgSpec(Vec<Arg>);
+
+impl<'de> Deserialize<'de> for ArgSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ArgVisitor;
+
+        impl<'de> Visitor<'de> for ArgVisitor {
+            type Value = ArgSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a name, a list of names or a table of defaults")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<ArgSpec, E> {
+                let name = v.to_string();
+                Ok(ArgSpec(vec![Arg { name, value: None }]))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<ArgSpec, A::Error> {
+                let names = Vec::<Text>::deserialize(SeqAccessDeserializer::new(seq))?;
+                let args = names.into_iter().map(|name| Arg {
+                    name: name.0,
+                    value: None,
+                });
+                Ok(ArgSpec(args.collect()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<ArgSpec, A::Error> {
+                let pairs = Pairs::<Text>::deserialize(MapAccessDeserializer::new(map))?;
+                let args = pairs
+                    .0
+                    .into_iter()
+                    .map(|(name, value)| Arg::from((name, Value::expand(value.0))));
+                Ok(ArgSpec(args.collect()))
+            }
+        }
+
+        deserializer.deserialize_any(ArgVisitor)
+    }
+}
+
+impl JsonSchema for ArgSpec {
+    fn schema_name() -> Cow<'static, str> {
+        "Arg".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } },
+                { "type": "object", "additionalProperties": text_schema() },
+            ],
+        })
+    }
+}
+
+/// `none` or table with command and options
+enum HealthCheckSpec {
+    None,
+    Check(CheckTable),
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CheckTable {
+    cmd: CommandSpec,
+    interval: Option<DurationSpec>,
+    timeout: Option<DurationSpec>,
+    start_period: Option<DurationSpec>,
+    start_interval: Option<DurationSpec>,
+    retries: Option<i32>,
+}
+
+impl<'de> Deserialize<'de> for HealthCheckSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct HealthCheckVisitor;
+
+        impl<'de> Visitor<'de> for HealthCheckVisitor {
+            type Value = HealthCheckSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "`none` or a table")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<HealthCheckSpec, E> {
+                if v.eq_ignore_ascii_case("none") {
+                    Ok(HealthCheckSpec::None)
+                } else {
+                    Err(E::invalid_value(de::Unexpected::Str(v), &self))
+                }
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<HealthCheckSpec, A::Error> {
+                CheckTable::deserialize(MapAccessDeserializer::new(map)).map(HealthCheckSpec::Check)
+            }
+        }
+
+        deserializer.deserialize_any(HealthCheckVisitor)
+    }
+}
+
+impl JsonSchema for HealthCheckSpec {
+    fn schema_name() -> Cow<'static, str> {
+        "HealthCheck".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [{ "const": "none" }, generator.subschema_for::<CheckTable>()],
+        })
+    }
+}
+
+/// Path or table with inline file in `heredoc`
+struct SourceSpec(Source);
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct SourceTable {
+    heredoc: HeredocSpec,
+}
+
+impl<'de> Deserialize<'de> for SourceSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SourceVisitor;
+
+        impl<'de> Visitor<'de> for SourceVisitor {
+            type Value = SourceSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a path or a table with `heredoc`")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<SourceSpec, E> {
+                Ok(SourceSpec(Source::Path(v.to_string())))
+            }
+
+            visit_scalars!();
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<SourceSpec, A::Error> {
+                let table = SourceTable::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(SourceSpec(Source::Heredoc(table.heredoc.0)))
+            }
+        }
+
+        deserializer.deserialize_any(SourceVisitor)
+    }
+}
+
+impl JsonSchema for SourceSpec {
+    fn schema_name() -> Cow<'static, str> {
+        "Source".into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [text_schema(), generator.subschema_for::<SourceTable>()],
+        })
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct AddSpec {
+    /// Paths relative to build context, URLs or inline files
+    src: OneOrMany<SourceSpec>,
+    dst: Text,
+    /// `user` or `user:group`
+    chown: Option<Text>,
+    /// Octal like `755` or symbolic like `u+x`
+    chmod: Option<Text>,
+    #[serde(default)]
+    link: bool,
+    /// Digest of remote source like `sha256:...`
+    checksum: Option<Text>,
+    /// Keeps `.git` directory of Git repository source
+    #[serde(default)]
+    keep_git_dir: bool,
+    #[serde(default)]
+    exclude: Vec<Text>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct CopySpec {
+    /// Paths relative to build context or to stage given in `from`, or inline files
+    src: OneOrMany<SourceSpec>,
+    dst: Text,
+    /// Stage or image to copy from
+    from: Option<Text>,
+    /// `user` or `user:group`
+    chown: Option<Text>,
+    /// Octal like `755` or symbolic like `u+x`
+    chmod: Option<Text>,
+    #[serde(default)]
+    link: bool,
+    #[serde(default)]
+    parents: bool,
+    #[serde(default)]
+    exclude: Vec<Text>,
+}
+
+fn sources(src: OneOrMany<SourceSpec>) -> Vec<Source> {
+    src.0.into_iter().map(|s| s.0).collect()
+}
+
+fn pairs(pairs: Pairs<Text>) -> Vec<(String, Value)> {
+    pairs
+        .0
+        .into_iter()
+        .map(|(k, v)| (k, Value::expand(v.0)))
+        .collect()
+}
+
+/// Table with a single key, lowercase keyword of instruction
+#[derive(JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum InstructionSpec {
+    Run(RunSpec),
+    Cmd(CommandSpec),
+    Label(Pairs<Text>),
+    Maintainer(Text),
+    Expose(OneOrMany<PortSpec>),
+    Env(Pairs<Text>),
+    Add(AddSpec),
+    Copy(CopySpec),
+    Entrypoint(CommandSpec),
+    Volume(OneOrMany<Text>),
+    User(Text),
+    Workdir(Text),
+    Arg(ArgSpec),
+    Onbuild(Box<Instruction>),
+    Stopsignal(Text),
+    Healthcheck(HealthCheckSpec),
+    Shell(Vec<Text>),
+    Comment(Text),
+}
+
+const KEYWORDS: &[&str] = &[
+    "run",
+    "cmd",
+    "label",
+    "maintainer",
+    "expose",
+    "env",
+    "add",
+    "copy",
+    "entrypoint",
+    "volume",
+    "user",
+    "workdir",
+    "arg",
+    "onbuild",
+    "stopsignal",
+    "healthcheck",
+    "shell",
+    "comment",
+];
+
+// YAML deserializer reads externally tagged enums only from tags like `!run`
+impl<'de> Deserialize<'de> for InstructionSpec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct InstructionVisitor;
+
+        impl<'de> Visitor<'de> for InstructionVisitor {
+            type Value = InstructionSpec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a table with a single instruction")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let keyword: String = match map.next_key()? {
+                    Some(keyword) => keyword,
+                    None => return Err(de::Error::invalid_length(0, &self)),
+                };
+                let instruction = match keyword.as_str() {
+                    "run" => InstructionSpec::Run(map.next_value()?),
+                    "cmd" => InstructionSpec::Cmd(map.next_value()?),
+                    "label" => InstructionSpec::Label(map.next_value()?),
+                    "maintainer" => InstructionSpec::Maintainer(map.next_value()?),
+                    "expose" => InstructionSpec::Expose(map.next_value()?),
+                    "env" => InstructionSpec::Env(map.next_value()?),
+                    "add" => InstructionSpec::Add(map.next_value()?),
+                    "copy" => InstructionSpec::Copy(map.next_value()?),
+                    "entrypoint" => InstructionSpec::Entrypoint(map.next_value()?),
+                    "volume" => InstructionSpec::Volume(map.next_value()?),
+                    "user" => InstructionSpec::User(map.next_value()?),
+                    "workdir" => InstructionSpec::Workdir(map.next_value()?),
+                    "arg" => InstructionSpec::Arg(map.next_value()?),
+                    "onbuild" => InstructionSpec::Onbuild(map.next_value()?),
+                    "stopsignal" => InstructionSpec::Stopsignal(map.next_value()?),
+                    "healthcheck" => InstructionSpec::Healthcheck(map.next_value()?),
+                    "shell" => InstructionSpec::Shell(map.next_value()?),
+                    "comment" => InstructionSpec::Comment(map.next_value()?),
+                    keyword => return Err(de::Error::unknown_variant(keyword, KEYWORDS)),
+                };
+                if let Some(keyword) = map.next_key::<String>()? {
+                    return Err(de::Error::custom(format!(
+                        "unexpected `{}`, instruction must be a table with a single key",
+                        keyword
+                    )));
+                }
+                Ok(instruction)
+            }
+        }
+
+        deserializer.deserialize_map(InstructionVisitor)
+    }
+}
+
+/// Validated instructions, `arg` can declare several of them
+#[derive(JsonSchema)]
+#[schemars(with = "InstructionSpec")]
+struct Instruction(Vec<InstructionKind>);
+
+deserialize_via!(Instruction, InstructionSpec);
+
+impl TryFrom<InstructionSpec> for Instruction {
+    type Error = String;
+
+    fn try_from(spec: InstructionSpec) -> Result<Self, Self::Error> {
+        let instruction = match spec {
+            InstructionSpec::Run(RunSpec::Command(command)) => InstructionKind::Run(Run {
+                mounts: Vec::new(),
+                network: None,
+                security: None,
+                command,
+            }),
+            InstructionSpec::Run(RunSpec::Table(table)) => {
+                let command = match (table.command, table.heredoc) {
+                    (Some(CommandSpec(command)), None) => command,
+                    (None, Some(HeredocSpec(heredoc))) => CommandForm::Heredoc {
+                        command: None,
+                        heredoc,
+                        suffix: None,
+                    },
+                    (
+                        Some(CommandSpec(CommandForm::Shell(command))),
+                        Some(HeredocSpec(heredoc)),
+                    ) => CommandForm::Heredoc {
+                        command: Some(command),
+                        heredoc,
+                        suffix: None,
+                    },
+                    (Some(_), Some(_)) => {
+                        return Err("command that reads heredoc must be a string".to_string())
+                    }
+                    (None, None) => return Err("`run` requires `command` or `heredoc`".to_string()),
+                };
+                InstructionKind::Run(Run {
+                    mounts: table.mounts.into_iter().map(MountSpec::mount).collect(),
+                    network: table.network,
+                    security: table.security,
+                    command,
+                })
+            }
+            InstructionSpec::Cmd(CommandSpec(command)) => InstructionKind::Cmd(Cmd { command }),
+            InstructionSpec::Label(label) => InstructionKind::Label(Label::from(pairs(label))),
+            InstructionSpec::Maintainer(name) => {
+                InstructionKind::Maintainer(Maintainer::from(name.0))
+            }
+            InstructionSpec::Expose(ports) => InstructionKind::Expose(Expose {
+                ports: ports
+                    .0
+                    .into_iter()
+                    .map(|p| ExposedPort::Port(p.0))
+                    .collect(),
+            }),
+            InstructionSpec::Env(env) => InstructionKind::Env(Env::from(pairs(env))),
+            InstructionSpec::Add(add) => InstructionKind::Add(Add {
+                src: sources(add.src),
+                dst: add.dst.0,
+                chown: add.chown.map(|c| parser::user(&c.0)),
+                chmod: add.chmod.map(|c| Chmod::from(c.0)),
+                link: add.link,
+                checksum: text(add.checksum),
+                keep_git_dir: add.keep_git_dir,
+                exclude: add.exclude.into_iter().map(|e| e.0).collect(),
+            }),
+            InstructionSpec::Copy(copy) => InstructionKind::Copy(Copy {
+                src: sources(copy.src),
+                dst: copy.dst.0,
+                from: text(copy.from),
+                chown: copy.chown.map(|c| parser::user(&c.0)),
+                chmod: copy.chmod.map(|c| Chmod::from(c.0)),
+                link: copy.link,
+                parents: copy.parents,
+                exclude: copy.exclude.into_iter().map(|e| e.0).collect(),
+            }),
+            InstructionSpec::Entrypoint(CommandSpec(command)) => {
+                InstructionKind::EntryPoint(EntryPoint { command })
+            }
+            InstructionSpec::Volume(paths) => InstructionKind::Volume(Volume {
+                paths: paths.0.into_iter().map(|p| p.0).collect(),
+            }),
+            InstructionSpec::User(user) => InstructionKind::User(parser::user(&user.0)),
+            InstructionSpec::Workdir(path) => InstructionKind::WorkDir(WorkDir::from(path.0)),
+            InstructionSpec::Arg(args) => {
+                let args: Vec<InstructionKind> =
+                    args.0.into_iter().map(InstructionKind::Arg).collect();
+                return validated(args);
+            }
+            InstructionSpec::Onbuild(trigger) => {
+                let triggers = trigger
+                    .0
+                    .into_iter()
+                    .map(|trigger| match trigger {
+                        InstructionKind::From(_)
+                        | InstructionKind::Maintainer(_)
+                        | InstructionKind::OnBuild(_) => Err(format!(
+                            "`{}` can't be a trigger of `ONBUILD`",
+                            trigger.to_string().split(' ').next().unwrap_or_default()
+                        )),
+                        trigger => Ok(InstructionKind::OnBuild(OnBuild {
+                            inner: Box::new(trigger),
+                        })),
+                    })
+                    .collect::<Result<_, _>>()?;
+                return Ok(Instruction(triggers));
+            }
+            InstructionSpec::Stopsignal(signal) => {
+                InstructionKind::StopSignal(StopSignal::from(signal.0))
+            }
+            InstructionSpec::Healthcheck(HealthCheckSpec::None) => {
+                InstructionKind::HealthCheck(HealthCheck::None)
+            }
+            InstructionSpec::Healthcheck(HealthCheckSpec::Check(check)) => {
+                let duration = |d: Option<DurationSpec>| d.map(|d| d.0);
+                InstructionKind::HealthCheck(HealthCheck::Check {
+                    cmd: Cmd {
+                        command: check.cmd.0,
+                    },
+                    interval: duration(check.interval),
+                    timeout: duration(check.timeout),
+                    start_period: duration(check.start_period),
+                    start_interval: duration(check.start_interval),
+                    retries: check.retries,
+                })
+            }
+            InstructionSpec::Shell(params) => InstructionKind::Shell(Shell {
+                params: params.into_iter().map(|p| p.0).collect(),
+            }),
+            InstructionSpec::Comment(comment) => InstructionKind::Comment(Comment::from(comment.0)),
+        };
+        validated(vec![instruction])
+    }
+}
+
+fn validated(instructions: Vec<InstructionKind>) -> Result<Instruction, String> {
+    for instruction in &instructions {
+        instruction.validate().map_err(|e| e.to_string())?;
+    }
+    Ok(Instruction(instructions))
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+struct StageSpec {
+    /// Base image like `rust:1.75`, `debian@sha256:...` or name of earlier stage
+    from: Text,
+    /// Name of stage given with `AS`
+    name: Option<Text>,
+    /// Platform like `linux/amd64` or `$BUILDPLATFORM`
+    platform: Option<Text>,
+    /// Network of every `RUN` in stage that has no network
+    network: Option<Network>,
+    #[serde(default)]
+    instructions: Vec<Instruction>,
+}
+
+#[derive(JsonSchema)]
+#[schemars(with = "StageSpec")]
+struct Stage(DockerStage);
+
+deserialize_via!(Stage, StageSpec);
+
+impl TryFrom<StageSpec> for Stage {
+    type Error = String;
+
+    fn try_from(spec: StageSpec) -> Result<Self, Self::Error> {
+        let (image, tag_or_digest) = parser::reference(&spec.from.0);
+        let from = From {
+            image,
+            tag_or_digest,
+            name: text(spec.name),
+            platform: spec.platform.map(|p| Platform::from(p.0)),
+        };
+        from.validate().map_err(|e| e.to_string())?;
+
+        let mut stage = DockerStage::from(from);
+        stage
+            .instructions_mut()
+            .extend(spec.instructions.into_iter().flat_map(|i| i.0));
+        if let Some(network) = spec.network {
+            stage = stage.default_network(network);
+        }
+        Ok(Stage(stage))
+    }
+}
+
+#[derive(JsonSchema)]
+#[schemars(with = "Vec<Stage>")]
+struct Stages(Vec<DockerStage>);
+
+deserialize_via!(Stages, Vec<Stage>);
+
+impl TryFrom<Vec<Stage>> for Stages {
+    type Error = &'static str;
+
+    fn try_from(stages: Vec<Stage>) -> Result<Self, Self::Error> {
+        if stages.is_empty() {
+            Err("at least one stage is required")
+        } else {
+            Ok(Stages(stages.into_iter().map(|s| s.0).collect()))
+        }
+    }
+}
+
+/// Version of the format, only `1` is supported
+struct Version;
+
+deserialize_via!(Version, u32);
+
+impl JsonSchema for Version {
+    fn schema_name() -> Cow<'static, str> {
+        "Version".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({ "const": VERSION })
+    }
+}
+
+impl TryFrom<u32> for Version {
+    type Error = String;
+
+    fn try_from(version: u32) -> Result<Self, Self::Error> {
+        if version == VERSION {
+            Ok(Version)
+        } else {
+            Err(format!(
+                "unsupported version {}, expected {}",
+                version, VERSION
+            ))
+        }
+    }
+}
+
+/// `\` or `` ` ``
+#[derive(JsonSchema)]
+#[schemars(with = "char")]
+struct Escape(char);
+
+deserialize_via!(Escape, char);
+
+impl TryFrom<char> for Escape {
+    type Error = String;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        Directive::Escape(c)
+            .validate()
+            .map(|_| Escape(c))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Validated image of `# syntax=` directive
+#[derive(JsonSchema)]
+#[schemars(with = "Text")]
+struct Syntax(Directive);
+
+deserialize_via!(Syntax, Text);
+
+impl TryFrom<Text> for Syntax {
+    type Error = String;
+
+    fn try_from(text: Text) -> Result<Self, Self::Error> {
+        let directive = Directive::syntax(text.0);
+        directive.validate().map_err(|e| e.to_string())?;
+        Ok(Syntax(directive))
+    }
+}
+
+/// Validated `ARG`s before the first `FROM`
+#[derive(JsonSchema)]
+#[schemars(with = "ArgSpec")]
+struct GlobalArgs(Vec<InstructionKind>);
+
+deserialize_via!(GlobalArgs, ArgSpec);
+
+impl TryFrom<ArgSpec> for GlobalArgs {
+    type Error = String;
+
+    fn try_from(args: ArgSpec) -> Result<Self, Self::Error> {
+        let args = args.0.into_iter().map(InstructionKind::Arg).collect();
+        validated(args).map(|args| GlobalArgs(args.0))
+    }
+}
+
+/// Spec of `Dockerfile`, compiled by dockerfile-rs
+#[derive(JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(title = "dockerfile-rs spec")]
+struct Spec {
+    // checked and used while reading
+    #[allow(dead_code)]
+    version: Version,
+    /// Values inserted into strings with `{{ name }}`
+    #[allow(dead_code)]
+    #[serde(default)]
+    variables: Pairs<Text>,
+    /// Frontend image of `# syntax=` directive, like `docker/dockerfile:1`
+    syntax: Option<Syntax>,
+    /// Escape character of `# escape=` directive
+    escape: Option<Escape>,
+    /// `ARG`s before the first `FROM`, they can be used in `from` of stages
+    args: Option<GlobalArgs>,
+    /// Build stages, the last one is the image
+    stages: Stages,
+}
+
+const FIELDS: &[&str] = &["version", "variables", "syntax", "escape", "args", "stages"];
+
+/// Reads value of field that can be given once, variables are substituted in it
+fn field<'de, A, T>(
+    map: &mut A,
+    slot: &mut Option<T>,
+    name: &'static str,
+    variables: &Variables,
+) -> Result<(), A::Error>
+where
+    A: MapAccess<'de>,
+    T: Deserialize<'de>,
+{
+    if slot.is_some() {
+        return Err(de::Error::duplicate_field(name));
+    }
+    *slot = Some(map.next_value_seed(Substitute::new(PhantomData, variables))?);
+    Ok(())
+}
+
+// variables are passed to values that come after them, so spec is read in one pass
+impl<'de> Deserialize<'de> for Spec {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SpecVisitor;
+
+        impl<'de> Visitor<'de> for SpecVisitor {
+            type Value = Spec;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a table with `version` and `stages`")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Spec, A::Error> {
+                let mut version = None;
+                let mut variables: Option<Pairs<Text>> = None;
+                let mut values = Variables::default();
+                let mut syntax = None;
+                let mut escape = None;
+                let mut args = None;
+                let mut stages = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "version" => field(&mut map, &mut version, "version", &values)?,
+                        "variables" => {
+                            if variables.is_some() {
+                                return Err(de::Error::duplicate_field("variables"));
+                            }
+                            let pairs: Pairs<Text> = map.next_value()?;
+                            values = Variables(
+                                pairs
+                                    .0
+                                    .iter()
+                                    .map(|(k, v)| (k.clone(), v.0.clone()))
+                                    .collect(),
+                            );
+                            variables = Some(pairs);
+                        }
+                        "syntax" => field(&mut map, &mut syntax, "syntax", &values)?,
+                        "escape" => field(&mut map, &mut escape, "escape", &values)?,
+                        "args" => field(&mut map, &mut args, "args", &values)?,
+                        "stages" => field(&mut map, &mut stages, "stages", &values)?,
+                        key => return Err(de::Error::unknown_field(key, FIELDS)),
+                    }
+                }
+                Ok(Spec {
+                    version: version.ok_or_else(|| de::Error::missing_field("version"))?,
+                    variables: variables.unwrap_or_default(),
+                    syntax,
+                    escape,
+                    args,
+                    stages: stages.ok_or_else(|| de::Error::missing_field("stages"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("Spec", FIELDS, SpecVisitor)
+    }
+}
+
+impl Spec {
+    fn docker_file(self) -> DockerFile {
+        let stages = self.stages.0;
+        let mut docker_file = DockerFile::from(stages[0].base().clone());
+        *docker_file.stages_mut() = stages;
+        if let Some(args) = self.args {
+            docker_file.global_mut().extend(args.0);
+        }
+        if let Some(syntax) = self.syntax {
+            docker_file = docker_file.directive(syntax.0);
+        }
+        if let Some(escape) = self.escape {
+            docker_file = docker_file.directive(Directive::Escape(escape.0));
+        }
+        docker_file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instructions() {
+        let docker_file = from_yaml(
+            r#"
+version: 1
+syntax: docker/dockerfile:1
+variables:
+  registry: ghcr.io/org
+  user: app
+args: { TAG: "1.0" }
+stages:
+  - from: "{{ registry }}/base:$TAG"
+    name: base
+    platform: $BUILDPLATFORM
+    network: none
+    instructions:
+      - arg: [TAG, OTHER]
+      - env: { Z: 1, A: "$Z:{{ user }}" }
+      - label: { org.opencontainers.image.title: "{{user}}" }
+      - run:
+          command: python3
+          heredoc: { content: "print(1)", delimiter: PY }
+          mounts:
+            - { type: cache, target: /root/.cache, sharing: locked, mode: "0755" }
+            - { type: secret, id: token, required: true }
+      - run: [docker, inspect, --format, "{{ .Id }}"]
+      - add: { src: https://example.com/a.tar.gz, dst: /tmp/, chmod: 644, checksum: "sha256:24454f830c" }
+      - copy: { src: [a, b], dst: /app/, chown: "{{ user }}:staff", link: true, exclude: ["*.md"] }
+      - expose: [80, 53/udp, 8000-8001]
+      - volume: /data
+      - user: "{{ user }}"
+      - workdir: /app
+      - stopsignal: SIGINT
+      - healthcheck: { cmd: [curl, localhost], interval: 1m30s, retries: 3 }
+      - shell: [/bin/bash, -c]
+      - onbuild: { run: make }
+      - comment: done
+      - entrypoint: exec app
+      - cmd: [--help]
+  - from: base
+    instructions:
+      - healthcheck: none
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            docker_file.to_string(),
+            r#"# syntax=docker/dockerfile:1
+
+ARG TAG="1.0"
+
+FROM --platform=$BUILDPLATFORM ghcr.io/org/base:$TAG AS base
+
+ARG TAG
+ARG OTHER
+ENV Z="1" A="$Z:app"
+LABEL org.opencontainers.image.title="app"
+RUN --mount=type=cache,target=/root/.cache,sharing=locked,mode=0755 --mount=type=secret,id=token,required --network=none python3 <<PY
+print(1)
+PY
+RUN --network=none ["docker", "inspect", "--format", "{{ .Id }}"]
+ADD --chmod=0644 --checksum=sha256:24454f830c "https://example.com/a.tar.gz" "/tmp/"
+COPY --chown=app:staff --link --exclude=*.md "a" "b" "/app/"
+EXPOSE 80 53/udp 8000-8001
+VOLUME ["/data"]
+USER app
+WORKDIR "/app"
+STOPSIGNAL SIGINT
+HEALTHCHECK --interval=1m30s --retries=3 CMD ["curl", "localhost"]
+SHELL ["/bin/bash", "-c"]
+
+ONBUILD RUN make
+
+# done
+ENTRYPOINT exec app
+CMD ["--help"]
+
+FROM base
+
+HEALTHCHECK NONE
+"#
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = |input: &str| from_yaml(input).unwrap_err();
+        assert_eq!(
+            error("version: 1\nstages:\n  - from: rust\n    instructions:\n      - expose: [80, 70000]\n"),
+            SpecError {
+                line: 5,
+                column: 22,
+                message: "stages[0].instructions[0].expose[1]: invalid port `70000`".to_string(),
+            }
+        );
+        assert_eq!(
+            error("version: 1\nstages:\n  - from: rust:{{ missing }}\n").to_string(),
+            "3:11: stages[0].from: unknown variable `missing`"
+        );
+        assert_eq!(
+            error("version: 1\nstages:\n  - from: rust:{{ late }}\nvariables: { late: 1 }\n")
+                .to_string(),
+            "3:11: stages[0].from: unknown variable `late`"
+        );
+        assert_eq!(
+            error("version: 2\nstages: []\n").to_string(),
+            "1:10: version: unsupported version 2, expected 1"
+        );
+        assert_eq!(
+            error("version: 1\nstages: []\n").to_string(),
+            "2:9: stages: at least one stage is required"
+        );
+        assert!(error(
+            "version: 1\nstages:\n  - from: rust\n    instructions:\n      - env: { \"a b\": c }\n"
+        )
+        .message
+        .contains("invalid key `a b`"));
+        assert_eq!(
+            error("version: 1\nargs: { \"a b\": 1 }\nstages:\n  - from: rust\n").to_string(),
+            "2:7: args: invalid key `a b`"
+        );
+        assert_eq!(
+            error("version: 1\nsyntax: \"a\\nRUN evil\"\nstages:\n  - from: rust\n").to_string(),
+            "2:9: syntax: invalid directive `# syntax=a\nRUN evil`"
+        );
+        assert!(error("version: 1\nstages:\n  - from: rust\n    instructions:\n      - cmd: a\n        run: b\n")
+            .message
+            .contains("instructions[0]"));
+
+        let error = from_toml(
+            "version = 1\n\n[[stages]]\nfrom = \"rust\"\ninstructions = [{ healthcheck = { cmd = \"a\", interval = \"soon\" } }]\n",
+        )
+        .unwrap_err();
+        assert_eq!((error.line, error.column), (5, 57));
+        assert_eq!(error.message, "invalid duration `soon`");
+
+        let error = |input: &str| from_toml(input).unwrap_err();
+        let stage = "version = 1\n\n[[stages]]\nfrom = \"rust\"\n";
+        assert_eq!(
+            error(&format!(
+                "{}instructions = [{{ expose = [80, \"70000\"] }}]\n",
+                stage
+            ))
+            .to_string(),
+            "5:33: invalid port `70000`"
+        );
+        assert_eq!(
+            error("version = 1\n\n[[stages]]\nfrom = \"rust:{{ missing }}\"\n").to_string(),
+            "4:8: unknown variable `missing`"
+        );
+        assert_eq!(
+            error("version = 1\nstages = []\n").to_string(),
+            "2:10: at least one stage is required"
+        );
+        assert_eq!(
+            error(&format!(
+                "{}instructions = [{{ mkdir = \"/app\" }}]\n",
+                stage
+            ))
+            .line,
+            5
+        );
+
+        let instruction = |instruction: &str| {
+            error(&format!(
+                "{}instructions = [{{ {} }}]\n",
+                stage, instruction
+            ))
+            .message
+        };
+        assert_eq!(
+            instruction("onbuild = { maintainer = \"me\" }"),
+            "`MAINTAINER` can't be a trigger of `ONBUILD`"
+        );
+        assert_eq!(
+            instruction("onbuild = { onbuild = { run = \"make\" } }"),
+            "`ONBUILD` can't be a trigger of `ONBUILD`"
+        );
+        assert_eq!(
+            instruction("run = { command = [\"python3\"], heredoc = \"print(1)\" }"),
+            "command that reads heredoc must be a string"
+        );
+        assert_eq!(
+            instruction("run = { network = \"none\" }"),
+            "`run` requires `command` or `heredoc`"
+        );
+    }
+
+    #[test]
+    fn sources() {
+        let docker_file = from_yaml(
+            r##"
+version: 1
+variables: { port: 80 }
+stages:
+  - from: nginx
+    instructions:
+      - copy: { src: { heredoc: "listen {{ port }};" }, dst: /etc/nginx/conf.d/app.conf }
+      - add:
+          src:
+            - index.html
+            - heredoc: { content: "#!/bin/sh\nnginx\n", delimiter: SH }
+          dst: /app/
+"##,
+        )
+        .unwrap();
+        assert_eq!(
+            docker_file.to_string(),
+            r#"FROM nginx
+
+COPY <<EOF "/etc/nginx/conf.d/app.conf"
+listen 80;
+EOF
+ADD "index.html" <<SH "/app/"
+#!/bin/sh
+nginx
+SH
+"#
+        );
+
+        let same = from_toml(
+            r##"
+version = 1
+variables = { port = 80 }
+
+[[stages]]
+from = "nginx"
+instructions = [
+    { copy = { src = { heredoc = "listen {{ port }};" }, dst = "/etc/nginx/conf.d/app.conf" } },
+    { add = { src = ["index.html", { heredoc = { content = "#!/bin/sh\nnginx\n", delimiter = "SH" } }], dst = "/app/" } },
+]
+"##,
+        )
+        .unwrap();
+        assert_eq!(same, docker_file);
+
+        let error = from_yaml(
+            "version: 1\nstages:\n  - from: nginx\n    instructions:\n      - copy: { src: [{ heredoc: a }, { heredoc: b }], dst: /app }\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "5:9: stages[0].instructions[0]: destination `/app` of several sources must end with `/`"
+        );
+    }
+
+    #[test]
+    fn schema() {
+        let schema: serde_json::Value = serde_json::from_str(&super::schema()).unwrap();
+        assert_eq!(schema["title"], "dockerfile-rs spec");
+        assert_eq!(schema["required"], serde_json::json!(["version", "stages"]));
+        assert_eq!(
+            super::schema(),
+            include_str!("../spec.schema.json").trim_end(),
+            "spec.schema.json is outdated, write `spec::schema()` into it"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+//! Exit codes of the command-line binary: 0 on success, 1 when check fails, 2 on errors
+
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+/// Runs binary with `input` on standard input, returns exit code and standard error
+fn run(args: &[&str], input: &str) -> (i32, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dockerfile-rs"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // binary exits without reading input on invalid arguments
+    let _ = child.stdin.take().unwrap().write_all(input.as_bytes());
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+/// Writes `content` into temporary file of integration tests, returns its path
+fn file(name: &str, content: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn render() {
+    let spec = "version: 1\nstages:\n  - from: rust:1.75\n";
+    assert_eq!(run(&["render", "-"], spec).0, 0);
+    assert_eq!(run(&["render", "--format", "toml", "-"], spec).0, 2);
+
+    let (code, stderr) = run(&["render", "-"], "version: 1\nstages: []\n");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("at least one stage is required"));
+}
+
+#[test]
+fn diff() {
+    let old = file("diff-old.Dockerfile", "FROM rust:1.75\nCMD app\n");
+    let new = file("diff-new.Dockerfile", "FROM rust:1.76\nCMD app\n");
+    let invalid = file("diff-invalid.Dockerfile", "RUN make\n");
+    assert_eq!(
+        run(&["diff", &old, "-"], "from rust:1.75\n# same\ncmd app\n").0,
+        0
+    );
+    assert_eq!(run(&["diff", &old, &new], "").0, 1);
+    assert_eq!(run(&["diff", &old, &invalid], "").0, 2);
+    assert_eq!(run(&["diff", &old, "missing.Dockerfile"], "").0, 2);
+}
+
+#[test]
+fn eval() {
+    let input = "FROM rust\nARG PORT=80\nEXPOSE $PORT\n";
+    assert_eq!(run(&["eval", "-"], input).0, 0);
+    assert_eq!(run(&["eval", "--build-arg", "PORT=8080", "-"], input).0, 0);
+    assert_eq!(run(&["eval", "--build-arg", "PORT=http", "-"], input).0, 2);
+
+    let (code, stderr) = run(&["eval", "-"], "FROM\n");
+    assert_eq!(code, 2);
+    assert!(stderr.starts_with("error: -:"));
+}
+
+#[test]
+fn lint() {
+    assert_eq!(run(&["lint", "-"], "FROM rust:1.75\nCMD app\n").0, 0);
+    assert_eq!(run(&["lint", "-"], "FROM rust\n").0, 1);
+    assert_eq!(
+        run(&["lint", "--disable", "untagged-image", "-"], "FROM rust\n").0,
+        0
+    );
+    assert_eq!(
+        run(&["lint", "--fail-on", "error", "-"], "FROM rust\n").0,
+        0
+    );
+
+    let (code, stderr) = run(&["lint", "--disable", "no-such-rule", "-"], "FROM rust\n");
+    assert_eq!(code, 2);
+    assert_eq!(stderr, "error: unknown lint rule `no-such-rule`\n");
+
+    let (code, stderr) = run(&["lint", "-"], "RUN make\n");
+    assert_eq!(code, 2);
+    assert!(stderr.contains("`RUN` is not allowed before `FROM`"));
+}
+
+#[test]
+fn fmt() {
+    assert_eq!(run(&["fmt", "--check", "-"], "FROM rust\n").0, 0);
+    assert_eq!(run(&["fmt", "--check", "-"], "from rust\n").0, 1);
+    assert_eq!(run(&["fmt", "--check", "-"], "FROM\n").0, 2);
+}